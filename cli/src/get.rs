@@ -1,6 +1,7 @@
 use std::fs::File;
+use std::io::Read;
 
-use ini_ninja::IniParser;
+use ini_ninja::{Error, FromIniStr, IniParser};
 
 static HELP_TEXT_GET: &str = "
 Usage: ini-ninja[EXE] get [OPTIONS] <SECTION> <KEY> [File]
@@ -10,18 +11,60 @@ Arguments:
                Use empty quotes for the global namespace.
                Don't include the square brackets.
     <KEY>      The key to retrieve the value for.
+    [FILE]     Path to the INI file to read.
+               Use - to read the file from stdin.
 
 Options:
-  -h, --help     Print help";
+      --type <TYPE>  Fail if the value isn't a valid TYPE: bool, int or float
+  -h, --help         Print help";
+
+/// The type the value is checked against with `--type`.
+#[derive(Clone, Copy)]
+enum ValueType {
+    Bool,
+    Int,
+    Float,
+}
+
+impl ValueType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "bool" => Some(Self::Bool),
+            "int" => Some(Self::Int),
+            "float" => Some(Self::Float),
+            _ => None,
+        }
+    }
+}
 
 struct GetArgs<'a> {
     section: Option<&'a str>,
     key: &'a str,
     path: &'a str,
+    value_type: Option<ValueType>,
 }
 
 impl<'a> GetArgs<'a> {
-    fn parse(args: &'a [String]) -> GetArgs<'a> {
+    fn parse(mut args: &'a [String]) -> GetArgs<'a> {
+        let mut value_type = None;
+        // Options come before the arguments, so keys starting with a dash are left alone.
+        while let Some((arg, rest)) = args.split_first() {
+            match arg.as_str() {
+                "--type" => {
+                    let Some((name, rest)) = rest.split_first() else {
+                        eprintln!("\"--type\" expected a type.");
+                        std::process::exit(1);
+                    };
+                    let Some(parsed) = ValueType::parse(name) else {
+                        eprintln!("Unknown type \"{name}\", expected bool, int or float.");
+                        std::process::exit(1);
+                    };
+                    value_type = Some(parsed);
+                    args = rest;
+                }
+                _ => break,
+            }
+        }
         let (section, key, file) = match args.len() {
             2 => (None, &args[0], &args[1]),
             3 => (Some(&args[0]), &args[1], &args[2]),
@@ -34,6 +77,7 @@ impl<'a> GetArgs<'a> {
             section: section.map(|x| x.as_str()),
             key,
             path: file,
+            value_type,
         }
     }
 }
@@ -43,14 +87,34 @@ pub(crate) fn command_get(args: &[String]) {
         println!("{HELP_TEXT_GET}");
         return;
     }
-    let GetArgs { section, key, path } = GetArgs::parse(args);
+    let GetArgs {
+        section,
+        key,
+        path,
+        value_type,
+    } = GetArgs::parse(args);
     let parser = IniParser::default();
-    let Ok(source) = File::open(path) else {
-        eprintln!("Failed to open file at path: {path}");
-        std::process::exit(1);
+    let source: Box<dyn Read> = if path == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        let Ok(file) = File::open(path) else {
+            eprintln!("Failed to open file at path: {path}");
+            std::process::exit(1);
+        };
+        Box::new(file)
     };
-    let value = match parser.read_value::<String>(source, section, key) {
+    let value = match value_type {
+        None => read::<String>(&parser, source, section, key),
+        Some(ValueType::Bool) => read::<bool>(&parser, source, section, key),
+        Some(ValueType::Int) => read::<i64>(&parser, source, section, key),
+        Some(ValueType::Float) => read::<f64>(&parser, source, section, key),
+    };
+    let value = match value {
         Ok(value) => value,
+        Err(Error::Parse(err)) => {
+            eprintln!("{err}: {}", err.error);
+            std::process::exit(1);
+        }
         Err(err) => {
             eprintln!("{err}");
             std::process::exit(1);
@@ -60,3 +124,14 @@ pub(crate) fn command_get(args: &[String]) {
         println!("{value}");
     }
 }
+
+/// Reads the value as `T`, returning it formatted for printing.
+fn read<T: FromIniStr + ToString>(
+    parser: &IniParser,
+    source: impl Read,
+    section: Option<&str>,
+    key: &str,
+) -> Result<Option<String>, Error> {
+    let value = parser.read_value::<T>(source, section, key)?;
+    Ok(value.map(|value| value.to_string()))
+}
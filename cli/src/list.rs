@@ -0,0 +1,72 @@
+use std::fs::File;
+
+use ini_ninja::{Error, IniParser};
+
+static HELP_TEXT_LIST: &str = "
+Usage: ini-ninja[EXE] list [OPTIONS] [SECTION] <FILE>
+
+Arguments:
+    [SECTION]  INI section to list the keys of.
+               Use empty quotes for the global namespace.
+               Don't include the square brackets.
+               Lists the section names if not provided.
+    <FILE>     Path to the INI file to read.
+
+Options:
+  -h, --help     Print help";
+
+struct ListArgs<'a> {
+    /// `None` lists the sections, `Some(None)` lists the keys in the global section.
+    section: Option<Option<&'a str>>,
+    path: &'a str,
+}
+
+impl<'a> ListArgs<'a> {
+    fn parse(args: &'a [String]) -> ListArgs<'a> {
+        let (section, file) = match args.len() {
+            1 => (None, &args[0]),
+            2 => (Some(&args[0]), &args[1]),
+            x => {
+                eprintln!("\"list\" expected 1 or 2 arguments, received {x} arguments.");
+                std::process::exit(1);
+            }
+        };
+        Self {
+            section: section.map(|x| Some(x.as_str()).filter(|x| !x.is_empty())),
+            path: file,
+        }
+    }
+}
+
+pub(crate) fn command_list(args: &[String]) {
+    if args.is_empty() | ["-h", "--help"].contains(&args[0].as_str()) {
+        println!("{HELP_TEXT_LIST}");
+        return;
+    }
+    let ListArgs { section, path } = ListArgs::parse(args);
+    let parser = IniParser::default();
+    let Ok(source) = File::open(path) else {
+        eprintln!("Failed to open file at path: {path}");
+        std::process::exit(1);
+    };
+    let names = match section {
+        None => parser.sections(source),
+        Some(section) => parser
+            .read_section(source, section)
+            .map(|pairs| pairs.into_iter().map(|(key, _)| key).collect()),
+    };
+    match names {
+        Ok(names) => {
+            for name in names {
+                println!("{name}");
+            }
+        }
+        Err(err) => {
+            match err {
+                Error::ReadIo(err) => eprintln!("Failed to read file at path {path}: {err}"),
+                err => eprintln!("{err}"),
+            }
+            std::process::exit(1);
+        }
+    }
+}
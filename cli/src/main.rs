@@ -1,6 +1,8 @@
 use get::command_get;
+use list::command_list;
 use set::command_set;
 mod get;
+mod list;
 mod set;
 
 static HELP_TEXT: &str = "
@@ -11,6 +13,7 @@ Usage: ini-ninja[EXE] [OPTIONS] <COMMAND> [ARGUMENTS]
 Commands:
     get <section> <key>          Get a value from an ini file
     set <section> <key> <value>  Set a value in the ini file
+    list [section]               List the sections, or the keys in a section
 
 Options:
   -h, --help     Print help
@@ -21,6 +24,7 @@ fn main() {
     match args.get(1).map(|x| x.as_str()) {
         Some("get") => command_get(&args[2..]),
         Some("set") => command_set(&args[2..]),
+        Some("list") => command_list(&args[2..]),
         Some("-h") | Some("--help") | None => println!("{HELP_TEXT}"),
         Some("-V") | Some("--version") => {
             println!("{}", std::env!("CARGO_PKG_VERSION"))
@@ -1,11 +1,7 @@
-use std::{
-    fs::File,
-    io::BufReader,
-    path::{Path, PathBuf},
-};
+use std::fs::File;
+use std::io::{Cursor, Read};
 
-use ini_ninja::IniParser;
-use tempfile::NamedTempFile;
+use ini_ninja::{Error, IniParser};
 
 static HELP_TEXT_SET: &str = "
 Usage: ini-ninja[EXE] set [OPTIONS] <SECTION> <KEY> <VALUE> [File]
@@ -17,8 +13,12 @@ Arguments:
     <KEY>      The key set the value for.
     [VALUE]    Value to set for the provided key.
     [FILE]     Path to the INI file to edit.
+               Use - to read the file from stdin and write the changed file to
+               stdout. Messages are printed to stderr instead of stdout.
 
 Options:
+  -n, --dry-run  Print what would change without modifying the file
+  -v, --verbose  Print the value that was replaced
   -h, --help     Print help";
 
 struct SetArgs<'a> {
@@ -26,10 +26,23 @@ struct SetArgs<'a> {
     key: &'a str,
     value: &'a str,
     path: &'a str,
+    dry_run: bool,
+    verbose: bool,
 }
 
 impl<'a> SetArgs<'a> {
-    fn parse(args: &'a [String]) -> SetArgs<'a> {
+    fn parse(mut args: &'a [String]) -> SetArgs<'a> {
+        let mut dry_run = false;
+        let mut verbose = false;
+        // Options come before the arguments, so values starting with a dash are left alone.
+        while let Some((arg, rest)) = args.split_first() {
+            match arg.as_str() {
+                "-n" | "--dry-run" => dry_run = true,
+                "-v" | "--verbose" => verbose = true,
+                _ => break,
+            }
+            args = rest;
+        }
         let (section, key, value, file) = match args.len() {
             3 => (None, &args[0], &args[1], &args[2]),
             4 => (Some(&args[0]), &args[1], &args[2], &args[3]),
@@ -43,6 +56,8 @@ impl<'a> SetArgs<'a> {
             key,
             value,
             path: file,
+            dry_run,
+            verbose,
         }
     }
 }
@@ -56,58 +71,81 @@ pub(crate) fn command_set(args: &[String]) {
         key,
         value,
         path,
+        dry_run,
+        verbose,
     } = SetArgs::parse(args);
-    let path = Path::new(path);
-    let Ok(source) = File::open(path) else {
-        eprintln!("Failed to open file at path: {}", path.to_string_lossy());
-        std::process::exit(1);
+    let parser = IniParser::default();
+    let name = match section {
+        Some(section) => format!("[{section}].{key}"),
+        None => key.to_string(),
     };
-    let mut read_buffer = BufReader::new(source);
-    let mut use_copy = false;
-
-    // We'll initially write the changes to a temporary file and rename it to the original so it's
-    // an atomic operation.
-    let temp = if cfg!(target_os = "linux") {
-        // Use the directory of the destination as temp dir to avoid
-        // invalid cross-device link error when renaming,
-        // and XDG_CACHE_DIR for fallback, failing that will fall back to using a copy instead of
-        // rename.
-        let xdg_cache = std::env::var("XDG_CACHE_DIR");
-        let xdg_cache = xdg_cache.map(PathBuf::from).ok();
-        let path = path.parent().or(xdg_cache.as_deref());
-        if let Some(path) = path {
-            NamedTempFile::new_in(path)
+    let message = |old: Option<String>| {
+        let prefix = if dry_run { "would set" } else { "set" };
+        if verbose {
+            let old = old.as_deref().unwrap_or("unset");
+            format!("{prefix} {name} = {value} (was {old})")
         } else {
-            use_copy = true;
-            NamedTempFile::new()
+            format!("{prefix} {name} = {value}")
         }
-    } else {
-        NamedTempFile::new()
     };
-    let temp = match temp {
-        Ok(temp) => temp,
-        Err(err) => {
-            eprintln!("{err}");
+    if path == "-" {
+        // Writing needs to seek back to the start of the source, which stdin can't do.
+        let mut source = Vec::new();
+        if let Err(err) = std::io::stdin().read_to_end(&mut source) {
+            eprintln!("Failed to read stdin: {err}");
+            std::process::exit(1);
+        }
+        let result = if dry_run {
+            parser.preview_write(source.as_slice(), section, key)
+        } else {
+            parser.write_value_returning_old(
+                &mut Cursor::new(source),
+                std::io::stdout().lock(),
+                section,
+                key,
+                value,
+            )
+        };
+        let old = match result {
+            Ok(old) => old,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        };
+        if dry_run || verbose {
+            eprintln!("{}", message(old));
+        }
+        return;
+    }
+    if dry_run {
+        let Ok(source) = File::open(path) else {
+            eprintln!("Failed to open file at path: {path}");
+            std::process::exit(1);
+        };
+        match parser.preview_write(source, section, key) {
+            Ok(old) => println!("{}", message(old)),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    // The library writes the changes to a temporary file and renames it over the original so it's
+    // an atomic operation. The old value comes from the same read as the write so it describes
+    // the file that was replaced.
+    match parser.update_file_returning_old(path, section, key, value) {
+        // Only say the value was set once it actually has been
+        Ok(old) if verbose => println!("{}", message(old)),
+        Ok(_) => {}
+        Err(Error::ReadIo(err)) => {
+            eprintln!("Failed to update file at path {path}: {err}");
             std::process::exit(1);
         }
-    };
-
-    let parser = IniParser::default();
-    match parser.write_value(&mut read_buffer, &temp, section, key, value) {
-        Ok(value) => value,
         Err(err) => {
             eprintln!("{err}");
             std::process::exit(1);
         }
-    };
-    // now we tell the OS to replace the original file with our modified version.
-    if let Err(err) = if use_copy {
-        std::fs::copy(temp.path(), path).map(|_| ())
-    } else {
-        std::fs::rename(temp.path(), path)
-    } {
-        eprintln!("Error while replacing original file with modified file: {err}");
-        std::process::exit(1);
     }
-    let _ = std::fs::remove_file(temp.path());
 }
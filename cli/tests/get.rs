@@ -0,0 +1,70 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn get_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ini-ninja"))
+        .args(["get", "server", "port", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"[server]\nport=8080\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "8080\n");
+}
+
+fn get_file(args: &[&str]) -> std::process::Output {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        file.path(),
+        "[server]\nport=8080\nname=ninja\ndebug=yes\nratio=0.5\n",
+    )
+    .unwrap();
+    Command::new(env!("CARGO_BIN_EXE_ini-ninja"))
+        .arg("get")
+        .args(args)
+        .arg(file.path())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn get_type() {
+    for (value_type, key, expected) in [
+        ("int", "port", "8080\n"),
+        ("bool", "debug", "true\n"),
+        ("float", "ratio", "0.5\n"),
+    ] {
+        let output = get_file(&["--type", value_type, "server", key]);
+        assert!(output.status.success(), "--type {value_type}");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    }
+}
+
+#[test]
+fn get_type_invalid() {
+    let output = get_file(&["--type", "int", "server", "name"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error parsing value \"ninja\" for key [server].name (line 3): invalid digit found in string\n"
+    );
+}
+
+#[test]
+fn get_unknown_type() {
+    let output = get_file(&["--type", "date", "server", "port"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Unknown type \"date\", expected bool, int or float.\n"
+    );
+}
@@ -0,0 +1,51 @@
+use std::process::Command;
+
+const INI: &str = "global=1\n[server]\nport=8080\nhost=localhost\n[client]\nport=9090\n";
+
+fn ini_file() -> tempfile::NamedTempFile {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), INI).unwrap();
+    file
+}
+
+fn list(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_ini-ninja"))
+        .arg("list")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn list_sections() {
+    let file = ini_file();
+    let output = list(&[file.path().to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "server\nclient\n"
+    );
+}
+
+#[test]
+fn list_keys() {
+    let file = ini_file();
+    let output = list(&["server", file.path().to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "port\nhost\n");
+
+    let output = list(&["", file.path().to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "global\n");
+}
+
+#[test]
+fn list_missing_file() {
+    let output = list(&["does-not-exist.ini"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Failed to open file at path: does-not-exist.ini\n"
+    );
+}
@@ -0,0 +1,117 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const INI: &str = "[server]\nport=8080\n";
+
+fn ini_file() -> tempfile::NamedTempFile {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), INI).unwrap();
+    file
+}
+
+#[test]
+fn set() {
+    let file = ini_file();
+    let status = Command::new(env!("CARGO_BIN_EXE_ini-ninja"))
+        .args(["set", "server", "port", "9090"])
+        .arg(file.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let contents = std::fs::read_to_string(file.path()).unwrap();
+    assert_eq!(contents, "[server]\nport=9090\n");
+}
+
+#[test]
+fn set_dry_run() {
+    let file = ini_file();
+    let output = Command::new(env!("CARGO_BIN_EXE_ini-ninja"))
+        .args(["set", "--dry-run", "--verbose", "server", "port", "9090"])
+        .arg(file.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "would set [server].port = 9090 (was 8080)\n"
+    );
+    let contents = std::fs::read_to_string(file.path()).unwrap();
+    assert_eq!(contents, INI);
+}
+
+#[test]
+fn set_verbose() {
+    let file = ini_file();
+    let output = Command::new(env!("CARGO_BIN_EXE_ini-ninja"))
+        .args(["set", "--verbose", "server", "port", "9090"])
+        .arg(file.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "set [server].port = 9090 (was 8080)\n"
+    );
+    let contents = std::fs::read_to_string(file.path()).unwrap();
+    assert_eq!(contents, "[server]\nport=9090\n");
+}
+
+#[test]
+fn set_verbose_failed_update() {
+    let file = ini_file();
+    let mut permissions = std::fs::metadata(file.path()).unwrap().permissions();
+    permissions.set_readonly(true);
+    std::fs::set_permissions(file.path(), permissions).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_ini-ninja"))
+        .args(["set", "--verbose", "server", "port", "9090"])
+        .arg(file.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    let contents = std::fs::read_to_string(file.path()).unwrap();
+    assert_eq!(contents, INI);
+}
+
+fn set_stdin(args: &[&str]) -> std::process::Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ini-ninja"))
+        .arg("set")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(INI.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn set_stdin_to_stdout() {
+    let output = set_stdin(&["server", "port", "9090", "-"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[server]\nport=9090\n"
+    );
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn set_stdin_verbose() {
+    let output = set_stdin(&["-v", "server", "port", "9090", "-"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[server]\nport=9090\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "set [server].port = 9090 (was 8080)\n"
+    );
+}
@@ -0,0 +1,104 @@
+//! Benchmarks on a generated file that's several megabytes, to keep an eye on how the crate
+//! handles large files.
+//!
+//! Each group compares looking up a key at the start of the file with one at the end. Reads that
+//! can stop at the first match should be much faster for the first key, if they're not, the early
+//! exit has regressed.
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use ini_ninja::{DuplicateKeyStrategy, IniParser};
+use std::hint::black_box;
+use std::io::Cursor;
+
+const SECTIONS: usize = 20_000;
+const KEYS_PER_SECTION: usize = 10;
+
+fn large_ini() -> String {
+    let mut ini = String::from("; generated for benchmarks\n");
+    for section in 0..SECTIONS {
+        ini.push_str(&format!("\n[section_{section}]\n"));
+        for key in 0..KEYS_PER_SECTION {
+            ini.push_str(&format!("key_{key} = value {section} {key} # comment\n"));
+        }
+    }
+    ini
+}
+
+/// The first and last key in the file.
+fn lookups() -> [(&'static str, String); 2] {
+    [
+        ("first", "section_0".to_string()),
+        ("last", format!("section_{}", SECTIONS - 1)),
+    ]
+}
+
+fn read_value(c: &mut Criterion) {
+    let ini = large_ini();
+    let mut group = c.benchmark_group("read_value");
+    for (strategy_name, strategy) in [
+        ("use_first", DuplicateKeyStrategy::UseFirst),
+        ("use_last", DuplicateKeyStrategy::UseLast),
+    ] {
+        let parser = IniParser {
+            duplicate_keys: strategy,
+            ..Default::default()
+        };
+        for (position, section) in lookups() {
+            group.bench_function(BenchmarkId::new(strategy_name, position), |b| {
+                b.iter(|| {
+                    parser
+                        .read_value::<String>(ini.as_bytes(), Some(black_box(&section)), "key_0")
+                        .unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn contains_key(c: &mut Criterion) {
+    let ini = large_ini();
+    let parser = IniParser::default();
+    let mut group = c.benchmark_group("contains_key");
+    for (position, section) in lookups() {
+        group.bench_function(position, |b| {
+            b.iter(|| {
+                parser
+                    .contains_key(ini.as_bytes(), Some(black_box(&section)), "key_0")
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn write_value(c: &mut Criterion) {
+    let ini = large_ini();
+    let parser = IniParser::default();
+    let mut group = c.benchmark_group("write_value");
+    for (position, section) in lookups() {
+        group.bench_function(position, |b| {
+            b.iter(|| {
+                let mut source = Cursor::new(ini.as_bytes());
+                let mut destination = Vec::with_capacity(ini.len());
+                parser
+                    .write_value(
+                        &mut source,
+                        &mut destination,
+                        Some(black_box(&section)),
+                        "key_0",
+                        "new value",
+                    )
+                    .unwrap();
+                destination
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = read_value, contains_key, write_value
+}
+criterion_main!(benches);
@@ -0,0 +1,167 @@
+use crate::{DuplicateKeyStrategy, IniParser, NewKeyPosition, Newline, QuoteStyle, WriteLogFormat};
+use std::borrow::Cow;
+
+/// Builds an [`IniParser`], starting from the defaults. Prefer this over struct update syntax
+/// so your code keeps working as new settings are added.
+///
+/// ```
+/// # use ini_ninja::{DuplicateKeyStrategy, IniParser};
+/// # fn main () -> Result<(), ini_ninja::Error> {
+/// let parser = IniParser::builder()
+///     .comment_delimiters(vec![';'])
+///     .case_sensitive(false)
+///     .duplicate_keys(DuplicateKeyStrategy::UseFirst)
+///     .build();
+///
+/// let ini = "[/Script/Engine.GameSession]\nmaxplayers=40\nMaxPlayers=20 ; ignored\n";
+/// let max_players: Option<usize> = parser
+///    .read_value(ini.as_bytes(), Some("/Script/Engine.GameSession"), "MaxPlayers")?;
+///
+/// assert_eq!(max_players, Some(40));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct IniParserBuilder<'a> {
+    parser: IniParser<'a>,
+}
+
+/// Generates a setter for each field of [`IniParser`].
+macro_rules! setters {
+    ($($field:ident: $type:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Sets [`IniParser::", stringify!($field), "`].")]
+            pub fn $field(mut self, $field: $type) -> Self {
+                self.parser.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+impl<'a> IniParserBuilder<'a> {
+    /// Sets [`IniParser::comment_delimiters`].
+    pub fn comment_delimiters(mut self, comment_delimiters: impl Into<Cow<'a, [char]>>) -> Self {
+        self.parser.comment_delimiters = comment_delimiters.into();
+        self
+    }
+
+    /// Sets [`IniParser::line_comment_delimiters`].
+    pub fn line_comment_delimiters(
+        mut self,
+        line_comment_delimiters: impl Into<Cow<'a, [char]>>,
+    ) -> Self {
+        self.parser.line_comment_delimiters = Some(line_comment_delimiters.into());
+        self
+    }
+
+    /// Sets [`IniParser::inline_comment_delimiters`].
+    pub fn inline_comment_delimiters(
+        mut self,
+        inline_comment_delimiters: impl Into<Cow<'a, [char]>>,
+    ) -> Self {
+        self.parser.inline_comment_delimiters = Some(inline_comment_delimiters.into());
+        self
+    }
+
+    /// Sets [`IniParser::value_start_delimiters`].
+    pub fn value_start_delimiters(
+        mut self,
+        value_start_delimiters: impl Into<Cow<'a, [char]>>,
+    ) -> Self {
+        self.parser.value_start_delimiters = value_start_delimiters.into();
+        self
+    }
+
+    setters! {
+        trailing_comments: bool,
+        line_continuation: bool,
+        continuation_char: char,
+        duplicate_keys: DuplicateKeyStrategy,
+        blank_line_before_section: bool,
+        bool_true_values: &'a [&'a str],
+        bool_false_values: &'a [&'a str],
+        file_ref_prefix: Option<char>,
+        write_log_format: WriteLogFormat,
+        triple_quote_strings: bool,
+        newline: Newline,
+        new_key_position: NewKeyPosition,
+        sort_keys_on_normalize: bool,
+        align_equals: bool,
+        empty_header_is_global: bool,
+        quote_chars: &'a [char],
+        env_separator: char,
+        case_sensitive: bool,
+        escape_sequences: bool,
+        group_separators: &'a [char],
+        max_bytes: Option<u64>,
+        value_on_next_line_when_empty: bool,
+        path_separator: char,
+        section_separator: char,
+        create_parent_sections: bool,
+        require_whitespace_before_comment: bool,
+        collapse_whitespace: bool,
+        flag_keys_as_true: bool,
+        strict_sections: bool,
+        allow_indented_sections: bool,
+        quote_values: QuoteStyle,
+        durable: bool,
+        multiline_quoted_values: bool,
+    }
+
+    /// Finishes building the parser.
+    pub fn build(self) -> IniParser<'a> {
+        self.parser
+    }
+}
+
+impl<'a> IniParser<'a> {
+    /// Start building a parser with [`IniParserBuilder`].
+    pub fn builder() -> IniParserBuilder<'a> {
+        IniParserBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_defaults() {
+        assert!(IniParser::builder().build() == IniParser::default());
+    }
+
+    #[test]
+    fn builder_sets_fields() {
+        let parser = IniParser::builder()
+            .value_start_delimiters(&[':'][..])
+            .case_sensitive(false)
+            .max_bytes(Some(1024))
+            .build();
+        assert!(
+            parser
+                == IniParser {
+                    value_start_delimiters: Cow::Borrowed(&[':']),
+                    case_sensitive: false,
+                    max_bytes: Some(1024),
+                    ..Default::default()
+                }
+        );
+    }
+
+    #[test]
+    fn builder_sets_comment_delimiters() {
+        let parser = IniParser::builder()
+            .line_comment_delimiters(vec![';'])
+            .inline_comment_delimiters(&['#'][..])
+            .build();
+        assert!(
+            parser
+                == IniParser {
+                    line_comment_delimiters: Some(Cow::Owned(vec![';'])),
+                    inline_comment_delimiters: Some(Cow::Borrowed(&['#'])),
+                    ..Default::default()
+                }
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use crate::{FromIniStr, IniParser, error::Error};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use std::io::Read;
+
+#[cfg(feature = "async")]
+use tokio::io::AsyncRead;
+
+impl IniParser<'_> {
+    /// Read a value into any type that implements [`serde::Deserialize`], without having to
+    /// implement [`FromIniStr`] for it. See [`Deserialized`] for the types that are supported.
+    /// If section is none, it will look in the global space.
+    pub fn read_value_de<T>(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let value = self.read_value::<Deserialized<T>>(source, section, key)?;
+        Ok(value.map(|value| value.0))
+    }
+
+    /// Async version of [`IniParser::read_value_de`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_de_async<T>(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let value = self
+            .read_value_async::<Deserialized<T>>(source, section, key)
+            .await?;
+        Ok(value.map(|value| value.0))
+    }
+}
+
+/// Reads any type that implements [`serde::Deserialize`] from a single value, so it can be used
+/// anywhere a [`FromIniStr`] is expected, e.g. `Vec<Deserialized<LogLevel>>` with
+/// [`IniParser::read_list`].
+///
+/// Scalars are parsed the same way their [`FromIniStr`] implementations parse them, and enums
+/// with unit variants are read from the variant's name, e.g. `level=Debug`. Structs, maps and
+/// sequences aren't supported since a value can't contain them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Deserialized<T>(pub T);
+
+impl<T: DeserializeOwned> FromIniStr for Deserialized<T> {
+    type Err = DeserializeError;
+    fn from_ini_str(ini_str: &str) -> Result<Self, Self::Err> {
+        Self::from_ini_str_with_parser(ini_str, &IniParser::default())
+    }
+
+    fn from_ini_str_with_parser(ini_str: &str, parser: &IniParser) -> Result<Self, Self::Err> {
+        T::deserialize(ValueDeserializer {
+            value: ini_str,
+            parser,
+        })
+        .map(Deserialized)
+    }
+}
+
+/// The value couldn't be deserialized into the requested type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeserializeError(String);
+
+impl std::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+impl de::Error for DeserializeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// Deserializes a single value as it appears in the ini file.
+struct ValueDeserializer<'v, 'p> {
+    value: &'v str,
+    parser: &'p IniParser<'p>,
+}
+
+impl ValueDeserializer<'_, '_> {
+    fn parse<T: FromIniStr>(&self) -> Result<T, DeserializeError> {
+        T::from_ini_str_with_parser(self.value, self.parser).map_err(de::Error::custom)
+    }
+}
+
+/// Generates `deserialize_*` methods that parse the value with its [`FromIniStr`] implementation.
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident($type:ty)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.$visit(self.parse::<$type>()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'_, '_> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let value = self
+            .parser
+            .bool_from_str(self.value)
+            .map_err(de::Error::custom)?;
+        visitor.visit_bool(value)
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_i128 => visit_i128(i128),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_u128 => visit_u128(u128),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+        deserialize_char => visit_char(char),
+        deserialize_str => visit_string(String),
+        deserialize_string => visit_string(String),
+        deserialize_identifier => visit_string(String),
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let variant: String = self.parse()?;
+        visitor.visit_enum(variant.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        bytes byte_buf seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum LogLevel {
+        Debug,
+        Info,
+        #[serde(rename = "warn")]
+        Warning,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Port(u16);
+
+    const INI: &str = indoc::indoc! {r#"
+        [log]
+        level = Info
+        quoted = "warn"
+        other = Trace
+        [server]
+        port = 8080
+        enabled = on
+        ratio = 0.5
+        name = "ninja"
+    "#};
+
+    #[test]
+    fn read_value_de_enum() {
+        let parser = IniParser::default();
+        let read = |key| parser.read_value_de::<LogLevel>(INI.as_bytes(), Some("log"), key);
+        assert_eq!(read("level").unwrap(), Some(LogLevel::Info));
+        assert_eq!(read("quoted").unwrap(), Some(LogLevel::Warning));
+        assert_eq!(read("missing").unwrap(), None);
+        let err = read("other").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error parsing value \"Trace\" for key [log].other (line 4)"
+        );
+        assert_matches::assert_matches!(err, Error::Parse(_));
+    }
+
+    #[test]
+    fn read_value_de_scalars() {
+        let parser = IniParser {
+            bool_true_values: &["enabled"],
+            ..Default::default()
+        };
+        let source = INI.as_bytes();
+        let server = Some("server");
+        assert_eq!(
+            parser.read_value_de::<u16>(source, server, "port").unwrap(),
+            Some(8080)
+        );
+        assert_eq!(
+            parser
+                .read_value_de::<Port>(source, server, "port")
+                .unwrap(),
+            Some(Port(8080))
+        );
+        assert_eq!(
+            parser
+                .read_value_de::<bool>(source, server, "enabled")
+                .unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            parser
+                .read_value_de::<f64>(source, server, "ratio")
+                .unwrap(),
+            Some(0.5)
+        );
+        assert_eq!(
+            parser
+                .read_value_de::<Option<String>>(source, server, "name")
+                .unwrap(),
+            Some(Some("ninja".to_string()))
+        );
+        assert!(parser.read_value_de::<u8>(source, server, "port").is_err());
+    }
+
+    #[test]
+    fn deserialized_list() {
+        let parser = IniParser::default();
+        let levels = parser
+            .read_list::<Deserialized<LogLevel>>(
+                "levels = Debug,Info".as_bytes(),
+                None,
+                "levels",
+                ',',
+            )
+            .unwrap();
+        assert_eq!(
+            levels,
+            Some(vec![
+                Deserialized(LogLevel::Debug),
+                Deserialized(LogLevel::Info)
+            ])
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_de_async() {
+        let parser = IniParser::default();
+        let level = parser
+            .read_value_de_async::<LogLevel>(INI.as_bytes(), Some("log"), "level")
+            .await
+            .unwrap();
+        assert_eq!(level, Some(LogLevel::Info));
+    }
+}
@@ -6,13 +6,66 @@ pub enum Error {
     DuplicateKey {
         key: String,
         section: Option<String>,
+        /// The line the duplicate was found on, starting at 1.
+        line: usize,
+    },
+    Parse(ParseError),
+    /// More bytes were read than [`IniParser::max_bytes`](crate::IniParser::max_bytes) allows.
+    TooLarge {
+        limit: u64,
+        /// How many bytes had been read when the limit was exceeded.
+        found: u64,
+    },
+    /// The source isn't valid UTF-8.
+    InvalidUtf8 {
+        /// Where the first invalid byte is in the source.
+        byte_offset: usize,
+    },
+    /// A quoted value was never closed. Only returned when
+    /// [`IniParser::multiline_quoted_values`](crate::IniParser::multiline_quoted_values) is
+    /// enabled.
+    UnterminatedQuote {
+        /// The line the value starts on, starting at 1.
+        line: usize,
+    },
+    /// A file name for [`IniParser::split_sections`](crate::IniParser::split_sections) isn't a
+    /// single file in the output directory, e.g. it contains `..` or a path separator.
+    InvalidFileName {
+        /// The section the file is for, `None` for the global section.
+        section: Option<String>,
+        name: String,
     },
-    Parse(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// A value in the ini file that couldn't be parsed into the requested type.
+#[derive(Debug)]
+pub struct ParseError {
+    /// The line the value is on, starting at 1.
+    pub line: usize,
+    /// The section the key is in, `None` for the global section.
+    pub section: Option<String>,
+    pub key: String,
+    /// The value as it appears in the file.
+    pub value: String,
+    /// The error returned while parsing the value.
+    pub error: Box<dyn std::error::Error + Send + Sync>,
 }
 
 impl Error {
-    pub(crate) fn new_parse<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self {
-        Self::Parse(Box::new(err))
+    pub(crate) fn new_parse<E: std::error::Error + Send + Sync + 'static>(
+        err: E,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+        line: usize,
+    ) -> Self {
+        Self::Parse(ParseError {
+            line,
+            section: section.map(|s| s.to_owned()),
+            key: key.to_owned(),
+            value: value.to_owned(),
+            error: Box::new(err),
+        })
     }
 }
 
@@ -21,7 +74,11 @@ impl std::error::Error for Error {
         match self {
             Error::ReadIo(source) => Option::Some(source),
             Error::DuplicateKey { .. } => Option::None,
-            Error::Parse(err) => Some(err.as_ref()),
+            Error::Parse(err) => Some(err.error.as_ref()),
+            Error::TooLarge { .. }
+            | Error::InvalidUtf8 { .. }
+            | Error::UnterminatedQuote { .. }
+            | Error::InvalidFileName { .. } => None,
         }
     }
 }
@@ -30,10 +87,14 @@ impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::core::fmt::Result {
         match self {
             Error::ReadIo(_) => f.write_str("IO error while reading file"),
-            Error::DuplicateKey { key: name, section } => {
+            Error::DuplicateKey {
+                key: name,
+                section,
+                line,
+            } => {
                 write!(
                     f,
-                    "duplicate key {}{} found in ini file",
+                    "duplicate key {}{} found in ini file (line {line})",
                     section
                         .clone()
                         .map(|s| format!("[{s}]."))
@@ -41,13 +102,79 @@ impl std::fmt::Display for Error {
                     name
                 )
             }
-            Error::Parse(_) => f.write_str("error while parsing value"),
+            Error::Parse(err) => err.fmt(f),
+            Error::TooLarge { limit, found } => write!(
+                f,
+                "read {found} bytes, which is more than the limit of {limit} bytes"
+            ),
+            Error::InvalidUtf8 { byte_offset } => {
+                write!(f, "invalid UTF-8 at byte {byte_offset}")
+            }
+            Error::UnterminatedQuote { line } => {
+                write!(f, "quoted value starting on line {line} is never closed")
+            }
+            Error::InvalidFileName { section, name } => write!(
+                f,
+                "file name {name:?} for {} is outside of the output directory",
+                section
+                    .as_ref()
+                    .map(|s| format!("section [{s}]"))
+                    .unwrap_or_else(|| "the global section".to_owned())
+            ),
         }
     }
 }
 
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::core::fmt::Result {
+        write!(
+            f,
+            "error parsing value {:?} for key {}{} (line {})",
+            self.value,
+            self.section
+                .as_ref()
+                .map(|s| format!("[{s}]."))
+                .unwrap_or_default(),
+            self.key,
+            self.line
+        )
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(source: io::Error) -> Self {
         Error::ReadIo(source)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn error_is_send_sync() {
+        is_send_sync::<Error>();
+        is_send_sync::<ParseError>();
+        is_send_sync::<Result<String, Error>>();
+        is_send_sync::<<u16 as crate::FromIniStr>::Err>();
+    }
+
+    #[test]
+    fn error_can_be_boxed_across_threads() {
+        let err = Error::TooLarge { limit: 1, found: 2 };
+        let boxed: Box<dyn std::error::Error + Send + Sync> = Box::new(err);
+        let message = std::thread::spawn(move || boxed.to_string()).join();
+        assert!(matches!(
+            message.as_deref(),
+            Ok("read 2 bytes, which is more than the limit of 1 bytes")
+        ));
+    }
+}
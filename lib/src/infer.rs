@@ -0,0 +1,268 @@
+use crate::{DuplicateKeyStrategy, IniParser, QuoteStyle, error::Error};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::Read;
+
+/// Comment delimiters that [`IniParser::infer`] looks for.
+const COMMENT_DELIMITERS: [char; 2] = ['#', ';'];
+/// Value delimiters that [`IniParser::infer`] looks for.
+const VALUE_DELIMITERS: [char; 2] = ['=', ':'];
+
+impl IniParser<'static> {
+    /// Reads the source and returns a parser set up to match its conventions, so edits made with
+    /// it look like the rest of the file. Settings that can't be told from the file are left at
+    /// their defaults.
+    ///
+    /// - [`IniParser::comment_delimiters`] and [`IniParser::value_start_delimiters`] are the ones
+    ///   used in the file, most used first.
+    /// - [`IniParser::line_continuation`] and [`IniParser::triple_quote_strings`] are enabled if
+    ///   any line uses them.
+    /// - [`IniParser::quote_values`] is [`QuoteStyle::Always`] if most values are quoted, and `'`
+    ///   is added to [`IniParser::quote_chars`] if any values are quoted with it.
+    /// - [`IniParser::duplicate_keys`] is [`DuplicateKeyStrategy::UseLast`] if a key appears more
+    ///   than once in a section, otherwise the faster [`DuplicateKeyStrategy::UseFirst`].
+    /// - [`IniParser::blank_line_before_section`] is enabled if every section header after the
+    ///   first line has a blank line before it.
+    /// - [`IniParser::flag_keys_as_true`] is enabled if there are keys without a value delimiter.
+    pub fn infer(source: impl Read) -> Result<IniParser<'static>, Error> {
+        let mut source = std::io::BufReader::new(source);
+        // Lines joined by continuation or triple quotes are looked at as one line
+        let reader = IniParser {
+            line_continuation: true,
+            triple_quote_strings: true,
+            ..Default::default()
+        };
+        let mut conventions = Conventions::default();
+        let mut line = String::new();
+        let mut bytes_processed = 0;
+        loop {
+            line.clear();
+            let bytes_read = reader.read_raw_line(&mut source, &mut line, bytes_processed)?;
+            if bytes_read == 0 {
+                break;
+            }
+            bytes_processed += bytes_read;
+            conventions.process_line(&reader, &line);
+        }
+        Ok(conventions.parser())
+    }
+}
+
+/// What [`IniParser::infer`] has seen in the file so far.
+#[derive(Default)]
+struct Conventions {
+    /// How many comments start with each delimiter, in the order they were first seen.
+    comments: Vec<(char, usize)>,
+    /// How many keys are followed by each delimiter, in the order they were first seen.
+    delimiters: Vec<(char, usize)>,
+    continued_lines: usize,
+    values: usize,
+    double_quoted: usize,
+    single_quoted: usize,
+    triple_quoted: usize,
+    flags: usize,
+    duplicates: usize,
+    /// Section headers that aren't on the first line.
+    later_sections: usize,
+    later_sections_after_blank: usize,
+    section: Option<String>,
+    keys: HashSet<(Option<String>, String)>,
+    seen_lines: bool,
+    previous_blank: bool,
+}
+
+impl Conventions {
+    fn process_line(&mut self, reader: &IniParser, line: &str) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            self.previous_blank = true;
+            return;
+        }
+        if line.lines().any(|l| l.trim_end().ends_with('\\')) {
+            self.continued_lines += 1;
+        }
+        if let Some(delimiter) = trimmed
+            .chars()
+            .next()
+            .filter(|c| COMMENT_DELIMITERS.contains(c))
+        {
+            increment(&mut self.comments, delimiter);
+        } else if let Some(section) = reader.try_section(line) {
+            if self.seen_lines {
+                self.later_sections += 1;
+                if self.previous_blank {
+                    self.later_sections_after_blank += 1;
+                }
+            }
+            self.section = Some(section.trim().to_owned());
+        } else if let Some(index) =
+            reader.find_unquoted(trimmed, |_, c| VALUE_DELIMITERS.contains(&c))
+        {
+            let delimiter = trimmed[index..].chars().next().unwrap_or('=');
+            increment(&mut self.delimiters, delimiter);
+            let key = trimmed[..index].trim().to_owned();
+            if !self.keys.insert((self.section.clone(), key)) {
+                self.duplicates += 1;
+            }
+            let value = trimmed[index + delimiter.len_utf8()..].trim_start();
+            self.values += 1;
+            if value.starts_with(r#"""""#) {
+                self.triple_quoted += 1;
+            } else if value.starts_with('"') {
+                self.double_quoted += 1;
+            } else if value.starts_with('\'') {
+                self.single_quoted += 1;
+            }
+        } else {
+            self.flags += 1;
+        }
+        self.seen_lines = true;
+        self.previous_blank = false;
+    }
+
+    /// A parser using the conventions seen in the file.
+    fn parser(self) -> IniParser<'static> {
+        let defaults = IniParser::default();
+        let quoted = self.double_quoted + self.single_quoted;
+        IniParser {
+            comment_delimiters: most_used(self.comments).unwrap_or(defaults.comment_delimiters),
+            value_start_delimiters: most_used(self.delimiters)
+                .unwrap_or(defaults.value_start_delimiters),
+            line_continuation: self.continued_lines > 0,
+            triple_quote_strings: self.triple_quoted > 0,
+            quote_chars: match (self.double_quoted, self.single_quoted) {
+                (_, 0) => &['"'],
+                (double, single) if single > double => &['\'', '"'],
+                _ => &['"', '\''],
+            },
+            quote_values: if quoted * 2 > self.values {
+                QuoteStyle::Always
+            } else {
+                QuoteStyle::Preserve
+            },
+            duplicate_keys: if self.duplicates > 0 {
+                DuplicateKeyStrategy::UseLast
+            } else {
+                DuplicateKeyStrategy::UseFirst
+            },
+            blank_line_before_section: self.later_sections > 0
+                && self.later_sections_after_blank == self.later_sections,
+            flag_keys_as_true: self.flags > 0,
+            ..defaults
+        }
+    }
+}
+
+fn increment(counts: &mut Vec<(char, usize)>, c: char) {
+    match counts.iter_mut().find(|(seen, _)| *seen == c) {
+        Some((_, count)) => *count += 1,
+        None => counts.push((c, 1)),
+    }
+}
+
+/// The characters sorted by how often they were used, `None` if none were used.
+fn most_used(mut counts: Vec<(char, usize)>) -> Option<Cow<'static, [char]>> {
+    // Stable sort so ties stay in the order they were first seen
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+    let chars: Vec<char> = counts.into_iter().map(|(c, _)| c).collect();
+    (!chars.is_empty()).then_some(Cow::Owned(chars))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+
+    const CONAN_EXILES_INI: &str = indoc::indoc! {r#"
+        [/Script/Engine.GameSession]
+        MaxPlayers=40
+
+        [/Script/ConanSandbox.SystemSettings]
+        ; Server settings
+        ServerName=Ninja Server
+        PVPEnabled=True
+
+        [/Script/Engine.Engine]
+        +ActiveGameNameRedirects=(OldGameName="TP_ThirdPersonBP",NewGameName="/Script/ConanSandbox")
+        +ActiveGameNameRedirects=(OldGameName="/Script/TP_ThirdPersonBP",NewGameName="/Script/ConanSandbox")
+    "#};
+
+    const GIT_CONFIG: &str = indoc::indoc! {r#"
+        # Written by git
+        [core]
+        	repositoryformatversion = 0
+        	bare = false
+        [remote "origin"]
+        	url = https://github.com/paul-hansen/ini-ninja.git
+        	fetch = +refs/heads/*:refs/remotes/origin/*
+        [alias]
+        	lg = "log --oneline \
+        		--graph"
+        ; added by hand
+        	st = "status"
+    "#};
+
+    #[test]
+    fn infer_conan_exiles() {
+        let parser = IniParser::infer(CONAN_EXILES_INI.as_bytes()).unwrap();
+        assert_eq!(&*parser.comment_delimiters, &[';']);
+        assert_eq!(&*parser.value_start_delimiters, &['=']);
+        assert!(parser.duplicate_keys == DuplicateKeyStrategy::UseLast);
+        assert!(parser.blank_line_before_section);
+        assert!(!parser.line_continuation);
+        assert!(parser.quote_values == QuoteStyle::Preserve);
+        assert!(!parser.flag_keys_as_true);
+
+        let max_players: Option<u16> = parser
+            .read_value(
+                CONAN_EXILES_INI.as_bytes(),
+                Some("/Script/Engine.GameSession"),
+                "MaxPlayers",
+            )
+            .unwrap();
+        assert_eq!(max_players, Some(40));
+    }
+
+    #[test]
+    fn infer_git_config() {
+        let parser = IniParser::infer(GIT_CONFIG.as_bytes()).unwrap();
+        assert_eq!(&*parser.comment_delimiters, &['#', ';']);
+        assert_eq!(&*parser.value_start_delimiters, &['=']);
+        assert!(parser.duplicate_keys == DuplicateKeyStrategy::UseFirst);
+        assert!(!parser.blank_line_before_section);
+        assert!(parser.line_continuation);
+        assert!(parser.quote_values == QuoteStyle::Preserve);
+        assert_eq!(parser.quote_chars, &['"']);
+
+        let url: Option<String> = parser
+            .read_value(GIT_CONFIG.as_bytes(), Some(r#"remote "origin""#), "url")
+            .unwrap();
+        assert_eq!(
+            url.as_deref(),
+            Some("https://github.com/paul-hansen/ini-ninja.git")
+        );
+    }
+
+    #[test]
+    fn infer_properties_style() {
+        let source = "name: ninja\nport: 8080\nhost = 'localhost'\nuser: 'admin'\n";
+        let parser = IniParser::infer(source.as_bytes()).unwrap();
+        assert_eq!(&*parser.value_start_delimiters, &[':', '=']);
+        assert_eq!(&*parser.comment_delimiters, &['#', ';']);
+        assert_eq!(parser.quote_chars, &['\'', '"']);
+        assert!(parser.quote_values == QuoteStyle::Preserve);
+    }
+
+    #[test]
+    fn infer_quoted_values() {
+        let source = "[a]\nname=\"ninja\"\nmessage=\"\"\"hi\nthere\"\"\"\nempty=\nFlag\n";
+        let parser = IniParser::infer(source.as_bytes()).unwrap();
+        assert!(parser.triple_quote_strings);
+        assert!(parser.flag_keys_as_true);
+        assert!(parser.quote_values == QuoteStyle::Preserve);
+
+        let source = "[a]\nname=\"ninja\"\nport=\"8080\"\nhost=localhost\n";
+        let parser = IniParser::infer(source.as_bytes()).unwrap();
+        assert!(parser.quote_values == QuoteStyle::Always);
+    }
+}
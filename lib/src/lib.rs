@@ -8,7 +8,8 @@
 //! - Can handle large files with low memory use, never needs to have the whole file in ram at once.
 //! - Async and sync versions of read and write functions.
 //! - Tests, CI, all the good things to make sure the code quality stays consistent in the future.
-//! - No dependencies.
+//! - No required dependencies. The default `fs` feature uses `tempfile` for
+//!   [`IniParser::update_file`], turn off default features to leave it out.
 //!
 //! ## Examples
 //!
@@ -53,19 +54,42 @@
 #![deny(clippy::unwrap_used)]
 #![deny(clippy::expect_used)]
 #![deny(clippy::panic)]
+mod builder;
+#[cfg(feature = "serde")]
+mod de;
 mod error;
+mod infer;
+mod normalize;
 mod read;
+mod split;
 #[cfg(test)]
 mod test_helpers;
+mod values;
 mod write;
-pub use error::Error;
-use std::{ops::Range, str::FromStr};
+pub use builder::IniParserBuilder;
+#[cfg(feature = "serde")]
+pub use de::{DeserializeError, Deserialized};
+pub use error::{Error, ParseError};
+pub use read::{Entry, Location};
+use std::{borrow::Cow, ops::Range, str::FromStr};
+pub use values::{
+    IniGroupedInt, IniRange, IniValue, ParseDurationError, ParseRangeError, ParseUnitError,
+    ValueKind,
+};
 #[cfg(doctest)]
 mod readme_tests;
 
 pub trait FromIniStr: Sized {
     type Err: std::error::Error + Send + Sync + 'static;
     fn from_ini_str(ini_str: &str) -> Result<Self, Self::Err>;
+
+    /// Parses the value using the settings of the parser that read it. This is what the
+    /// [`IniParser`] read functions use, by default it ignores the parser and calls
+    /// [`FromIniStr::from_ini_str`].
+    fn from_ini_str_with_parser(ini_str: &str, parser: &IniParser) -> Result<Self, Self::Err> {
+        let _ = parser;
+        Self::from_ini_str(ini_str)
+    }
 }
 
 macro_rules! impl_from_ini_str {
@@ -77,6 +101,22 @@ macro_rules! impl_from_ini_str {
             }
         }
     };
+    // Types that may be written in quotes, which are removed like they are for strings.
+    (quoted $type:ty) => {
+        impl FromIniStr for $type {
+            type Err = <$type as FromStr>::Err;
+            fn from_ini_str(ini_str: &str) -> Result<Self, Self::Err> {
+                FromStr::from_str(trim_whitespace_and_quotes(ini_str, &['"']))
+            }
+
+            fn from_ini_str_with_parser(
+                ini_str: &str,
+                parser: &IniParser,
+            ) -> Result<Self, Self::Err> {
+                FromStr::from_str(trim_whitespace_and_quotes(ini_str, parser.quote_chars))
+            }
+        }
+    };
 }
 
 impl FromIniStr for bool {
@@ -95,7 +135,18 @@ impl FromIniStr for bool {
 impl FromIniStr for String {
     type Err = <String as FromStr>::Err;
     fn from_ini_str(ini_str: &str) -> Result<Self, Self::Err> {
-        FromStr::from_str(trim_whitespace_and_quotes(ini_str))
+        FromStr::from_str(trim_whitespace_and_quotes(ini_str, &['"']))
+    }
+
+    /// Removes the quotes in [`IniParser::quote_chars`] from around the string and unescapes it
+    /// if [`IniParser::escape_sequences`] is enabled.
+    fn from_ini_str_with_parser(ini_str: &str, parser: &IniParser) -> Result<Self, Self::Err> {
+        let trimmed = trim_whitespace_and_quotes(ini_str, parser.quote_chars);
+        if parser.escape_sequences {
+            Ok(unescape(trimmed))
+        } else {
+            FromStr::from_str(trimmed)
+        }
     }
 }
 
@@ -115,6 +166,10 @@ impl_from_ini_str!(f32);
 impl_from_ini_str!(f64);
 impl_from_ini_str!(char);
 impl_from_ini_str!(std::path::PathBuf);
+impl_from_ini_str!(quoted std::net::IpAddr);
+impl_from_ini_str!(quoted std::net::Ipv4Addr);
+impl_from_ini_str!(quoted std::net::Ipv6Addr);
+impl_from_ini_str!(quoted std::net::SocketAddr);
 
 #[derive(Default, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum DuplicateKeyStrategy {
@@ -126,32 +181,294 @@ pub enum DuplicateKeyStrategy {
     Error,
 }
 
+/// Where new keys are added in a section.
+#[derive(Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NewKeyPosition {
+    /// After the last key in the section.
+    #[default]
+    Bottom,
+    /// Right after the section header.
+    Top,
+    /// Before the first key in the section that sorts after the new key. Keeps a section that's
+    /// already sorted in order.
+    Sorted,
+}
+
+/// Whether values are wrapped in quotes when they're written.
+#[derive(Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum QuoteStyle {
+    /// Quote the new value if the value it replaces is quoted, using the same quote.
+    #[default]
+    Preserve,
+    /// Always quote values, using the first of [`IniParser::quote_chars`].
+    Always,
+    /// Write values as they're given.
+    Never,
+}
+
+/// What [`IniParser::write_value_outcome`] did to write the value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum WriteOutcome {
+    /// The key was already in the section and its value was replaced.
+    Replaced,
+    /// The key was added to a section that's already in the file.
+    AppendedToSection,
+    /// The section wasn't in the file, so it was added to the end along with the key.
+    CreatedSection,
+}
+
+/// Line endings used for new lines.
+#[derive(Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Newline {
+    /// Use whichever line ending most of the lines in the source end with, so new lines match the
+    /// rest of the file. Falls back to `\n` if the source has no line endings, or when writing
+    /// without looking at the source first like [`IniParser::normalize`] does.
+    #[default]
+    Detect,
+    /// `\n`, used by Linux and macOS.
+    Lf,
+    /// `\r\n`, used by Windows.
+    CrLf,
+}
+
+impl Newline {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Newline::Detect | Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Counts the line endings in a source so [`Newline::Detect`] can pick the most common one.
+#[derive(Default, Clone, Copy)]
+struct LineEndings {
+    lf: usize,
+    crlf: usize,
+}
+
+impl LineEndings {
+    /// Counts the line endings in a line read with its line endings kept.
+    fn count(&mut self, line: &str) {
+        let crlf = line.matches("\r\n").count();
+        self.crlf += crlf;
+        self.lf += line.matches('\n').count() - crlf;
+    }
+
+    /// The line ending to use for new lines given the [`IniParser::newline`] setting.
+    fn newline(&self, setting: Newline) -> &'static str {
+        match setting {
+            Newline::Detect if self.crlf > self.lf => Newline::CrLf.as_str(),
+            setting => setting.as_str(),
+        }
+    }
+}
+
+/// How the lines written by [`IniParser::write_value_logged`] are formatted.
+#[derive(Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum WriteLogFormat {
+    /// Tab separated values. Tabs, newlines and backslashes in fields are escaped with a
+    /// backslash.
+    #[default]
+    Tsv,
+    /// Comma separated values. Fields containing commas, quotes or newlines are quoted.
+    Csv,
+}
+
+impl WriteLogFormat {
+    fn format(&self, fields: &[&str]) -> String {
+        match self {
+            WriteLogFormat::Tsv => fields
+                .iter()
+                .map(|field| {
+                    field
+                        .replace('\\', "\\\\")
+                        .replace('\t', "\\t")
+                        .replace('\n', "\\n")
+                        .replace('\r', "\\r")
+                })
+                .collect::<Vec<_>>()
+                .join("\t"),
+            WriteLogFormat::Csv => fields
+                .iter()
+                .map(|field| {
+                    if field.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", field.replace('"', "\"\""))
+                    } else {
+                        field.to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+}
+
 /// Parses and writes values to INI files with the provided settings.
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct IniParser<'a> {
-    /// Characters that indicate the start of a comment.
-    pub comment_delimiters: &'a [char],
+    /// Characters that indicate the start of a comment, both on a line of its own and after a
+    /// value. Can be owned, e.g. `Cow::Owned(vec!['#'])`, for delimiters that are only known at
+    /// runtime. Use [`IniParser::line_comment_delimiters`] and
+    /// [`IniParser::inline_comment_delimiters`] for formats that treat them differently.
+    pub comment_delimiters: Cow<'a, [char]>,
     /// Are comments supported after a key=value on the same line?
     pub trailing_comments: bool,
+    /// Characters that start a comment on a line of its own. Uses
+    /// [`IniParser::comment_delimiters`] if `None`.
+    pub line_comment_delimiters: Option<Cow<'a, [char]>>,
+    /// Characters that start a comment after a value on the same line, e.g. the `#` in
+    /// `key=value # comment`. Uses [`IniParser::comment_delimiters`] if `None`.
+    pub inline_comment_delimiters: Option<Cow<'a, [char]>>,
     /// Character that will be used to split the key and value.
-    /// It's very uncommon that this isn't `=`.
-    pub value_start_delimiters: &'a [char],
-    /// If true, lines ending with `\` will consider the next line part of the
-    /// current line. This allows multiline values or comments.
+    /// It's very uncommon that this isn't `=`. If there's more than one, e.g. `['=', ':']` for
+    /// `.properties` style files, the line is split at whichever comes first.
+    pub value_start_delimiters: Cow<'a, [char]>,
+    /// If true, lines ending with [`IniParser::continuation_char`] will consider the next line
+    /// part of the current line. This allows multiline values or comments. The character doesn't
+    /// continue the line if it's part of a comment after a value, e.g. `Map=Level ; see \`.
     pub line_continuation: bool,
+    /// The character at the end of a line that continues it on the next line when
+    /// [`IniParser::line_continuation`] is enabled.
+    pub continuation_char: char,
     /// How should we handle duplicate keys in the ini file?
     pub duplicate_keys: DuplicateKeyStrategy,
+    /// If true, a blank line will be inserted before a section header that gets appended to the
+    /// end of a file when writing a value to a section that doesn't exist yet.
+    pub blank_line_before_section: bool,
+    /// Extra values that [`IniParser::read_bool`] will read as `true`, in addition to the ones
+    /// supported by [`bool`]'s [`FromIniStr`] implementation. Compared case-insensitively.
+    pub bool_true_values: &'a [&'a str],
+    /// Extra values that [`IniParser::read_bool`] will read as `false`, in addition to the ones
+    /// supported by [`bool`]'s [`FromIniStr`] implementation. Compared case-insensitively.
+    pub bool_false_values: &'a [&'a str],
+    /// If set, [`IniParser::read_value_file_ref`] will treat values starting with this character
+    /// as a path to a file containing the actual value, e.g. `password=@/run/secrets/password`.
+    pub file_ref_prefix: Option<char>,
+    /// Format of the lines written by [`IniParser::write_value_logged`].
+    pub write_log_format: WriteLogFormat,
+    /// If true, values starting with `"""` can span multiple lines until the closing `"""`.
+    /// The new lines are kept as part of the value when reading, and values containing new lines
+    /// are wrapped in `"""` when writing.
+    pub triple_quote_strings: bool,
+    /// The line ending used for lines added when writing values.
+    pub newline: Newline,
+    /// Where [`IniParser::set_section_values`] adds keys that aren't in the section yet.
+    pub new_key_position: NewKeyPosition,
+    /// If true, [`IniParser::normalize`] will sort the keys in each section.
+    pub sort_keys_on_normalize: bool,
+    /// If true, [`IniParser::normalize`] pads the keys in each section with spaces so their value
+    /// delimiters line up. Writing a single value never changes the alignment of other keys.
+    pub align_equals: bool,
+    /// If true, an empty section header `[]` switches back to the global section instead of
+    /// starting a section named `""`.
+    pub empty_header_is_global: bool,
+    /// Characters that can be used to quote string values. The quotes are removed when reading a
+    /// [`String`] if the same character is at both the start and end of the value. Set it to `&[]`
+    /// to keep quotes as part of the value.
+    pub quote_chars: &'a [char],
+    /// Separates the base section name from the environment in sections read by
+    /// [`IniParser::read_value_env`], e.g. `[db:production]`.
+    pub env_separator: char,
+    /// If false, keys and section names are compared case-insensitively, so `MaxPlayers` and
+    /// `maxplayers` are the same key. Only ASCII letters are case folded, other characters still
+    /// have to match exactly.
+    pub case_sensitive: bool,
+    /// If true, `\n`, `\t`, `\r`, `\\` and `\"` in values are unescaped when reading a
+    /// [`String`], and those characters are escaped when writing a value. This is separate from
+    /// [`IniParser::line_continuation`], a `\` at the end of a line is never an escape sequence.
+    pub escape_sequences: bool,
+    /// Characters that [`IniGroupedInt`] ignores, e.g. the `,` in `1,000,000`. Other integers
+    /// don't accept them.
+    pub group_separators: &'a [char],
+    /// The most bytes that will be read from a source before giving up with
+    /// [`Error::TooLarge`]. Without a limit a source that never ends, or never sends a newline,
+    /// will be read forever.
+    pub max_bytes: Option<u64>,
+    /// If true, when reading a key with an empty value, e.g. `key=`, a following line that's
+    /// indented further than the key is taken as its value. Writing a new value to the key
+    /// replaces the indented line's value, leaving it on its own line.
+    pub value_on_next_line_when_empty: bool,
+    /// Separates the section from the key in paths given to [`IniParser::read_path`], e.g. the
+    /// `.` in `net.port`.
+    pub path_separator: char,
+    /// Joins the parts of a nested section name given to [`IniParser::read_value_nested`], e.g.
+    /// the `.` in `[parent.child]`.
+    pub section_separator: char,
+    /// If true, writing a value to a section that doesn't exist also adds the headers of its
+    /// parent sections that don't exist, e.g. `[a]` and `[a.b]` before `[a.b.c]`. The parents
+    /// are found by splitting the section name on [`IniParser::section_separator`].
+    pub create_parent_sections: bool,
+    /// If true, a comment delimiter after a value only starts a comment when there's whitespace
+    /// before it, so values like `password=p#ssw0rd` or `url=http://x/#anchor` are kept whole.
+    pub require_whitespace_before_comment: bool,
+    /// If true, runs of whitespace inside a value are collapsed into a single space when it's
+    /// read, so `name=John    Doe` reads as `John Doe`. This includes the newlines in triple
+    /// quoted strings.
+    pub collapse_whitespace: bool,
+    /// If true, a key on its own line without a value delimiter, e.g. `EnableLogging`, is a flag
+    /// that reads as `true`. Writing an empty value to a new key adds it as a flag.
+    pub flag_keys_as_true: bool,
+    /// If true, a line is only a section header if everything after the `]` is whitespace or a
+    /// comment, so `[section]extra` isn't a header. Otherwise anything after the `]` is ignored.
+    pub strict_sections: bool,
+    /// If true, section headers can be indented like `  [section]`. Otherwise a line is only a
+    /// section header if the `[` is at the start of the line. Keys can always be indented.
+    pub allow_indented_sections: bool,
+    /// Whether written values are wrapped in quotes. Values that are already wrapped in one of
+    /// the [`IniParser::quote_chars`] aren't quoted again.
+    pub quote_values: QuoteStyle,
+    /// If true, [`IniParser::update_file`] waits for the changes to reach the disk so they
+    /// survive a crash or power loss. Turning it off is faster, but the update could be lost.
+    pub durable: bool,
+    /// If true, a value that starts with one of the [`IniParser::quote_chars`] but doesn't have
+    /// the closing quote on the same line continues onto the following lines until it's closed.
+    /// The new lines are kept as part of the value. Reading a value whose quote is never closed
+    /// returns [`Error::UnterminatedQuote`].
+    pub multiline_quoted_values: bool,
 }
 
 impl Default for IniParser<'_> {
     /// The defaults are chosen to be compatible with the widest range of ini formats.
     fn default() -> Self {
         Self {
-            comment_delimiters: &['#', ';'],
+            comment_delimiters: Cow::Borrowed(&['#', ';']),
             trailing_comments: true,
-            value_start_delimiters: &['='],
+            line_comment_delimiters: None,
+            inline_comment_delimiters: None,
+            value_start_delimiters: Cow::Borrowed(&['=']),
             line_continuation: false,
+            continuation_char: '\\',
             duplicate_keys: DuplicateKeyStrategy::default(),
+            blank_line_before_section: false,
+            bool_true_values: &[],
+            bool_false_values: &[],
+            file_ref_prefix: None,
+            write_log_format: WriteLogFormat::default(),
+            triple_quote_strings: false,
+            newline: Newline::default(),
+            new_key_position: NewKeyPosition::default(),
+            sort_keys_on_normalize: false,
+            align_equals: false,
+            empty_header_is_global: false,
+            quote_chars: &['"'],
+            env_separator: ':',
+            case_sensitive: true,
+            escape_sequences: false,
+            group_separators: &[','],
+            max_bytes: None,
+            value_on_next_line_when_empty: false,
+            path_separator: '.',
+            section_separator: '.',
+            create_parent_sections: false,
+            require_whitespace_before_comment: false,
+            collapse_whitespace: false,
+            flag_keys_as_true: false,
+            strict_sections: false,
+            allow_indented_sections: true,
+            quote_values: QuoteStyle::default(),
+            durable: true,
+            multiline_quoted_values: false,
         }
     }
 }
@@ -160,75 +477,367 @@ struct ValueByteRangeResult {
     file_size_bytes: usize,
     last_byte_in_section: Option<usize>,
     value_range: Option<Range<usize>>,
+    /// The text currently in `value_range`, `true` for a flag key.
+    old_value: Option<String>,
+    line_endings: LineEndings,
+    /// Is the key a flag without a value, see [`IniParser::flag_keys_as_true`]?
+    is_flag: bool,
+    /// The last line of the source doesn't have a line ending.
+    missing_final_newline: bool,
+    /// The parents of the section that are in the source, see
+    /// [`IniParser::create_parent_sections`].
+    existing_parents: Vec<String>,
+    /// The line the key is on, starting at 1.
+    value_line: Option<usize>,
+    /// The value the way the read methods see it before it's parsed, with joined lines put
+    /// together and triple quotes removed. It's on the line after the key if
+    /// [`IniParser::value_on_next_line_when_empty`] put it there.
+    value: Option<String>,
+    /// The line the key was found on a second time, only tracked for
+    /// [`DuplicateKeyStrategy::Error`].
+    duplicate_line: Option<usize>,
+}
+
+impl ValueByteRangeResult {
+    /// What writing a value to the location described by this result will do.
+    fn outcome(&self) -> WriteOutcome {
+        if self.value_range.is_some() {
+            WriteOutcome::Replaced
+        } else if self.last_byte_in_section.is_some() {
+            WriteOutcome::AppendedToSection
+        } else {
+            WriteOutcome::CreatedSection
+        }
+    }
+}
+
+/// Why a line needs the line after it joined onto it to be complete.
+#[derive(Clone, Copy)]
+enum Unfinished {
+    /// The line ends with the [`IniParser::continuation_char`].
+    Continuation,
+    /// The value opens a triple quoted string that isn't closed yet.
+    TripleQuote,
+    /// The value opens a quote that isn't closed yet, see [`IniParser::multiline_quoted_values`].
+    Quote(char),
 }
 
 impl IniParser<'_> {
+    /// Is the line a comment on its own? That is, after trimming whitespace it starts with one of
+    /// the [`IniParser::line_comment_delimiters`].
+    pub fn is_comment_line(&self, line: &str) -> bool {
+        line.trim().starts_with(self.line_comments())
+    }
+
+    /// The characters that start a comment on a line of its own.
+    fn line_comments(&self) -> &[char] {
+        self.line_comment_delimiters
+            .as_deref()
+            .unwrap_or(&self.comment_delimiters)
+    }
+
+    /// The characters that start a comment after a value, none if
+    /// [`IniParser::trailing_comments`] is disabled.
+    fn inline_comments(&self) -> &[char] {
+        if !self.trailing_comments {
+            return &[];
+        }
+        self.inline_comment_delimiters
+            .as_deref()
+            .unwrap_or(&self.comment_delimiters)
+    }
+
     /// Given a string, check try to parse as a key value and return the range of the string that
     /// contains the value.
     fn try_value(&self, line: &str, key: &str) -> Option<Range<usize>> {
-        let name = key.trim();
-        // Since comments are always at the end of the line, it won't change the positions to
-        // remove them.
-        let line = line
-            .split_once(self.comment_delimiters)
-            .map(|x| x.0)
-            .unwrap_or(line);
-
-        if let Some(delimiter_index) = line
-            .chars()
-            .position(|c| self.value_start_delimiters.contains(&c))
-        {
-            let this_name = line
-                .split_at(line.char_indices().nth(delimiter_index)?.0)
-                .0
-                .trim();
-            if this_name != name {
+        let (this_name, range) = self.try_key_value(line)?;
+        if !self.names_match(this_name, key.trim()) {
+            return None;
+        }
+        Some(range)
+    }
+
+    /// If the line is a section header, returns the name of the section, taking
+    /// [`IniParser::strict_sections`] and [`IniParser::allow_indented_sections`] into account.
+    fn try_section<'l>(&self, line: &'l str) -> Option<&'l str> {
+        if !self.allow_indented_sections && strip_bom(line).starts_with(char::is_whitespace) {
+            return None;
+        }
+        let section = try_section_from_line(line)?;
+        if self.strict_sections {
+            let after = line.split_once(']').map(|x| x.1.trim()).unwrap_or_default();
+            if !after.is_empty() && !after.starts_with(self.inline_comments()) {
                 return None;
             }
-            let mut value_start = delimiter_index + 1;
-
-            // Find the first non-whitespace character after the '='
-            while value_start < line.len()
-                && line
-                    .chars()
-                    .nth(value_start)
-                    .is_some_and(|c| c.is_whitespace())
+        }
+        Some(section)
+    }
+
+    /// If [`IniParser::flag_keys_as_true`] is enabled and the line is only a key, returns the key.
+    fn try_flag<'l>(&self, line: &'l str) -> Option<&'l str> {
+        if !self.flag_keys_as_true
+            || self.is_comment_line(line)
+            || self.try_section(line).is_some()
+            || self.try_key_value(line).is_some()
+        {
+            return None;
+        }
+        let flag = strip_bom(&line[..self.comment_start(line).unwrap_or(line.len())]).trim();
+        // A comment line is empty once the comment is removed.
+        (!flag.is_empty()).then_some(flag)
+    }
+
+    /// Given a string, try to parse it as a key value and return the key along with the range of
+    /// the string that contains the value.
+    fn try_key_value<'l>(&self, line: &'l str) -> Option<(&'l str, Range<usize>)> {
+        if self.is_comment_line(line) {
+            return None;
+        }
+        // Since comments are always at the end of the line, it won't change the positions to
+        // remove them.
+        let line = &line[..self.comment_start(line).unwrap_or(line.len())];
+
+        // Delimiters inside quotes, e.g. `path="a=b"`, are part of the value.
+        let Some(delimiter_index) =
+            self.find_unquoted(line, |_, c| self.value_start_delimiters.contains(&c))
+        else {
+            // If there isn't a value delimiter, there's no value.
+            return None;
+        };
+        let this_name = strip_bom(&line[..delimiter_index]).trim();
+        let after_delimiter = delimiter_index
+            + line[delimiter_index..]
+                .chars()
+                .next()
+                .map_or(0, char::len_utf8);
+        let value = line[after_delimiter..].trim();
+        // An empty value starts right after the delimiter
+        let start = if value.is_empty() {
+            after_delimiter
+        } else {
+            line.len() - line[after_delimiter..].trim_start().len()
+        };
+        let end = start + value.len();
+        Some((this_name, start..end))
+    }
+
+    /// Finds where the comment in the line starts, taking
+    /// [`IniParser::require_whitespace_before_comment`] into account.
+    /// Comment delimiters inside quotes don't start a comment.
+    fn comment_start(&self, line: &str) -> Option<usize> {
+        self.find_unquoted(line, |i, c| {
+            self.inline_comments().contains(&c)
+                && (!self.require_whitespace_before_comment
+                    || line[..i]
+                        .chars()
+                        .next_back()
+                        .is_none_or(char::is_whitespace))
+        })
+    }
+
+    /// Finds the byte position of the first character that `matches`, skipping over text wrapped
+    /// in a pair of [`IniParser::quote_chars`]. A quote without a closing quote is treated like
+    /// any other character.
+    fn find_unquoted(&self, line: &str, matches: impl Fn(usize, char) -> bool) -> Option<usize> {
+        let mut i = 0;
+        while let Some(c) = line[i..].chars().next() {
+            let after = i + c.len_utf8();
+            if self.quote_chars.contains(&c)
+                && let Some(len) = line[after..].find(c)
             {
-                value_start += 1;
+                // Skip to after the closing quote
+                i = after + len + c.len_utf8();
+                continue;
+            }
+            if matches(i, c) {
+                return Some(i);
             }
+            i = after;
+        }
+        None
+    }
 
-            // Start byte position
-            let start = line
-                .char_indices()
-                .nth(value_start)
-                .map(|(idx, _)| idx)
-                .unwrap_or_else(|| {
-                    // If we couldn't get the start position (reached end of string),
-                    // use the position right after the delimiter
-                    line.char_indices()
-                        .nth(delimiter_index + 1)
-                        .map(|(idx, _)| idx)
-                        .unwrap_or(line.len())
-                });
+    /// Does a section header for `this_section` start the section we are looking for?
+    /// A `section` of `None` is the global section.
+    fn is_section(&self, this_section: &str, section: Option<&str>) -> bool {
+        if self.empty_header_is_global && this_section.is_empty() {
+            section.is_none()
+        } else {
+            section.is_some_and(|section| self.names_match(this_section, section))
+        }
+    }
+
+    /// Are the two key or section names the same, taking [`IniParser::case_sensitive`] into
+    /// account?
+    fn names_match(&self, a: &str, b: &str) -> bool {
+        if self.case_sensitive {
+            a == b
+        } else {
+            a.eq_ignore_ascii_case(b)
+        }
+    }
+
+    /// If [`IniParser::line_continuation`] is enabled and the line continues on the next line,
+    /// returns the line without the [`IniParser::continuation_char`] and line ending. This is the
+    /// only place that decides whether a line continues, for both reading and writing.
+    ///
+    /// The line doesn't continue if the character is part of a comment after a value, or if
+    /// [`IniParser::escape_sequences`] is enabled and it's a `\\` escaped by another `\\`.
+    fn strip_continuation<'l>(&self, line: &'l str) -> Option<&'l str> {
+        if !self.line_continuation {
+            return None;
+        }
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let stripped = line.strip_suffix(self.continuation_char)?;
+        let escaped = self.escape_sequences
+            && self.continuation_char == '\\'
+            && (line.len() - line.trim_end_matches('\\').len()).is_multiple_of(2);
+        let in_comment = !self.is_comment_line(line) && self.comment_start(line).is_some();
+        (!escaped && !in_comment).then_some(stripped)
+    }
+
+    /// The line from the start of its value to the end, `None` for comments, section headers and
+    /// other lines that aren't a key and value.
+    fn value_to_end<'l>(&self, line: &'l str) -> Option<&'l str> {
+        if self.try_section(line).is_some() {
+            return None;
+        }
+        let (_, range) = self.try_key_value(line)?;
+        Some(&line[range.start..])
+    }
 
-            // Find the last non-whitespace character for the end position
-            let end = line[start..]
-                .char_indices()
-                .rev()
-                .find(|(_, c)| !c.is_whitespace())
-                .map(|(idx, c)| start + idx + c.len_utf8())
-                .unwrap_or(start);
+    /// Does the line have a value that starts with `"""` but doesn't have the closing `"""`?
+    fn opens_triple_quote(&self, line: &str) -> bool {
+        self.value_to_end(line).is_some_and(|value| {
+            value
+                .strip_prefix(TRIPLE_QUOTE)
+                .is_some_and(|rest| !rest.contains(TRIPLE_QUOTE))
+        })
+    }
+
+    /// If [`IniParser::multiline_quoted_values`] is enabled and the line has a value that starts
+    /// with a quote but doesn't close it, returns the quote character.
+    fn opens_quote(&self, line: &str) -> Option<char> {
+        if !self.multiline_quoted_values
+            || (self.triple_quote_strings && self.opens_triple_quote(line))
+        {
+            return None;
+        }
+        let value = self.value_to_end(line)?;
+        let mut chars = value.chars();
+        let quote = chars.next().filter(|c| self.quote_chars.contains(c))?;
+        (!chars.as_str().contains(quote)).then_some(quote)
+    }
+
+    /// What the line needs from the lines after it to be complete, if anything. `joined` is why
+    /// the last line was joined onto it, so the checks are done in order: line continuation,
+    /// then triple quotes, then multiline quotes. This is the only place that decides whether
+    /// lines are joined, for both reading and writing.
+    ///
+    /// Unless `raw` is set, the continuation character and line ending are removed from a line
+    /// that continues.
+    fn unfinished(
+        &self,
+        line: &mut String,
+        raw: bool,
+        joined: Option<Unfinished>,
+    ) -> Option<Unfinished> {
+        if matches!(joined, None | Some(Unfinished::Continuation))
+            && let Some(stripped) = self.strip_continuation(line)
+        {
+            if !raw {
+                line.truncate(stripped.len());
+            }
+            return Some(Unfinished::Continuation);
+        }
+        if matches!(joined, Some(Unfinished::Quote(_))) {
+            return None;
+        }
+        if self.triple_quote_strings
+            && !matches!(joined, Some(Unfinished::TripleQuote))
+            && self.opens_triple_quote(line)
+        {
+            return Some(Unfinished::TripleQuote);
+        }
+        self.opens_quote(line).map(Unfinished::Quote)
+    }
 
-            Some(start..end)
+    /// The line the way the read methods see it, from a line read with its line endings along with
+    /// the lines joined onto it.
+    fn joined_line(&self, raw: &str) -> String {
+        let mut lines = raw
+            .split_inclusive('\n')
+            .map(|line| line.trim_end_matches(['\r', '\n']));
+        let mut line = lines.next().unwrap_or_default().to_owned();
+        let mut unfinished = self.unfinished(&mut line, false, None);
+        for next_line in lines {
+            let Some(waiting) = unfinished else {
+                break;
+            };
+            unfinished = self.join_line(&mut line, next_line, waiting, false);
+        }
+        line
+    }
+
+    /// Joins the next line onto a line that's `unfinished`, returning what the line still needs.
+    ///
+    /// If `raw` is set the line is kept as it is in the source so byte positions in it match.
+    /// Otherwise `next_line` is expected to be without its line ending, the indentation of
+    /// continued lines is removed and quoted lines are separated by `\n`.
+    fn join_line(
+        &self,
+        line: &mut String,
+        next_line: &str,
+        unfinished: Unfinished,
+        raw: bool,
+    ) -> Option<Unfinished> {
+        let closed = match unfinished {
+            Unfinished::Continuation => {
+                line.push_str(if raw {
+                    next_line
+                } else {
+                    next_line.trim_start()
+                });
+                return self.unfinished(line, raw, Some(unfinished));
+            }
+            Unfinished::TripleQuote => next_line.contains(TRIPLE_QUOTE),
+            Unfinished::Quote(quote) => next_line.contains(quote),
+        };
+        if !raw {
+            line.push('\n');
+        }
+        line.push_str(next_line);
+        if closed {
+            self.unfinished(line, raw, Some(unfinished))
         } else {
-            // If there isn't a value delimiter, there's no value.
-            None
+            Some(unfinished)
         }
     }
 }
 
+const TRIPLE_QUOTE: &str = r#"""""#;
+
+fn is_triple_quoted(value: &str) -> bool {
+    value.len() >= TRIPLE_QUOTE.len() * 2
+        && value.starts_with(TRIPLE_QUOTE)
+        && value.ends_with(TRIPLE_QUOTE)
+}
+
+/// The UTF-8 byte order mark some editors add to the start of files.
+const UTF8_BOM: char = '\u{feff}';
+
+/// Removes the byte order mark from the start of the line if it has one. It's only removed when
+/// matching keys and sections, so byte positions in the line are unchanged and the byte order
+/// mark is kept when writing.
+fn strip_bom(line: &str) -> &str {
+    line.strip_prefix(UTF8_BOM).unwrap_or(line)
+}
+
+/// If the line is a section header, returns the name of the section. The name is everything
+/// between the `[` and the first `]`, anything after the `]` is ignored.
 fn try_section_from_line(line: &str) -> Option<&str> {
-    let trimmed = line.trim();
+    let trimmed = strip_bom(line).trim();
     if trimmed.starts_with('[') {
         let end = trimmed.find(']')?;
         let section_name = &trimmed[1..end];
@@ -238,10 +847,56 @@ fn try_section_from_line(line: &str) -> Option<&str> {
     }
 }
 
-fn trim_whitespace_and_quotes(text: &str) -> &str {
+/// Trims whitespace and then a pair of matching quotes from around the text. Quotes are only
+/// removed if the same quote character is on both ends.
+fn trim_whitespace_and_quotes<'t>(text: &'t str, quote_chars: &[char]) -> &'t str {
     let text = text.trim();
-    let text = text.strip_prefix('"').unwrap_or(text);
-    text.strip_suffix('"').unwrap_or(text)
+    quote_chars
+        .iter()
+        .find_map(|&quote| text.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(text)
+}
+
+/// Replaces the escape sequences supported by [`IniParser::escape_sequences`] with the characters
+/// they represent. Unknown escape sequences are left as they are.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some('"') => unescaped.push('"'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Escapes the characters supported by [`IniParser::escape_sequences`].
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
 #[cfg(test)]
@@ -289,6 +944,210 @@ mod tests {
         assert_eq!(new, ROUNDTRIP_INI_END);
     }
 
+    #[test]
+    fn read_socket_addr() {
+        let parser = IniParser::default();
+        let ini = "bind=127.0.0.1:8080\nhost = \"::1\"\nbad=localhost:80\n";
+        let bind: Option<std::net::SocketAddr> =
+            parser.read_value(ini.as_bytes(), None, "bind").unwrap();
+        assert_eq!(bind, Some(([127, 0, 0, 1], 8080).into()));
+        let host: Option<std::net::IpAddr> =
+            parser.read_value(ini.as_bytes(), None, "host").unwrap();
+        assert_eq!(host, Some(std::net::Ipv6Addr::LOCALHOST.into()));
+        let bad = parser.read_value::<std::net::SocketAddr>(ini.as_bytes(), None, "bad");
+        assert!(matches!(bad, Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn read_string_unbalanced_quotes() {
+        let parser = IniParser::default();
+        let ini = indoc::indoc! {r#"
+            motd="Welcome"
+            regex=^".*$
+            leading="Welcome
+            trailing=Welcome"
+            embedded=He said "hi" there
+            mixed="Welcome'
+        "#};
+        let read = |key| {
+            parser
+                .read_value::<String>(ini.as_bytes(), None, key)
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(read("motd"), "Welcome");
+        assert_eq!(read("regex"), r#"^".*$"#);
+        assert_eq!(read("leading"), r#""Welcome"#);
+        assert_eq!(read("trailing"), r#"Welcome""#);
+        assert_eq!(read("embedded"), r#"He said "hi" there"#);
+        assert_eq!(read("mixed"), r#""Welcome'"#);
+        assert_eq!(String::from_ini_str(r#""Welcome"#).unwrap(), r#""Welcome"#);
+    }
+
+    #[test]
+    fn read_string_without_quote_chars() {
+        let parser = IniParser {
+            quote_chars: &[],
+            ..Default::default()
+        };
+        let motd: Option<String> = parser
+            .read_value(r#"motd="Welcome""#.as_bytes(), None, "motd")
+            .unwrap();
+        assert_eq!(motd.as_deref(), Some(r#""Welcome""#));
+    }
+
+    #[test]
+    fn ip_addr_quote_chars() {
+        let parser = IniParser {
+            quote_chars: &['\''],
+            ..Default::default()
+        };
+        let ip: Option<std::net::Ipv4Addr> = parser
+            .read_value("ip='10.0.0.1'".as_bytes(), None, "ip")
+            .unwrap();
+        assert_eq!(ip, Some(std::net::Ipv4Addr::new(10, 0, 0, 1)));
+    }
+
+    /// Builds a parser from delimiters only known at runtime.
+    fn runtime_parser(comment: &str, delimiter: &str) -> IniParser<'static> {
+        IniParser {
+            comment_delimiters: Cow::Owned(comment.chars().collect()),
+            value_start_delimiters: Cow::Owned(delimiter.chars().collect()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn owned_delimiters() {
+        let parser = runtime_parser("%", ":");
+        let ini = "% comment\nname: tom % trailing\n";
+        let name: Option<String> = parser.read_value(ini.as_bytes(), None, "name").unwrap();
+        assert_eq!(name, Some("tom".to_string()));
+        assert!(parser.is_comment_line("% comment"));
+        assert!(!parser.is_comment_line("# comment"));
+    }
+
+    #[test]
+    fn is_comment_line() {
+        let parser = IniParser::default();
+        assert!(parser.is_comment_line("# x"));
+        assert!(parser.is_comment_line("  ; y"));
+        assert!(!parser.is_comment_line("key=val"));
+        assert!(!parser.is_comment_line(""));
+        assert!(!parser.is_comment_line("key=val # comment"));
+    }
+
+    /// `;` only starts a comment on its own line and `#` only after a value.
+    fn split_comments_parser() -> IniParser<'static> {
+        IniParser {
+            line_comment_delimiters: Some(Cow::Borrowed(&[';'])),
+            inline_comment_delimiters: Some(Cow::Borrowed(&['#'])),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn split_comment_delimiters() {
+        let parser = split_comments_parser();
+        let ini = "; name = commented\n[server]\nname = a;b # comment\n";
+        let read = |key| parser.read_value::<String>(ini.as_bytes(), Some("server"), key);
+        assert_eq!(read("name").unwrap(), Some("a;b".to_string()));
+        let global: Option<String> = parser.read_value(ini.as_bytes(), None, "; name").unwrap();
+        assert_eq!(global, None);
+        assert!(parser.is_comment_line("; comment"));
+        assert!(!parser.is_comment_line("# comment"));
+        assert_eq!(parser.try_value("#name = x", "#name"), None);
+    }
+
+    #[test]
+    fn split_comment_delimiters_write() {
+        let parser = split_comments_parser();
+        let mut source = std::io::Cursor::new("; name = commented\nname = a;b # comment\n");
+        let mut dest = Vec::new();
+        parser
+            .write_value(&mut source, &mut dest, None, "name", "c;d")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(dest).unwrap(),
+            "; name = commented\nname = c;d # comment\n"
+        );
+    }
+
+    #[test]
+    fn trailing_comments_disabled() {
+        let parser = IniParser {
+            trailing_comments: false,
+            ..Default::default()
+        };
+        let ini = "# comment\nname = a # b\n";
+        let name: Option<String> = parser.read_value(ini.as_bytes(), None, "name").unwrap();
+        assert_eq!(name, Some("a # b".to_string()));
+        assert!(parser.is_comment_line("# comment"));
+    }
+
+    #[test]
+    fn try_value_earliest_delimiter() {
+        let parser = IniParser {
+            value_start_delimiters: Cow::Borrowed(&['=', ':']),
+            ..Default::default()
+        };
+        let line = "url: http://x?a=b";
+        let (key, range) = parser.try_key_value(line).unwrap();
+        assert_eq!(key, "url");
+        assert_eq!(&line[range], "http://x?a=b");
+        let line = "a=b:c";
+        let (key, range) = parser.try_key_value(line).unwrap();
+        assert_eq!(key, "a");
+        assert_eq!(&line[range], "b:c");
+    }
+
+    #[test]
+    fn require_whitespace_before_comment() {
+        let parser = IniParser {
+            require_whitespace_before_comment: true,
+            ..Default::default()
+        };
+        let ini = "password=p#ssw0rd\nurl=http://x/;y ; comment\n# comment=1\n";
+        let password: Option<String> = parser.read_value(ini.as_bytes(), None, "password").unwrap();
+        assert_eq!(password, Some("p#ssw0rd".to_string()));
+        let url: Option<String> = parser.read_value(ini.as_bytes(), None, "url").unwrap();
+        assert_eq!(url, Some("http://x/;y".to_string()));
+        let comment: Option<String> = parser.read_value(ini.as_bytes(), None, "comment").unwrap();
+        assert_eq!(comment, None);
+
+        let password: Option<String> = IniParser::default()
+            .read_value(ini.as_bytes(), None, "password")
+            .unwrap();
+        assert_eq!(password, Some("p".to_string()));
+    }
+
+    #[test]
+    fn try_value_quoted_delimiters() {
+        let parser = IniParser::default();
+        let line = r#"path="C:\Program Files\;app=1" ; comment"#;
+        let range = parser.try_value(line, "path").unwrap();
+        assert_eq!(&line[range], r#""C:\Program Files\;app=1""#);
+        let path: Option<String> = parser.read_value(line.as_bytes(), None, "path").unwrap();
+        assert_eq!(path, Some(r"C:\Program Files\;app=1".to_string()));
+    }
+
+    #[test]
+    fn try_value_quoted_key() {
+        let parser = IniParser::default();
+        let line = r#""a=b" = c # comment"#;
+        let (key, range) = parser.try_key_value(line).unwrap();
+        assert_eq!(key, r#""a=b""#);
+        assert_eq!(&line[range], "c");
+    }
+
+    #[test]
+    fn try_value_unclosed_quote() {
+        let parser = IniParser::default();
+        let line = r#"name = "tom ; comment"#;
+        let range = parser.try_value(line, "name").unwrap();
+        assert_eq!(&line[range], r#""tom"#);
+    }
+
     #[test]
     fn try_value_newline() {
         let parser = IniParser::default();
@@ -326,4 +1185,16 @@ mod tests {
         s.replace_range(range, "bill");
         assert_eq!(s, "name=bill");
     }
+
+    #[test]
+    fn try_value_tabs() {
+        let parser = IniParser::default();
+        let line = "\tname\t=\tbob\t\n";
+        let range = parser.try_value(line, "name").unwrap();
+        assert_eq!(&line[range.clone()], "bob");
+        let mut s = String::from(line);
+        s.replace_range(range, "bill");
+        assert_eq!(s, "\tname\t=\tbill\t\n");
+        assert_eq!(parser.try_value(line, "name\t"), Some(8..11));
+    }
 }
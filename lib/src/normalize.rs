@@ -0,0 +1,222 @@
+use crate::read::Lines;
+use crate::{IniParser, error::Error};
+use std::io::{Read, Write};
+
+impl IniParser<'_> {
+    /// Rewrites the whole source into a consistent format and writes it to the destination.
+    ///
+    /// - Section headers are written as `[section]`.
+    /// - Keys are written as `key=value` using the first of [`IniParser::value_start_delimiters`],
+    ///   followed by any trailing comment.
+    /// - Indentation and trailing whitespace are removed from all lines.
+    /// - Lines joined by line continuation are written as a single line.
+    /// - Lines end with [`IniParser::newline`].
+    ///
+    /// Comments and blank lines are otherwise kept. If [`IniParser::sort_keys_on_normalize`] is
+    /// set, the keys in each section are sorted and the comments and blank lines above a key move
+    /// with it. Lines after the last key in a section stay at the end of the section.
+    /// If [`IniParser::align_equals`] is set, keys are padded so the delimiters in each section
+    /// line up.
+    pub fn normalize(&self, source: impl Read, mut destination: impl Write) -> Result<(), Error> {
+        let buffer = std::io::BufReader::new(source);
+        let mut lines = Lines::new(buffer);
+        let delimiter = self.value_start_delimiters.first().unwrap_or(&'=');
+        let mut section = SectionLines::default();
+        // The whole section is needed before it can be sorted or aligned
+        let buffer_section = self.sort_keys_on_normalize || self.align_equals;
+        while let Some(line) = self.next_line(&mut lines)? {
+            if let Some(name) = self.try_section(&line) {
+                section.write(&mut destination, self)?;
+                let rest = line.split_once(']').map(|x| x.1.trim()).unwrap_or_default();
+                let header = format!("[{name}]");
+                self.write_normalized_line(&mut destination, &header, rest)?;
+            } else if let Some((key, range)) = self.try_key_value(&line) {
+                let key_value = format!("{key}{delimiter}{}", &line[range.clone()]);
+                let mut normalized = Vec::new();
+                self.write_normalized_line(&mut normalized, &key_value, line[range.end..].trim())?;
+                if buffer_section {
+                    section.push_key(key.to_owned(), normalized);
+                } else {
+                    destination.write_all(&normalized)?;
+                }
+            } else {
+                let mut normalized = Vec::new();
+                self.write_normalized_line(&mut normalized, line.trim(), "")?;
+                if buffer_section {
+                    section.pending.push(normalized);
+                } else {
+                    destination.write_all(&normalized)?;
+                }
+            }
+        }
+        section.write(&mut destination, self)?;
+        Ok(())
+    }
+
+    /// Writes the line, followed by the comment separated with a space if there is one.
+    fn write_normalized_line(
+        &self,
+        destination: &mut impl Write,
+        line: &str,
+        comment: &str,
+    ) -> Result<(), Error> {
+        destination.write_all(line.as_bytes())?;
+        if !comment.is_empty() {
+            write!(destination, " {comment}")?;
+        }
+        destination.write_all(self.newline.as_str().as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The normalized lines of a section, grouped so they can be sorted by key.
+#[derive(Default)]
+struct SectionLines {
+    /// Each key along with the lines above it since the previous key and its own line last.
+    entries: Vec<(String, Vec<Vec<u8>>)>,
+    /// Lines since the last key.
+    pending: Vec<Vec<u8>>,
+}
+
+impl SectionLines {
+    fn push_key(&mut self, key: String, line: Vec<u8>) {
+        let mut lines = std::mem::take(&mut self.pending);
+        lines.push(line);
+        self.entries.push((key, lines));
+    }
+
+    /// Writes the lines, sorted and aligned as configured by the parser, and clears them.
+    fn write(&mut self, destination: &mut impl Write, parser: &IniParser) -> Result<(), Error> {
+        if parser.sort_keys_on_normalize {
+            // Stable sort so duplicate keys stay in the same order
+            self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        let width = self
+            .entries
+            .iter()
+            .map(|(key, _)| key.chars().count())
+            .max()
+            .unwrap_or_default();
+        for (key, mut lines) in self.entries.drain(..) {
+            let key_line = lines.pop().unwrap_or_default();
+            for line in lines {
+                destination.write_all(&line)?;
+            }
+            if parser.align_equals {
+                // The key line starts with the key, followed by the delimiter
+                let (key_bytes, rest) = key_line.split_at(key.len());
+                destination.write_all(key_bytes)?;
+                write!(destination, "{:1$}", "", width - key.chars().count())?;
+                destination.write_all(rest)?;
+            } else {
+                destination.write_all(&key_line)?;
+            }
+        }
+        for line in self.pending.drain(..) {
+            destination.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use crate::assert_eq_preserve_new_lines;
+    use indoc::indoc;
+
+    #[test]
+    fn normalize() {
+        let parser = IniParser::default();
+        let input = indoc! {"
+            # Global comment
+              name =  tom   # trailing
+            [ contact ]   ; section comment
+
+                email   =tom@example.com
+        "};
+        let mut dest = Vec::new();
+        parser.normalize(input.as_bytes(), &mut dest).unwrap();
+        let expected = indoc! {"
+            # Global comment
+            name=tom # trailing
+            [contact] ; section comment
+
+            email=tom@example.com
+        "};
+        assert_eq_preserve_new_lines!(String::from_utf8(dest).unwrap(), expected);
+    }
+
+    #[test]
+    fn normalize_sort_keys() {
+        let parser = IniParser {
+            sort_keys_on_normalize: true,
+            ..Default::default()
+        };
+        let input = indoc! {"
+            [server]
+            # The port to listen on
+            port = 8080
+            host = localhost
+            # Enables extra logging
+            ; Don't use in production
+            debug = true
+            # end of server
+
+            [client]
+            timeout = 30
+            retries = 3
+        "};
+        let mut dest = Vec::new();
+        parser.normalize(input.as_bytes(), &mut dest).unwrap();
+        let expected = indoc! {"
+            [server]
+            # Enables extra logging
+            ; Don't use in production
+            debug=true
+            host=localhost
+            # The port to listen on
+            port=8080
+            # end of server
+
+            [client]
+            retries=3
+            timeout=30
+        "};
+        assert_eq_preserve_new_lines!(String::from_utf8(dest).unwrap(), expected);
+    }
+
+    #[test]
+    fn normalize_align_equals() {
+        let parser = IniParser {
+            align_equals: true,
+            ..Default::default()
+        };
+        let input = indoc! {"
+            [server]
+            port = 8080
+            # The name of the host
+            hostname = localhost ; trailing
+            ip=127.0.0.1
+
+            [client]
+            timeout = 30
+            x = 1
+        "};
+        let mut dest = Vec::new();
+        parser.normalize(input.as_bytes(), &mut dest).unwrap();
+        let expected = indoc! {"
+            [server]
+            port    =8080
+            # The name of the host
+            hostname=localhost ; trailing
+            ip      =127.0.0.1
+
+            [client]
+            timeout=30
+            x      =1
+        "};
+        assert_eq_preserve_new_lines!(String::from_utf8(dest).unwrap(), expected);
+    }
+}
@@ -1,10 +1,14 @@
 use crate::DuplicateKeyStrategy;
-use crate::try_section_from_line;
+use crate::{TRIPLE_QUOTE, is_triple_quoted};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::io::{BufRead, Read};
+use std::ops::Range;
 
-use crate::{FromIniStr, IniParser, error::Error};
+use crate::values::parse_with_units;
+use crate::{FromIniStr, IniParser, IniValue, ValueByteRangeResult, ValueKind, error::Error};
 #[cfg(feature = "async")]
-use tokio::io::{AsyncBufReadExt, AsyncRead};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt};
 
 impl IniParser<'_> {
     /// Read a value from a INI file source.
@@ -19,10 +23,10 @@ impl IniParser<'_> {
         T: FromIniStr,
     {
         let value = self.value_unaltered(source, section, key)?;
-        let Some(value) = value else {
+        let Some((value, line)) = value else {
             return Ok(None);
         };
-        let value = FromIniStr::from_ini_str(&value).map_err(Error::new_parse)?;
+        let value = self.parse_value(&value, section, key, line)?;
         Ok(Some(value))
     }
 
@@ -39,474 +43,4761 @@ impl IniParser<'_> {
         T: FromIniStr,
     {
         let value = self.value_unaltered_async(source, section, key).await?;
-        let Some(value) = value else {
+        let Some((value, line)) = value else {
             return Ok(None);
         };
-        let value = FromIniStr::from_ini_str(&value).map_err(Error::new_parse)?;
+        let value = self.parse_value(&value, section, key, line)?;
         Ok(Some(value))
     }
 
-    /// Returns the value for the given section and name without any parsing. Notably this may
-    /// still have quotation marks around strings. Leading and trailing whitespace will still be
-    /// stripped though.
+    /// Read a value from an ini file that's already in memory. Same as [`IniParser::read_value`].
+    /// If section is none, it will look in the global space.
+    pub fn read_value_str<T>(
+        &self,
+        text: &str,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<T>, Error>
+    where
+        T: FromIniStr,
+    {
+        self.read_value(text.as_bytes(), section, key)
+    }
+
+    /// Calls `f` with the value before it's parsed and returns what `f` returns, or `None` if the
+    /// key isn't found. The value is the same as [`IniParser::read_value_both`]'s raw value:
+    /// trailing comments and whitespace are removed but quotes are kept. Useful for parsing or
+    /// copying just part of the value without [`IniParser::read_value`] allocating a [`String`]
+    /// for the whole value, `f` is given a slice of the line buffer the value was read into.
     ///
-    /// Usually only use this if you are manually parsing something.
-    fn value_unaltered(
+    /// The `&str` is only valid while `f` runs, anything that needs to outlive it has to be copied.
+    /// If section is none, it will look in the global space.
+    pub fn with_value<R>(
         &self,
         source: impl Read,
         section: Option<&str>,
         key: &str,
-    ) -> Result<Option<String>, Error> {
+        f: impl FnOnce(&str) -> R,
+    ) -> Result<Option<R>, Error> {
         let buffer = std::io::BufReader::new(source);
-
-        // Are we in the section we are looking for?
-        // Starts in the global namespace, so if section is none it starts as true, changing as we
-        // parse different sections.
         let mut in_section = section.is_none();
-        let mut value = None;
-        let mut lines = BufRead::lines(buffer);
-        loop {
-            let Some(line) = lines.next() else {
+        let mut value_line = None;
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            let line_number = lines.line_number;
+            if self.process_value_line(
+                line,
+                line_number,
+                section,
+                key,
+                &mut in_section,
+                &mut value_line,
+            )? {
                 break;
-            };
-            let mut line = line?;
-            if self.line_continuation
-                && let Some(line2) = line.strip_suffix('\\')
-            {
-                line = line2.to_string();
-                for next_line in lines.by_ref() {
-                    let next_line = next_line?;
-                    let next_line = next_line.trim_start();
-                    line.push_str(next_line);
-                    if let Some(line2) = line.strip_suffix('\\') {
-                        line = line2.to_string();
-                    } else {
-                        break;
-                    }
-                }
-            }
-            if self.process_line(line, section, key, &mut in_section, &mut value)? {
-                return Ok(value);
             }
         }
-        Ok(value)
+        Ok(value_line.and_then(|line| self.value_or_flag_value(&line, key).map(f)))
     }
-    /// Returns the value for the given section and name without any parsing. Notably this may
-    /// still have quotation marks around strings. Leading and trailing whitespace will still be
-    /// stripped though.
-    ///
-    /// Usually only use this if you are manually parsing something.
+
+    /// Async version of [`IniParser::with_value`].
     #[cfg(feature = "async")]
-    async fn value_unaltered_async(
+    pub async fn with_value_async<R>(
         &self,
         source: impl AsyncRead,
         section: Option<&str>,
         key: &str,
-    ) -> Result<Option<String>, Error> {
+        f: impl FnOnce(&str) -> R,
+    ) -> Result<Option<R>, Error> {
         let buffer = Box::pin(tokio::io::BufReader::new(source));
-
-        // Are we in the section we are looking for?
-        // Starts in the global namespace, so if section is none it starts as true, changing as we
-        // parse different sections.
         let mut in_section = section.is_none();
-        let mut value = None;
-        let mut lines = buffer.lines();
-        loop {
-            let Some(line) = lines.next_line().await? else {
+        let mut value_line = None;
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            let line_number = lines.line_number;
+            if self.process_value_line(
+                line,
+                line_number,
+                section,
+                key,
+                &mut in_section,
+                &mut value_line,
+            )? {
                 break;
-            };
-            let mut line = line;
-            // Handle line continuation
-            if self.line_continuation
-                && let Some(line2) = line.strip_suffix('\\')
-            {
-                line = line2.to_string();
-                while let Some(next_line) = lines.next_line().await? {
-                    let next_line = next_line.trim_start();
-                    line.push_str(next_line);
-                    if let Some(line2) = line.strip_suffix('\\') {
-                        line = line2.to_string();
-                    } else {
-                        break;
-                    }
-                }
-            }
-            if self.process_line(line, section, key, &mut in_section, &mut value)? {
-                return Ok(value);
             }
         }
-        Ok(value)
+        Ok(value_line.and_then(|line| self.value_or_flag_value(&line, key).map(f)))
     }
 
-    /// Mainly used to extract common functionality between async and sync implementations.
-    /// Returns true if we found the final value. (Note that depending on duplicate handling, this
-    /// may not be the first time we see the value)
-    fn process_line(
+    /// Same as [`IniParser::read_value`] but also returns where the value is in the source, e.g. so
+    /// an editor can jump to it. Line numbers start at 1.
+    /// If section is none, it will look in the global space.
+    pub fn read_value_located<T>(
         &self,
-        line: String,
+        source: impl Read,
         section: Option<&str>,
         key: &str,
-        in_section: &mut bool,
-        value: &mut Option<String>,
-    ) -> Result<bool, Error> {
-        if let Some(this_section) = try_section_from_line(&line) {
-            if let Some(section) = &section {
-                *in_section = *section == this_section;
-            } else {
-                // If section is None, we are looking for a global variable.
-                // Since this_section is some here, we know we aren't in the global section
-                *in_section = false;
-            }
-        } else if *in_section && let Some(range) = self.try_value(&line, key) {
-            let had_previous = value.is_some();
-            *value = Some(line[range].to_string());
-            match self.duplicate_keys {
-                DuplicateKeyStrategy::Error => {
-                    if had_previous {
-                        return Err(Error::DuplicateKey {
-                            key: key.to_string(),
-                            section: section.map(|s| s.to_owned()),
-                        });
-                    }
-                }
-                DuplicateKeyStrategy::UseFirst => {
-                    return Ok(true);
-                }
-                _ => {}
-            }
-        }
-
-        Ok(false)
+    ) -> Result<Option<(T, Location)>, Error>
+    where
+        T: FromIniStr,
+    {
+        let mut buffer = std::io::BufReader::new(source);
+        let result = self.value_byte_range(&mut buffer, section, key)?;
+        self.located_value(result, section, key)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    #![allow(clippy::unwrap_used)]
-    use crate::{DuplicateKeyStrategy, try_section_from_line};
 
-    use super::*;
+    /// Async version of [`IniParser::read_value_located`].
     #[cfg(feature = "async")]
-    use ::paste::paste;
-
-    /// Generate async and sync versions of tests that get values from a given ini
-    macro_rules! read_value_eq {
-        {
-            $test_name:ident,
-            $parser:expr,
-            $ini_file_string:expr,
-            $section:expr,
-            $key:expr,
-            $expected:expr $(,)?
-        } => {
-            #[test]
-            fn $test_name() {
-                let parser = $parser;
-                let reader = std::io::Cursor::new($ini_file_string);
-                let value = parser.read_value(reader, $section, $key).unwrap();
-                assert_eq!(value, $expected);
-            }
+    pub async fn read_value_located_async<T>(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<(T, Location)>, Error>
+    where
+        T: FromIniStr,
+    {
+        let mut buffer = Box::pin(tokio::io::BufReader::new(source));
+        let result = self
+            .value_byte_range_async(&mut buffer, section, key)
+            .await?;
+        self.located_value(result, section, key)
+    }
 
-            #[cfg(feature = "async")]
-            paste! {
-                #[tokio::test]
-                async fn [<$test_name _async>]() {
-                    let parser = $parser;
-                    let reader = std::io::Cursor::new($ini_file_string);
-                    let value = parser.read_value_async(reader, $section, $key).await.unwrap();
-                    assert_eq!(value, $expected);
-                }
-            }
+    /// Parses the value found by [`IniParser::value_byte_range`] along with where it is.
+    fn located_value<T: FromIniStr>(
+        &self,
+        result: ValueByteRangeResult,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<(T, Location)>, Error> {
+        if let Some(line) = result.duplicate_line {
+            return Err(Error::DuplicateKey {
+                key: key.to_string(),
+                section: section.map(|s| s.to_owned()),
+                line,
+            });
+        }
+        let (Some(byte_range), Some(line), Some(value)) =
+            (result.value_range, result.value_line, result.value)
+        else {
+            return Ok(None);
         };
+        let value = self.parse_value(&value, section, key, line)?;
+        Ok(Some((value, Location { line, byte_range })))
     }
 
-    /// Generate async and sync versions of tests that get values from a given ini and assert that
-    /// the result matches a pattern. Useful for partially matching errors.
-    macro_rules! read_value_matches {
-        {
-            $test_name:ident,
-            $parser:expr,
-            $ini_file_string:expr,
-            $section:expr,
-            $key:expr,
-            $expected:pat $(,)?
-        } => {
-            #[test]
-            fn $test_name() {
-                let parser = $parser;
-                let reader = std::io::Cursor::new($ini_file_string);
-                let value = parser.read_value(reader, $section, $key);
-                ::assert_matches::assert_matches!(value, $expected);
+    /// Read a value exactly as it appears in the source, from right after the value delimiter up
+    /// to the comment or the end of the line. Unlike the other read methods, whitespace around the
+    /// value and quotes aren't removed, so `key = " a " # comment` gives ` " a " `. Lines joined
+    /// by line continuation are still joined.
+    /// If section is none, it will look in the global space.
+    pub fn read_value_raw(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<String>, Error> {
+        let buffer = std::io::BufReader::new(source);
+        let mut in_section = section.is_none();
+        let mut value = None;
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            let line_number = lines.line_number;
+            if self.process_line_raw(
+                &line,
+                line_number,
+                section,
+                key,
+                &mut in_section,
+                &mut value,
+            )? {
+                break;
             }
+        }
+        Ok(value.map(|(value, _)| value))
+    }
 
-            #[cfg(feature = "async")]
-            paste! {
-                #[tokio::test]
-                async fn [<$test_name _async>]() {
-                    let parser = $parser;
-                    let reader = std::io::Cursor::new($ini_file_string);
-                    let value = parser.read_value_async(reader, $section, $key).await;
-                    ::assert_matches::assert_matches!(value, $expected);
-                }
+    /// Async version of [`IniParser::read_value_raw`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_raw_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<String>, Error> {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+        let mut in_section = section.is_none();
+        let mut value = None;
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            let line_number = lines.line_number;
+            if self.process_line_raw(
+                &line,
+                line_number,
+                section,
+                key,
+                &mut in_section,
+                &mut value,
+            )? {
+                break;
             }
-        };
+        }
+        Ok(value.map(|(value, _)| value))
     }
 
-    #[test]
-    fn try_section_not() {
-        assert_eq!(try_section_from_line("This is a line"), None);
+    /// Read a value that is itself a small ini, written as `;` separated `key=value` pairs, e.g.
+    /// `options = a=1;b=2`, and return the value of `inner_key` in it. The inner pairs are read
+    /// with the same parser settings as the outer file.
+    ///
+    /// `;` is one of the default [`IniParser::comment_delimiters`], it must be removed from them
+    /// or the outer value will end at the first `;`.
+    /// If section is none, it will look in the global space.
+    pub fn read_nested_value(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+        inner_key: &str,
+    ) -> Result<Option<String>, Error> {
+        let value: Option<String> = self.read_value(source, section, key)?;
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        self.read_value(nested_ini(&value).as_bytes(), None, inner_key)
     }
 
-    #[test]
-    fn try_section_no_comment() {
-        assert_eq!(try_section_from_line("[SECTION]"), Some("SECTION"));
+    /// Async version of [`IniParser::read_nested_value`].
+    #[cfg(feature = "async")]
+    pub async fn read_nested_value_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+        inner_key: &str,
+    ) -> Result<Option<String>, Error> {
+        let value: Option<String> = self.read_value_async(source, section, key).await?;
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        self.read_value(nested_ini(&value).as_bytes(), None, inner_key)
     }
 
-    #[test]
-    fn try_section_comment() {
-        assert_eq!(
-            try_section_from_line("[SECTION] # This is a comment"),
-            Some("SECTION")
-        );
+    /// Read a value given a path made of the section and key joined by
+    /// [`IniParser::path_separator`], e.g. `net.port`. A path without the separator is a key in the
+    /// global section.
+    ///
+    /// The path is split at the first separator, so keys can contain the separator but sections
+    /// can't: `net.ip.v4` is the key `ip.v4` in the `net` section.
+    pub fn read_path<T>(&self, source: impl Read, path: &str) -> Result<Option<T>, Error>
+    where
+        T: FromIniStr,
+    {
+        let (section, key) = self.split_path(path);
+        self.read_value(source, section, key)
     }
 
-    #[test]
-    fn try_section_whitespace() {
-        assert_eq!(try_section_from_line("[ SECTION ]"), Some("SECTION"));
+    /// Async version of [`IniParser::read_path`].
+    #[cfg(feature = "async")]
+    pub async fn read_path_async<T>(
+        &self,
+        source: impl AsyncRead,
+        path: &str,
+    ) -> Result<Option<T>, Error>
+    where
+        T: FromIniStr,
+    {
+        let (section, key) = self.split_path(path);
+        self.read_value_async(source, section, key).await
     }
 
-    #[test]
-    fn try_value() {
-        let name_line = "  Name=John Doe  ".to_string();
-        let parser = IniParser::default();
-
-        // make sure the variable's name check works and is case sensitive
-        assert!(parser.try_value(&name_line, "name").is_none());
-
-        let value_range = parser.try_value(&name_line, "Name").unwrap();
-        let mut new_name = String::new();
-        new_name.push_str(&name_line[..value_range.start]);
-        new_name.push_str("Ender Wiggins");
-        new_name.push_str(&name_line[value_range.end..]);
-        assert_eq!(new_name, "  Name=Ender Wiggins  ");
+    /// Read a value from a nested section given the parts of its name, e.g. `&["parent", "child"]`
+    /// reads from `[parent.child]`. The parts are joined with [`IniParser::section_separator`].
+    /// An empty `sections` is the global section.
+    ///
+    /// Keys in `[parent]` aren't inherited by `[parent.child]`, every section is separate.
+    pub fn read_value_nested<T>(
+        &self,
+        source: impl Read,
+        sections: &[&str],
+        key: &str,
+    ) -> Result<Option<T>, Error>
+    where
+        T: FromIniStr,
+    {
+        let section = self.join_sections(sections);
+        self.read_value(source, section.as_deref(), key)
     }
 
-    read_value_eq! {
-        read_value,
-        IniParser::default(),
-        r#"
-            first_name = "tom"
-        "#,
-        None,
-        "first_name",
-        Some("tom".to_string()),
+    /// Async version of [`IniParser::read_value_nested`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_nested_async<T>(
+        &self,
+        source: impl AsyncRead,
+        sections: &[&str],
+        key: &str,
+    ) -> Result<Option<T>, Error>
+    where
+        T: FromIniStr,
+    {
+        let section = self.join_sections(sections);
+        self.read_value_async(source, section.as_deref(), key).await
+    }
+
+    /// Joins the parts of a nested section name with [`IniParser::section_separator`].
+    fn join_sections(&self, sections: &[&str]) -> Option<String> {
+        if sections.is_empty() {
+            return None;
+        }
+        let mut separator = [0; 4];
+        Some(sections.join(self.section_separator.encode_utf8(&mut separator)))
+    }
+
+    /// Returns true if the key is in the section, without reading or parsing its value. Stops
+    /// reading as soon as the key is found, so duplicate keys don't matter.
+    /// If section is none, it will look in the global space.
+    pub fn contains_key(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<bool, Error> {
+        let buffer = std::io::BufReader::new(source);
+        let mut in_section = section.is_none();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            if self.is_key_line(&line, section, key, &mut in_section) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Async version of [`IniParser::contains_key`].
+    #[cfg(feature = "async")]
+    pub async fn contains_key_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<bool, Error> {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+        let mut in_section = section.is_none();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            if self.is_key_line(&line, section, key, &mut in_section) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Is the line the key in the section we are looking for? Updates `in_section` when the line
+    /// is a section header.
+    fn is_key_line(
+        &self,
+        line: &str,
+        section: Option<&str>,
+        key: &str,
+        in_section: &mut bool,
+    ) -> bool {
+        if let Some(this_section) = self.try_section(line) {
+            *in_section = self.is_section(this_section, section);
+            false
+        } else {
+            *in_section && self.try_value(line, key).is_some()
+        }
+    }
+
+    /// Reads several `(section, key)` pairs in a single pass over the source, parsing each value as
+    /// `T`. Keys that aren't found are left out of the map. A `section` of `None` is the global
+    /// section.
+    pub fn read_all_typed<T>(
+        &self,
+        source: impl Read,
+        keys_of_interest: &[(Option<&str>, &str)],
+    ) -> Result<HashMap<(Option<String>, String), T>, Error>
+    where
+        T: FromIniStr,
+    {
+        let values = self.values_unaltered(source, keys_of_interest)?;
+        self.typed_map(keys_of_interest, values)
+    }
+
+    /// Async version of [`IniParser::read_all_typed`].
+    #[cfg(feature = "async")]
+    pub async fn read_all_typed_async<T>(
+        &self,
+        source: impl AsyncRead,
+        keys_of_interest: &[(Option<&str>, &str)],
+    ) -> Result<HashMap<(Option<String>, String), T>, Error>
+    where
+        T: FromIniStr,
+    {
+        let values = self
+            .values_unaltered_async(source, keys_of_interest)
+            .await?;
+        self.typed_map(keys_of_interest, values)
+    }
+
+    /// Parses the values found by [`IniParser::values_unaltered`] into a map.
+    fn typed_map<T: FromIniStr>(
+        &self,
+        keys: &[(Option<&str>, &str)],
+        values: Vec<Option<(String, usize)>>,
+    ) -> Result<HashMap<(Option<String>, String), T>, Error> {
+        keys.iter()
+            .zip(values)
+            .filter_map(|((section, key), value)| Some((section, key, value?)))
+            .map(|(section, key, (value, line))| {
+                let section = section.map(|s| s.to_owned());
+                let parsed = self.parse_value(&value, section.as_deref(), key, line)?;
+                Ok(((section, key.to_string()), parsed))
+            })
+            .collect()
+    }
+
+    /// Read a value for the given environment, preferring the section `[base_section:env]` and
+    /// falling back to `[base_section]` if the key isn't there. The separator between the section
+    /// and environment is set by [`IniParser::env_separator`].
+    pub fn read_value_env<T>(
+        &self,
+        source: impl Read,
+        base_section: &str,
+        env: &str,
+        key: &str,
+    ) -> Result<Option<T>, Error>
+    where
+        T: FromIniStr,
+    {
+        let env_section = self.env_section(base_section, env);
+        let lookups = [(Some(env_section.as_str()), key), (Some(base_section), key)];
+        let values = self.values_unaltered(source, &lookups)?;
+        self.parse_first_value(&lookups, values)
+    }
+
+    /// Async version of [`IniParser::read_value_env`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_env_async<T>(
+        &self,
+        source: impl AsyncRead,
+        base_section: &str,
+        env: &str,
+        key: &str,
+    ) -> Result<Option<T>, Error>
+    where
+        T: FromIniStr,
+    {
+        let env_section = self.env_section(base_section, env);
+        let lookups = [(Some(env_section.as_str()), key), (Some(base_section), key)];
+        let values = self.values_unaltered_async(source, &lookups).await?;
+        self.parse_first_value(&lookups, values)
+    }
+
+    /// Parses the first value that was found by [`IniParser::values_unaltered`].
+    fn parse_first_value<T: FromIniStr>(
+        &self,
+        lookups: &[(Option<&str>, &str)],
+        values: Vec<Option<(String, usize)>>,
+    ) -> Result<Option<T>, Error> {
+        lookups
+            .iter()
+            .zip(values)
+            .find_map(|((section, key), value)| Some((section, key, value?)))
+            .map(|(section, key, (value, line))| self.parse_value(&value, *section, key, line))
+            .transpose()
+    }
+
+    /// The name of the section for the environment, e.g. `db:production`.
+    fn env_section(&self, base_section: &str, env: &str) -> String {
+        format!(
+            "{}{}{}",
+            base_section.trim(),
+            self.env_separator,
+            env.trim()
+        )
+    }
+
+    /// Read a value from a INI file source, returning the default value for the type if the key
+    /// isn't found. Errors parsing the value are still returned.
+    /// If section is none, it will look in the global space.
+    pub fn read_value_or_default<T>(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<T, Error>
+    where
+        T: FromIniStr + Default,
+    {
+        Ok(self.read_value(source, section, key)?.unwrap_or_default())
+    }
+
+    /// Read a value from an async INI file source, returning the default value for the type if the
+    /// key isn't found. See [`IniParser::read_value_or_default`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_or_default_async<T>(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<T, Error>
+    where
+        T: FromIniStr + Default,
+    {
+        Ok(self
+            .read_value_async(source, section, key)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Read a value from a INI file source, returning `default` if the key isn't found. Errors
+    /// reading the source or parsing the value are still returned.
+    /// If section is none, it will look in the global space.
+    pub fn read_value_or<T>(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+        default: T,
+    ) -> Result<T, Error>
+    where
+        T: FromIniStr,
+    {
+        Ok(self.read_value(source, section, key)?.unwrap_or(default))
+    }
+
+    /// Async version of [`IniParser::read_value_or`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_or_async<T>(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+        default: T,
+    ) -> Result<T, Error>
+    where
+        T: FromIniStr,
+    {
+        Ok(self
+            .read_value_async(source, section, key)
+            .await?
+            .unwrap_or(default))
+    }
+
+    /// Same as [`IniParser::read_value_or`], but the default is only computed if the key isn't
+    /// found.
+    pub fn read_value_or_else<T>(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+        default: impl FnOnce() -> T,
+    ) -> Result<T, Error>
+    where
+        T: FromIniStr,
+    {
+        Ok(self
+            .read_value(source, section, key)?
+            .unwrap_or_else(default))
+    }
+
+    /// Async version of [`IniParser::read_value_or_else`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_or_else_async<T>(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+        default: impl FnOnce() -> T,
+    ) -> Result<T, Error>
+    where
+        T: FromIniStr,
+    {
+        Ok(self
+            .read_value_async(source, section, key)
+            .await?
+            .unwrap_or_else(default))
+    }
+
+    /// Read a value from a INI file source, returning both the parsed value and the raw string it
+    /// was parsed from. The raw string has leading and trailing whitespace removed but is otherwise
+    /// as it appears in the file, e.g. quotes around strings are kept.
+    /// If section is none, it will look in the global space.
+    pub fn read_value_both<T>(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<(T, String)>, Error>
+    where
+        T: FromIniStr,
+    {
+        let value = self.value_unaltered(source, section, key)?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        let parsed = self.parse_value(&value, section, key, line)?;
+        Ok(Some((parsed, value)))
+    }
+
+    /// Read a value from an async INI file source, returning both the parsed value and the raw
+    /// string it was parsed from. See [`IniParser::read_value_both`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_both_async<T>(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<(T, String)>, Error>
+    where
+        T: FromIniStr,
+    {
+        let value = self.value_unaltered_async(source, section, key).await?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        let parsed = self.parse_value(&value, section, key, line)?;
+        Ok(Some((parsed, value)))
+    }
+
+    /// Read a boolean value from a INI file source, also accepting the values in
+    /// [`IniParser::bool_true_values`] and [`IniParser::bool_false_values`].
+    /// If section is none, it will look in the global space.
+    pub fn read_bool(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<bool>, Error> {
+        let value = self.value_unaltered(source, section, key)?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        self.parse_bool(&value, section, key, line).map(Some)
+    }
+
+    /// Read a boolean value from an async INI file source. See [`IniParser::read_bool`].
+    #[cfg(feature = "async")]
+    pub async fn read_bool_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<bool>, Error> {
+        let value = self.value_unaltered_async(source, section, key).await?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        self.parse_bool(&value, section, key, line).map(Some)
+    }
+
+    /// Read a value that holds a list, e.g. `servers=a,b,c`, splitting it on the delimiter and
+    /// parsing each trimmed element. Empty elements, like the one after a trailing delimiter, are
+    /// skipped so an empty value gives an empty list.
+    /// If section is none, it will look in the global space.
+    pub fn read_list<T>(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+        delimiter: char,
+    ) -> Result<Option<Vec<T>>, Error>
+    where
+        T: FromIniStr,
+    {
+        let value = self.value_unaltered(source, section, key)?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        self.parse_list(&value, section, key, line, delimiter)
+            .map(Some)
+    }
+
+    /// Read a list value from an async INI file source. See [`IniParser::read_list`].
+    #[cfg(feature = "async")]
+    pub async fn read_list_async<T>(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+        delimiter: char,
+    ) -> Result<Option<Vec<T>>, Error>
+    where
+        T: FromIniStr,
+    {
+        let value = self.value_unaltered_async(source, section, key).await?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        self.parse_list(&value, section, key, line, delimiter)
+            .map(Some)
+    }
+
+    /// Read a value as the given kind of value, for when the type of each key is only known at
+    /// runtime, e.g. from a schema.
+    /// If section is none, it will look in the global space.
+    pub fn read_value_as(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+        kind: ValueKind,
+    ) -> Result<Option<IniValue>, Error> {
+        let value = self.value_unaltered(source, section, key)?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        self.parse_value_as(&value, section, key, line, kind)
+            .map(Some)
+    }
+
+    /// Async version of [`IniParser::read_value_as`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_as_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+        kind: ValueKind,
+    ) -> Result<Option<IniValue>, Error> {
+        let value = self.value_unaltered_async(source, section, key).await?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        self.parse_value_as(&value, section, key, line, kind)
+            .map(Some)
+    }
+
+    fn parse_value_as(
+        &self,
+        value: &str,
+        section: Option<&str>,
+        key: &str,
+        line: usize,
+        kind: ValueKind,
+    ) -> Result<IniValue, Error> {
+        Ok(match kind {
+            ValueKind::Bool => IniValue::Bool(self.parse_bool(value, section, key, line)?),
+            ValueKind::Int => IniValue::Int(self.parse_value(value, section, key, line)?),
+            ValueKind::Float => IniValue::Float(self.parse_value(value, section, key, line)?),
+            ValueKind::String => IniValue::String(self.parse_value(value, section, key, line)?),
+        })
+    }
+
+    /// Read a number followed by a unit, e.g. `size=10MB`, multiplying the number by the unit's
+    /// multiplier from `units`, e.g. `[("KB", 1e3), ("MB", 1e6)]`. A number without a unit isn't
+    /// multiplied. A unit that isn't in `units` is a parse error.
+    /// If section is none, it will look in the global space.
+    pub fn read_value_with_units(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+        units: &[(&str, f64)],
+    ) -> Result<Option<f64>, Error> {
+        let value = self.value_unaltered(source, section, key)?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        parse_with_units(&value, units)
+            .map(Some)
+            .map_err(|err| Error::new_parse(err, section, key, &value, line))
+    }
+
+    /// Async version of [`IniParser::read_value_with_units`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_with_units_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+        units: &[(&str, f64)],
+    ) -> Result<Option<f64>, Error> {
+        let value = self.value_unaltered_async(source, section, key).await?;
+        let Some((value, line)) = value else {
+            return Ok(None);
+        };
+        parse_with_units(&value, units)
+            .map(Some)
+            .map_err(|err| Error::new_parse(err, section, key, &value, line))
+    }
+
+    /// Read a string value from a INI file source. If [`IniParser::file_ref_prefix`] is set and the
+    /// value starts with it, the rest of the value is treated as a path and the trimmed contents
+    /// of that file are returned instead.
+    ///
+    /// A relative path is resolved against the current working directory of the process, not the
+    /// directory of the INI file, since the source doesn't have to be a file.
+    /// [`IniParser::max_bytes`] limits the referenced file the same as it limits the source.
+    /// If section is none, it will look in the global space.
+    pub fn read_value_file_ref(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<String>, Error> {
+        let value: Option<String> = self.read_value(source, section, key)?;
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let Some(path) = self.file_ref_path(&value) else {
+            return Ok(Some(value));
+        };
+        let file = std::fs::File::open(path)?;
+        let mut bytes = Vec::new();
+        match self.max_bytes {
+            // Read one byte past the limit so we can tell it was exceeded.
+            Some(limit) => file.take(limit.saturating_add(1)).read_to_end(&mut bytes)?,
+            None => (&file).read_to_end(&mut bytes)?,
+        };
+        self.file_ref_contents(bytes).map(Some)
+    }
+
+    /// Async version of [`IniParser::read_value_file_ref`].
+    #[cfg(feature = "async")]
+    pub async fn read_value_file_ref_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<String>, Error> {
+        let value: Option<String> = self.read_value_async(source, section, key).await?;
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let Some(path) = self.file_ref_path(&value) else {
+            return Ok(Some(value));
+        };
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut bytes = Vec::new();
+        match self.max_bytes {
+            // Read one byte past the limit so we can tell it was exceeded.
+            Some(limit) => {
+                file.take(limit.saturating_add(1))
+                    .read_to_end(&mut bytes)
+                    .await?
+            }
+            None => file.read_to_end(&mut bytes).await?,
+        };
+        self.file_ref_contents(bytes).map(Some)
+    }
+
+    /// The path in the value if it starts with [`IniParser::file_ref_prefix`].
+    fn file_ref_path<'v>(&self, value: &'v str) -> Option<&'v str> {
+        self.file_ref_prefix
+            .and_then(|prefix| value.strip_prefix(prefix))
+    }
+
+    /// Checks the contents of a file referenced by a value are within [`IniParser::max_bytes`]
+    /// and valid UTF-8, returning them trimmed.
+    fn file_ref_contents(&self, bytes: Vec<u8>) -> Result<String, Error> {
+        let mut contents = String::new();
+        self.push_line(&mut contents, bytes, 0)?;
+        Ok(contents.trim().to_owned())
+    }
+
+    /// Splits a path given to [`IniParser::read_path`] into its section and key.
+    fn split_path<'p>(&self, path: &'p str) -> (Option<&'p str>, &'p str) {
+        match path.split_once(self.path_separator) {
+            Some((section, key)) => (Some(section), key),
+            None => (None, path),
+        }
+    }
+
+    fn parse_list<T: FromIniStr>(
+        &self,
+        value: &str,
+        section: Option<&str>,
+        key: &str,
+        line: usize,
+        delimiter: char,
+    ) -> Result<Vec<T>, Error> {
+        value
+            .split(delimiter)
+            .map(str::trim)
+            .filter(|element| !element.is_empty())
+            .map(|element| self.parse_value(element, section, key, line))
+            .collect()
+    }
+
+    fn parse_bool(
+        &self,
+        value: &str,
+        section: Option<&str>,
+        key: &str,
+        line: usize,
+    ) -> Result<bool, Error> {
+        self.bool_from_str(value)
+            .map_err(|err| Error::new_parse(err, section, key, value, line))
+    }
+
+    /// Parses a bool, accepting [`IniParser::bool_true_values`] and
+    /// [`IniParser::bool_false_values`] as well as the values [`bool`]'s [`FromIniStr`]
+    /// implementation supports.
+    pub(crate) fn bool_from_str(&self, value: &str) -> Result<bool, std::str::ParseBoolError> {
+        let trimmed = value.trim();
+        if self
+            .bool_true_values
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(trimmed))
+        {
+            return Ok(true);
+        }
+        if self
+            .bool_false_values
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(trimmed))
+        {
+            return Ok(false);
+        }
+        bool::from_ini_str(value)
+    }
+
+    /// Returns the value for the given section and name without any parsing, along with the line
+    /// it's on. Notably this may still have quotation marks around strings. Leading and trailing
+    /// whitespace will still be stripped though.
+    ///
+    /// Usually only use this if you are manually parsing something.
+    fn value_unaltered(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<(String, usize)>, Error> {
+        let buffer = std::io::BufReader::new(source);
+
+        // Are we in the section we are looking for?
+        // Starts in the global namespace, so if section is none it starts as true, changing as we
+        // parse different sections.
+        let mut in_section = section.is_none();
+        let mut value = None;
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            let line_number = lines.line_number;
+            if self.process_line(
+                &line,
+                line_number,
+                section,
+                key,
+                &mut in_section,
+                &mut value,
+            )? {
+                return Ok(value);
+            }
+        }
+        Ok(value)
+    }
+    /// Returns the value for the given section and name without any parsing. Notably this may
+    /// still have quotation marks around strings. Leading and trailing whitespace will still be
+    /// stripped though.
+    ///
+    /// Usually only use this if you are manually parsing something.
+    #[cfg(feature = "async")]
+    async fn value_unaltered_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<(String, usize)>, Error> {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+
+        // Are we in the section we are looking for?
+        // Starts in the global namespace, so if section is none it starts as true, changing as we
+        // parse different sections.
+        let mut in_section = section.is_none();
+        let mut value = None;
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            let line_number = lines.line_number;
+            if self.process_line(
+                &line,
+                line_number,
+                section,
+                key,
+                &mut in_section,
+                &mut value,
+            )? {
+                return Ok(value);
+            }
+        }
+        Ok(value)
+    }
+
+    /// Same as [`IniParser::value_unaltered`] but finds several `(section, key)` pairs in a single
+    /// pass over the source. The values are returned in the same order as `lookups`.
+    fn values_unaltered(
+        &self,
+        source: impl Read,
+        lookups: &[(Option<&str>, &str)],
+    ) -> Result<Vec<Option<(String, usize)>>, Error> {
+        let buffer = std::io::BufReader::new(source);
+        let mut searches: Vec<_> = lookups.iter().copied().map(ValueSearch::new).collect();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            for search in searches.iter_mut() {
+                search.process_line(self, &line, lines.line_number)?;
+            }
+        }
+        Ok(searches.into_iter().map(|search| search.value).collect())
+    }
+
+    /// Async version of [`IniParser::values_unaltered`].
+    #[cfg(feature = "async")]
+    async fn values_unaltered_async(
+        &self,
+        source: impl AsyncRead,
+        lookups: &[(Option<&str>, &str)],
+    ) -> Result<Vec<Option<(String, usize)>>, Error> {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+        let mut searches: Vec<_> = lookups.iter().copied().map(ValueSearch::new).collect();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            for search in searches.iter_mut() {
+                search.process_line(self, &line, lines.line_number)?;
+            }
+        }
+        Ok(searches.into_iter().map(|search| search.value).collect())
+    }
+
+    /// Computes a hash of the key value pairs in a section, ignoring comments, whitespace around
+    /// keys and values, and the order the keys appear in. Useful for detecting meaningful changes
+    /// to a section while ignoring formatting changes.
+    ///
+    /// The hash is stable between runs and versions of this crate so it can be stored.
+    /// Returns `None` if the section doesn't exist. The global section (`None`) always exists.
+    pub fn section_hash(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+    ) -> Result<Option<u64>, Error> {
+        let (found, pairs) = self.section_pairs(source, section)?;
+        Ok(found.then(|| hash_pairs(pairs)))
+    }
+
+    /// Computes a hash of the key value pairs in a section of an async source.
+    /// See [`IniParser::section_hash`].
+    #[cfg(feature = "async")]
+    pub async fn section_hash_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+    ) -> Result<Option<u64>, Error> {
+        let (found, pairs) = self.section_pairs_async(source, section).await?;
+        Ok(found.then(|| hash_pairs(pairs)))
+    }
+
+    /// Read all the keys and values in a section, in the order they first appear in the file.
+    /// If section is none, it will read the keys before the first section header.
+    ///
+    /// Values are returned the same way as [`IniParser::read_value_both`]'s raw value: trailing
+    /// comments and whitespace are removed but quotes are kept. Duplicate keys are handled using
+    /// [`IniParser::duplicate_keys`].
+    pub fn read_section(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let (_, pairs) = self.section_pairs(source, section)?;
+        self.resolve_duplicates(pairs, section)
+    }
+
+    /// Read all the keys and values in a section of an async source.
+    /// See [`IniParser::read_section`].
+    #[cfg(feature = "async")]
+    pub async fn read_section_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let (_, pairs) = self.section_pairs_async(source, section).await?;
+        self.resolve_duplicates(pairs, section)
+    }
+
+    /// Lists the names of all the sections in the source, in the order they first appear.
+    /// Sections that appear more than once are only listed once.
+    pub fn sections(&self, source: impl Read) -> Result<Vec<String>, Error> {
+        let buffer = std::io::BufReader::new(source);
+        let mut sections = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            self.collect_section(&line, &mut sections);
+        }
+        Ok(sections)
+    }
+
+    /// Lists the names of all the sections in an async source. See [`IniParser::sections`].
+    #[cfg(feature = "async")]
+    pub async fn sections_async(&self, source: impl AsyncRead) -> Result<Vec<String>, Error> {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+        let mut sections = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            self.collect_section(&line, &mut sections);
+        }
+        Ok(sections)
+    }
+
+    /// Lists the names of all the sections in an ini file that's already in memory. Same as
+    /// [`IniParser::sections`].
+    pub fn sections_str(&self, text: &str) -> Result<Vec<String>, Error> {
+        self.sections(text.as_bytes())
+    }
+
+    /// Adds the line's section to `sections` if it's a section header we haven't seen before.
+    fn collect_section(&self, line: &str, sections: &mut Vec<String>) {
+        if let Some(section) = self.try_section(line)
+            && !(self.empty_header_is_global && section.is_empty())
+            && !sections.iter().any(|s| self.names_match(s, section))
+        {
+            sections.push(section.to_owned());
+        }
+    }
+
+    /// Lists the keys in the section in the order they appear, along with whether each one is
+    /// active (`true`) or commented out (`false`). A commented out key is a comment line that
+    /// would be a `key=value` line without the comment delimiter, where the key has no whitespace
+    /// in it so comments that are sentences aren't mistaken for keys.
+    /// If section is none, it will look in the global space.
+    pub fn list_keys_with_state(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+    ) -> Result<Vec<(String, bool)>, Error> {
+        let buffer = std::io::BufReader::new(source);
+        let mut in_section = section.is_none();
+        let mut keys = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            self.collect_key_state(&line, section, &mut in_section, &mut keys);
+        }
+        Ok(keys)
+    }
+
+    /// Async version of [`IniParser::list_keys_with_state`].
+    #[cfg(feature = "async")]
+    pub async fn list_keys_with_state_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+    ) -> Result<Vec<(String, bool)>, Error> {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+        let mut in_section = section.is_none();
+        let mut keys = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            self.collect_key_state(&line, section, &mut in_section, &mut keys);
+        }
+        Ok(keys)
+    }
+
+    /// Adds the line's key and whether it's active to `keys` if it's an active or commented out
+    /// key in the section.
+    fn collect_key_state(
+        &self,
+        line: &str,
+        section: Option<&str>,
+        in_section: &mut bool,
+        keys: &mut Vec<(String, bool)>,
+    ) {
+        if let Some(this_section) = self.try_section(line) {
+            *in_section = self.is_section(this_section, section);
+            return;
+        }
+        if !*in_section {
+            return;
+        }
+        let (text, active) = match line.trim().strip_prefix(self.line_comments()) {
+            Some(commented) => (commented, false),
+            None => (line, true),
+        };
+        if let Some((key, _)) = self.try_key_value(text)
+            && (active || (!key.is_empty() && !key.contains(char::is_whitespace)))
+        {
+            keys.push((key.to_owned(), active));
+        }
+    }
+
+    /// Read the comment after the key's value, e.g. `vsync = on ; comment` gives
+    /// `Some((';', "comment"))`. The delimiter is returned so the comment can be written back the
+    /// same way. Returns `None` if the key isn't found or has no comment.
+    /// If section is none, it will look in the global space.
+    pub fn read_comment(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<(char, String)>, Error> {
+        let buffer = std::io::BufReader::new(source);
+        let mut in_section = section.is_none();
+        let mut comment = None;
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            if self.collect_comment(&line, section, key, &mut in_section, &mut comment) {
+                break;
+            }
+        }
+        Ok(comment.flatten())
+    }
+
+    /// Async version of [`IniParser::read_comment`].
+    #[cfg(feature = "async")]
+    pub async fn read_comment_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<(char, String)>, Error> {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+        let mut in_section = section.is_none();
+        let mut comment = None;
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            if self.collect_comment(&line, section, key, &mut in_section, &mut comment) {
+                break;
+            }
+        }
+        Ok(comment.flatten())
+    }
+
+    /// Sets `comment` to the comment of the line if it has the key, `Some(None)` when the line
+    /// has no comment. Returns true when no more lines need to be read.
+    fn collect_comment(
+        &self,
+        line: &str,
+        section: Option<&str>,
+        key: &str,
+        in_section: &mut bool,
+        comment: &mut Option<Option<(char, String)>>,
+    ) -> bool {
+        if let Some(this_section) = self.try_section(line) {
+            *in_section = self.is_section(this_section, section);
+        } else if *in_section && self.try_value(line, key).is_some() {
+            *comment = Some(self.comment_start(line).and_then(|start| {
+                let mut text = line[start..].chars();
+                let delimiter = text.next()?;
+                Some((delimiter, text.as_str().trim().to_string()))
+            }));
+            return self.duplicate_keys == DuplicateKeyStrategy::UseFirst;
+        }
+        false
+    }
+
+    /// Read every value for a key in the section, in the order they appear in the source.
+    /// [`IniParser::duplicate_keys`] is ignored since all of the duplicates are returned.
+    /// Returns an empty `Vec` if the key isn't found.
+    /// If section is none, it will look in the global space.
+    pub fn read_all_values<T>(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Vec<T>, Error>
+    where
+        T: FromIniStr,
+    {
+        self.read_all_values_lenient(source, section, key)?
+            .into_iter()
+            .collect()
+    }
+
+    /// Async version of [`IniParser::read_all_values`].
+    #[cfg(feature = "async")]
+    pub async fn read_all_values_async<T>(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Vec<T>, Error>
+    where
+        T: FromIniStr,
+    {
+        self.read_all_values_lenient_async(source, section, key)
+            .await?
+            .into_iter()
+            .collect()
+    }
+
+    /// Read every value for a key in the section like [`IniParser::read_all_values`], but a value
+    /// that fails to parse doesn't stop the others from being read. Each value has its own result so
+    /// callers can see exactly which of the duplicates failed.
+    /// If section is none, it will look in the global space.
+    pub fn read_all_values_lenient<T>(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Vec<Result<T, Error>>, Error>
+    where
+        T: FromIniStr,
+    {
+        let (_, pairs) = self.section_pairs(source, section)?;
+        Ok(self.parse_all_values(pairs, section, key))
+    }
+
+    /// Async version of [`IniParser::read_all_values_lenient`].
+    #[cfg(feature = "async")]
+    pub async fn read_all_values_lenient_async<T>(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Vec<Result<T, Error>>, Error>
+    where
+        T: FromIniStr,
+    {
+        let (_, pairs) = self.section_pairs_async(source, section).await?;
+        Ok(self.parse_all_values(pairs, section, key))
+    }
+
+    /// Parses a value from the given line using [`FromIniStr::from_ini_str_with_parser`].
+    /// The section and key are only used for the error.
+    fn parse_value<T: FromIniStr>(
+        &self,
+        value: &str,
+        section: Option<&str>,
+        key: &str,
+        line: usize,
+    ) -> Result<T, Error> {
+        let collapsed = if self.collapse_whitespace {
+            Cow::Owned(value.split_whitespace().collect::<Vec<_>>().join(" "))
+        } else {
+            Cow::Borrowed(value)
+        };
+        T::from_ini_str_with_parser(&collapsed, self)
+            .map_err(|err| Error::new_parse(err, section, key, value, line))
+    }
+
+    /// Parses the value of every pair with the given key.
+    fn parse_all_values<T: FromIniStr>(
+        &self,
+        pairs: Vec<Pair>,
+        section: Option<&str>,
+        key: &str,
+    ) -> Vec<Result<T, Error>> {
+        pairs
+            .into_iter()
+            .filter(|pair| self.names_match(&pair.key, key))
+            .map(|pair| self.parse_value(&pair.value, section, &pair.key, pair.line))
+            .collect()
+    }
+
+    /// Returns whether the section was found and all of the key value pairs in it, including
+    /// duplicates.
+    fn section_pairs(
+        &self,
+        source: impl Read,
+        section: Option<&str>,
+    ) -> Result<(bool, Vec<Pair>), Error> {
+        let buffer = std::io::BufReader::new(source);
+        let mut in_section = section.is_none();
+        let mut found = in_section;
+        let mut pairs = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            let line_number = lines.line_number;
+            self.collect_pair(
+                line,
+                line_number,
+                section,
+                &mut in_section,
+                &mut found,
+                &mut pairs,
+            );
+        }
+        Ok((found, pairs))
+    }
+
+    /// Async version of [`IniParser::section_pairs`].
+    #[cfg(feature = "async")]
+    async fn section_pairs_async(
+        &self,
+        source: impl AsyncRead,
+        section: Option<&str>,
+    ) -> Result<(bool, Vec<Pair>), Error> {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+        let mut in_section = section.is_none();
+        let mut found = in_section;
+        let mut pairs = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            let line_number = lines.line_number;
+            self.collect_pair(
+                line,
+                line_number,
+                section,
+                &mut in_section,
+                &mut found,
+                &mut pairs,
+            );
+        }
+        Ok((found, pairs))
+    }
+
+    /// Removes duplicate keys according to [`IniParser::duplicate_keys`], keeping the position of
+    /// the first instance of each key.
+    fn resolve_duplicates(
+        &self,
+        pairs: Vec<Pair>,
+        section: Option<&str>,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let mut resolved: Vec<(String, String)> = Vec::with_capacity(pairs.len());
+        for Pair { key, value, line } in pairs {
+            let Some(existing) = resolved.iter_mut().find(|(k, _)| self.names_match(k, &key))
+            else {
+                resolved.push((key, value));
+                continue;
+            };
+            match self.duplicate_keys {
+                DuplicateKeyStrategy::UseLast => existing.1 = value,
+                DuplicateKeyStrategy::UseFirst => {}
+                DuplicateKeyStrategy::Error => {
+                    return Err(Error::DuplicateKey {
+                        key,
+                        section: section.map(|s| s.to_owned()),
+                        line,
+                    });
+                }
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Lazily reads every key value pair in the source, in the order they appear. Lines are read
+    /// as the iterator is advanced, so the whole source is never loaded at once. Keys before the
+    /// first section header have a section of `None`.
+    ///
+    /// The iterator ends after the first error.
+    pub fn entries(&self, source: impl Read) -> impl Iterator<Item = Result<Entry, Error>> {
+        let mut lines = Lines::new(std::io::BufReader::new(source));
+        let mut section = None;
+        let mut failed = false;
+        std::iter::from_fn(move || {
+            while !failed {
+                let line = match self.next_line(&mut lines) {
+                    Ok(Some(line)) => line,
+                    Ok(None) => return None,
+                    Err(err) => {
+                        failed = true;
+                        return Some(Err(err));
+                    }
+                };
+                if let Some(this_section) = self.try_section(&line) {
+                    section = self.header_section(this_section);
+                } else if let Some((key, range)) = self.try_key_value(&line) {
+                    return Some(Ok(Entry {
+                        section: section.clone(),
+                        key: key.to_owned(),
+                        value: self.value_in_range(&line, range).to_owned(),
+                    }));
+                }
+            }
+            None
+        })
+    }
+
+    /// Finds every key that appears more than once in the same section, returning the section,
+    /// key and how many times it appears, in the order the keys first appear. Sections with the
+    /// same name are treated as one section. [`IniParser::duplicate_keys`] is ignored.
+    pub fn find_duplicate_keys(
+        &self,
+        source: impl Read,
+    ) -> Result<Vec<(Option<String>, String, usize)>, Error> {
+        let buffer = std::io::BufReader::new(source);
+        let mut section = None;
+        let mut counts = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            self.count_key(&line, &mut section, &mut counts);
+        }
+        counts.retain(|(_, _, count)| *count > 1);
+        Ok(counts)
+    }
+
+    /// Async version of [`IniParser::find_duplicate_keys`].
+    #[cfg(feature = "async")]
+    pub async fn find_duplicate_keys_async(
+        &self,
+        source: impl AsyncRead,
+    ) -> Result<Vec<(Option<String>, String, usize)>, Error> {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+        let mut section = None;
+        let mut counts = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            self.count_key(&line, &mut section, &mut counts);
+        }
+        counts.retain(|(_, _, count)| *count > 1);
+        Ok(counts)
+    }
+
+    /// Reads the key from every section it's in, in the order the sections first appear, along
+    /// with the name of the section, `None` for the global section. The source is only read once.
+    ///
+    /// Sections with the same name are treated as one section, and a key that appears more than
+    /// once in a section is handled according to [`IniParser::duplicate_keys`], so there is at
+    /// most one value per section.
+    pub fn read_key_all_sections<T>(
+        &self,
+        source: impl Read,
+        key: &str,
+    ) -> Result<Vec<(Option<String>, T)>, Error>
+    where
+        T: FromIniStr,
+    {
+        let buffer = std::io::BufReader::new(source);
+        let mut section = None;
+        let mut found = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line(&mut lines)? {
+            let line_number = lines.line_number;
+            self.collect_key_in_section(&line, line_number, key, &mut section, &mut found)?;
+        }
+        self.parse_key_in_sections(found, key)
+    }
+
+    /// Async version of [`IniParser::read_key_all_sections`].
+    #[cfg(feature = "async")]
+    pub async fn read_key_all_sections_async<T>(
+        &self,
+        source: impl AsyncRead,
+        key: &str,
+    ) -> Result<Vec<(Option<String>, T)>, Error>
+    where
+        T: FromIniStr,
+    {
+        let buffer = Box::pin(tokio::io::BufReader::new(source));
+        let mut section = None;
+        let mut found = Vec::new();
+        let mut lines = Lines::new(buffer);
+        while let Some(line) = self.next_line_async(&mut lines).await? {
+            let line_number = lines.line_number;
+            self.collect_key_in_section(&line, line_number, key, &mut section, &mut found)?;
+        }
+        self.parse_key_in_sections(found, key)
+    }
+
+    /// Adds the key's value to `found` if the line has it, or switches `section` if it's a
+    /// header. Each section has one entry in `found`, with the section, value and line number.
+    fn collect_key_in_section(
+        &self,
+        line: &str,
+        line_number: usize,
+        key: &str,
+        section: &mut Option<String>,
+        found: &mut Vec<(Option<String>, String, usize)>,
+    ) -> Result<(), Error> {
+        if let Some(this_section) = self.try_section(line) {
+            *section = self.header_section(this_section);
+            return Ok(());
+        }
+        let Some(value) = self.value_or_flag_value(line, key) else {
+            return Ok(());
+        };
+        let Some(existing) = found
+            .iter_mut()
+            .find(|(s, _, _)| self.same_section(s, section))
+        else {
+            found.push((section.clone(), value.to_owned(), line_number));
+            return Ok(());
+        };
+        match self.duplicate_keys {
+            DuplicateKeyStrategy::UseLast => {
+                existing.1 = value.to_owned();
+                existing.2 = line_number;
+            }
+            DuplicateKeyStrategy::UseFirst => {}
+            DuplicateKeyStrategy::Error => {
+                return Err(Error::DuplicateKey {
+                    key: key.to_owned(),
+                    section: section.clone(),
+                    line: line_number,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the values found by [`IniParser::collect_key_in_section`].
+    fn parse_key_in_sections<T: FromIniStr>(
+        &self,
+        found: Vec<(Option<String>, String, usize)>,
+        key: &str,
+    ) -> Result<Vec<(Option<String>, T)>, Error> {
+        found
+            .into_iter()
+            .map(|(section, value, line)| {
+                let value = self.parse_value(&value, section.as_deref(), key, line)?;
+                Ok((section, value))
+            })
+            .collect()
+    }
+
+    /// Are the two sections, `None` for the global section, the same section?
+    fn same_section(&self, a: &Option<String>, b: &Option<String>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => self.names_match(a, b),
+            (a, b) => a == b,
+        }
+    }
+
+    /// Counts the line's key in `counts` if it has one, or switches `section` if it's a header.
+    fn count_key(
+        &self,
+        line: &str,
+        section: &mut Option<String>,
+        counts: &mut Vec<(Option<String>, String, usize)>,
+    ) {
+        if let Some(this_section) = self.try_section(line) {
+            *section = self.header_section(this_section);
+        } else if let Some((key, _)) = self.try_key_value(line) {
+            match counts
+                .iter_mut()
+                .find(|(s, k, _)| self.same_section(s, section) && self.names_match(k, key))
+            {
+                Some((_, _, count)) => *count += 1,
+                None => counts.push((section.clone(), key.to_owned(), 1)),
+            }
+        }
+    }
+
+    /// The section that a header starts, `None` for the global section.
+    fn header_section(&self, this_section: &str) -> Option<String> {
+        (!(self.empty_header_is_global && this_section.is_empty())).then(|| this_section.to_owned())
+    }
+
+    /// Adds the line's key and value to `pairs` if it's in the section we are looking for.
+    fn collect_pair(
+        &self,
+        line: String,
+        line_number: usize,
+        section: Option<&str>,
+        in_section: &mut bool,
+        found: &mut bool,
+        pairs: &mut Vec<Pair>,
+    ) {
+        if let Some(this_section) = self.try_section(&line) {
+            *in_section = self.is_section(this_section, section);
+            *found |= *in_section;
+        } else if *in_section && let Some((key, range)) = self.try_key_value(&line) {
+            pairs.push(Pair {
+                key: key.to_owned(),
+                value: self.value_in_range(&line, range).to_owned(),
+                line: line_number,
+            });
+        }
+    }
+
+    /// The key's value in the line, or `true` if the line is the key as a flag. See
+    /// [`IniParser::flag_keys_as_true`].
+    pub(crate) fn value_or_flag_value<'l>(&self, line: &'l str, key: &str) -> Option<&'l str> {
+        if let Some(range) = self.try_value(line, key) {
+            Some(self.value_in_range(line, range))
+        } else {
+            let flag = self.try_flag(line)?;
+            self.names_match(flag, key.trim()).then_some("true")
+        }
+    }
+
+    /// The value in the range of the line, with triple quotes removed if
+    /// [`IniParser::triple_quote_strings`] is enabled.
+    pub(crate) fn value_in_range<'l>(&self, line: &'l str, range: Range<usize>) -> &'l str {
+        let value = &line[range];
+        if self.triple_quote_strings && is_triple_quoted(value) {
+            &value[TRIPLE_QUOTE.len()..value.len() - TRIPLE_QUOTE.len()]
+        } else {
+            value
+        }
+    }
+
+    /// Reads the next line from the source. If [`IniParser::line_continuation`] is enabled, lines
+    /// ending with `\\` will have the following line joined onto them.
+    pub(crate) fn next_line(
+        &self,
+        lines: &mut Lines<impl BufRead>,
+    ) -> Result<Option<String>, Error> {
+        let Some(mut line) = lines.next_raw(self)? else {
+            return Ok(None);
+        };
+        lines.line_number = lines.read;
+        let mut unfinished = self.unfinished(&mut line, false, None);
+        while let Some(waiting) = unfinished {
+            let Some(next_line) = lines.next_raw(self)? else {
+                if self.opens_quote(&line).is_some() {
+                    return Err(Error::UnterminatedQuote {
+                        line: lines.line_number,
+                    });
+                }
+                break;
+            };
+            unfinished = self.join_line(&mut line, &next_line, waiting, false);
+        }
+        if self.value_on_next_line_when_empty
+            && let Some(start) = self.empty_value_start(&line)
+            && let Some(next_line) = lines.next_raw(self)?
+        {
+            if is_indented_further(&next_line, &line) && !self.is_comment_line(&next_line) {
+                line.insert_str(start, next_line.trim());
+            } else {
+                lines.put_back(next_line);
+            }
+        }
+        Ok(Some(line))
+    }
+
+    /// Reads the next line from an async source. If [`IniParser::line_continuation`] is enabled,
+    /// lines ending with `\\` will have the following line joined onto them.
+    #[cfg(feature = "async")]
+    async fn next_line_async(
+        &self,
+        lines: &mut Lines<impl AsyncBufRead + Unpin>,
+    ) -> Result<Option<String>, Error> {
+        let Some(mut line) = lines.next_raw_async(self).await? else {
+            return Ok(None);
+        };
+        lines.line_number = lines.read;
+        let mut unfinished = self.unfinished(&mut line, false, None);
+        while let Some(waiting) = unfinished {
+            let Some(next_line) = lines.next_raw_async(self).await? else {
+                if self.opens_quote(&line).is_some() {
+                    return Err(Error::UnterminatedQuote {
+                        line: lines.line_number,
+                    });
+                }
+                break;
+            };
+            unfinished = self.join_line(&mut line, &next_line, waiting, false);
+        }
+        if self.value_on_next_line_when_empty
+            && let Some(start) = self.empty_value_start(&line)
+            && let Some(next_line) = lines.next_raw_async(self).await?
+        {
+            if is_indented_further(&next_line, &line) && !self.is_comment_line(&next_line) {
+                line.insert_str(start, next_line.trim());
+            } else {
+                lines.put_back(next_line);
+            }
+        }
+        Ok(Some(line))
+    }
+
+    /// If the line is a key with an empty value, returns where the value would start.
+    pub(crate) fn empty_value_start(&self, line: &str) -> Option<usize> {
+        let (_, range) = self.try_key_value(line)?;
+        range.is_empty().then_some(range.start)
+    }
+
+    /// If [`IniParser::value_on_next_line_when_empty`] makes `line` the value of the key with an
+    /// empty value on the line before it, returns the range of the value in `line`. `key_indent`
+    /// is how much leading whitespace the key's line has. Used when writing, where each line is
+    /// looked at separately.
+    pub(crate) fn indented_value(&self, key_indent: usize, line: &str) -> Option<Range<usize>> {
+        let start = indent_of(line);
+        if line.trim().is_empty() || start <= key_indent || self.is_comment_line(line) {
+            return None;
+        }
+        let end = line[..self.comment_start(line).unwrap_or(line.len())]
+            .trim_end()
+            .len();
+        Some(start..end.max(start))
+    }
+
+    /// Reads a line into `line` like [`BufRead::read_line`], keeping the line ending.
+    /// `bytes_before` is how many bytes have already been read from the source, it's used to
+    /// enforce [`IniParser::max_bytes`] and to say where invalid UTF-8 is.
+    /// Returns the number of bytes read.
+    pub(crate) fn read_line(
+        &self,
+        source: &mut impl BufRead,
+        line: &mut String,
+        bytes_before: u64,
+    ) -> Result<usize, Error> {
+        let mut bytes = Vec::new();
+        let bytes_read = match self.max_bytes {
+            // Read one byte past the limit so we can tell it was exceeded.
+            Some(limit) => source
+                .take(limit.saturating_sub(bytes_before).saturating_add(1))
+                .read_until(b'\n', &mut bytes)?,
+            None => source.read_until(b'\n', &mut bytes)?,
+        };
+        self.push_line(line, bytes, bytes_before)?;
+        Ok(bytes_read)
+    }
+
+    /// Async version of [`IniParser::read_line`].
+    #[cfg(feature = "async")]
+    pub(crate) async fn read_line_async(
+        &self,
+        source: &mut (impl AsyncBufRead + Unpin),
+        line: &mut String,
+        bytes_before: u64,
+    ) -> Result<usize, Error> {
+        let mut bytes = Vec::new();
+        let bytes_read = match self.max_bytes {
+            // Read one byte past the limit so we can tell it was exceeded.
+            Some(limit) => {
+                source
+                    .take(limit.saturating_sub(bytes_before).saturating_add(1))
+                    .read_until(b'\n', &mut bytes)
+                    .await?
+            }
+            None => source.read_until(b'\n', &mut bytes).await?,
+        };
+        self.push_line(line, bytes, bytes_before)?;
+        Ok(bytes_read)
+    }
+
+    /// Checks a line read by [`IniParser::read_line`] is within [`IniParser::max_bytes`] and is
+    /// valid UTF-8 before adding it to `line`.
+    fn push_line(&self, line: &mut String, bytes: Vec<u8>, bytes_before: u64) -> Result<(), Error> {
+        let found = bytes_before + bytes.len() as u64;
+        if let Some(limit) = self.max_bytes
+            && found > limit
+        {
+            return Err(Error::TooLarge { limit, found });
+        }
+        let text = String::from_utf8(bytes).map_err(|err| Error::InvalidUtf8 {
+            byte_offset: (bytes_before as usize) + err.utf8_error().valid_up_to(),
+        })?;
+        line.push_str(&text);
+        Ok(())
+    }
+
+    /// Mainly used to extract common functionality between async and sync implementations.
+    /// Returns true if we found the final value. (Note that depending on duplicate handling, this
+    /// may not be the first time we see the value)
+    fn process_line(
+        &self,
+        line: &str,
+        line_number: usize,
+        section: Option<&str>,
+        key: &str,
+        in_section: &mut bool,
+        value: &mut Option<(String, usize)>,
+    ) -> Result<bool, Error> {
+        if let Some(this_section) = self.try_section(line) {
+            *in_section = self.is_section(this_section, section);
+        } else if *in_section && let Some(found) = self.value_or_flag_value(line, key) {
+            return self.record_value(found, line_number, section, key, value);
+        }
+
+        Ok(false)
+    }
+
+    /// Same as [`IniParser::process_line`] but finds the value with [`IniParser::raw_value`].
+    fn process_line_raw(
+        &self,
+        line: &str,
+        line_number: usize,
+        section: Option<&str>,
+        key: &str,
+        in_section: &mut bool,
+        value: &mut Option<(String, usize)>,
+    ) -> Result<bool, Error> {
+        if let Some(this_section) = self.try_section(line) {
+            *in_section = self.is_section(this_section, section);
+        } else if *in_section && let Some(found) = self.raw_value(line, key) {
+            return self.record_value(found, line_number, section, key, value);
+        }
+        Ok(false)
+    }
+
+    /// Same as [`IniParser::process_line`] but keeps the whole line the value is in, so the value
+    /// can be borrowed from it without being copied.
+    fn process_value_line(
+        &self,
+        line: String,
+        line_number: usize,
+        section: Option<&str>,
+        key: &str,
+        in_section: &mut bool,
+        value_line: &mut Option<String>,
+    ) -> Result<bool, Error> {
+        if let Some(this_section) = self.try_section(&line) {
+            *in_section = self.is_section(this_section, section);
+        } else if *in_section && self.value_or_flag_value(&line, key).is_some() {
+            let had_previous = value_line.replace(line).is_some();
+            return self.value_found(had_previous, line_number, section, key);
+        }
+        Ok(false)
+    }
+
+    /// Stores a value that was found for the key, taking [`IniParser::duplicate_keys`] into
+    /// account. Returns true if no more lines need to be read.
+    fn record_value(
+        &self,
+        found: &str,
+        line_number: usize,
+        section: Option<&str>,
+        key: &str,
+        value: &mut Option<(String, usize)>,
+    ) -> Result<bool, Error> {
+        let had_previous = value.is_some();
+        *value = Some((found.to_string(), line_number));
+        self.value_found(had_previous, line_number, section, key)
+    }
+
+    /// Takes [`IniParser::duplicate_keys`] into account after a value for the key is found.
+    /// Returns true if no more lines need to be read.
+    fn value_found(
+        &self,
+        had_previous: bool,
+        line_number: usize,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<bool, Error> {
+        match self.duplicate_keys {
+            DuplicateKeyStrategy::Error if had_previous => Err(Error::DuplicateKey {
+                key: key.to_string(),
+                section: section.map(|s| s.to_owned()),
+                line: line_number,
+            }),
+            DuplicateKeyStrategy::UseFirst => Ok(true),
+            _ => Ok(false),
+        }
+    }
+
+    /// The key's value exactly as it is in the line, from right after the value delimiter up to
+    /// the comment or the end of the line.
+    fn raw_value<'l>(&self, line: &'l str, key: &str) -> Option<&'l str> {
+        let range = self.try_value(line, key)?;
+        // Include the whitespace between the delimiter and the value
+        let start = line[..range.start].trim_end().len();
+        let end = self.comment_start(line).unwrap_or(line.len());
+        Some(line[start..end].trim_end_matches(['\r', '\n']))
+    }
+}
+
+/// Wraps the lines of a source, counting them so errors can say which line they're about.
+pub(crate) struct Lines<B> {
+    source: B,
+    /// How many lines have been read from the source so far.
+    read: usize,
+    /// How many bytes have been read from the source so far.
+    bytes_read: u64,
+    /// A line that was read ahead and put back, to be returned next.
+    put_back: Option<String>,
+    /// The line that the last line returned by [`IniParser::next_line`] started on, starting at 1.
+    pub(crate) line_number: usize,
+}
+
+impl<B> Lines<B> {
+    pub(crate) fn new(source: B) -> Self {
+        Self {
+            source,
+            read: 0,
+            bytes_read: 0,
+            put_back: None,
+            line_number: 0,
+        }
+    }
+
+    /// Puts a line back so it's returned again by the next read. Only one line can be put back.
+    fn put_back(&mut self, line: String) {
+        self.put_back = Some(line);
+    }
+
+    /// Counts a line that was just read, returning it without its line ending.
+    fn finish_line(&mut self, mut line: String, bytes_read: usize) -> Option<String> {
+        if bytes_read == 0 {
+            return None;
+        }
+        self.read += 1;
+        self.bytes_read += bytes_read as u64;
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Some(line)
+    }
+}
+
+impl<B: BufRead> Lines<B> {
+    fn next_raw(&mut self, parser: &IniParser) -> Result<Option<String>, Error> {
+        if let Some(line) = self.put_back.take() {
+            return Ok(Some(line));
+        }
+        let mut line = String::new();
+        let bytes_read = parser.read_line(&mut self.source, &mut line, self.bytes_read)?;
+        Ok(self.finish_line(line, bytes_read))
+    }
+}
+
+#[cfg(feature = "async")]
+impl<B: AsyncBufRead + Unpin> Lines<B> {
+    async fn next_raw_async(&mut self, parser: &IniParser<'_>) -> Result<Option<String>, Error> {
+        if let Some(line) = self.put_back.take() {
+            return Ok(Some(line));
+        }
+        let mut line = String::new();
+        let bytes_read = parser
+            .read_line_async(&mut self.source, &mut line, self.bytes_read)
+            .await?;
+        Ok(self.finish_line(line, bytes_read))
+    }
+}
+
+/// Puts each of the `;` separated pairs in a value read by [`IniParser::read_nested_value`] on
+/// their own line.
+fn nested_ini(value: &str) -> String {
+    value.replace(';', "\n")
+}
+
+/// Does `line` have more leading whitespace than `other`? Blank lines never do.
+fn is_indented_further(line: &str, other: &str) -> bool {
+    !line.trim().is_empty() && indent_of(line) > indent_of(other)
+}
+
+/// How many bytes of leading whitespace the line has.
+pub(crate) fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// A key value pair read by [`IniParser::entries`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The section the key is in, `None` for the global section.
+    pub section: Option<String>,
+    pub key: String,
+    /// The value as it appears in the file, e.g. quotes around strings are kept.
+    pub value: String,
+}
+
+/// Where a value is in the source, returned by [`IniParser::read_value_located`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The line the key is on, starting at 1.
+    pub line: usize,
+    /// The bytes of the value as it appears in the source, e.g. quotes around strings are
+    /// included. Empty for a flag key, see [`IniParser::flag_keys_as_true`].
+    pub byte_range: Range<usize>,
+}
+
+/// A key value pair in a section along with the line it's on.
+struct Pair {
+    key: String,
+    value: String,
+    line: usize,
+}
+
+/// The state of looking for a key in one section, used when looking in several sections at once.
+struct ValueSearch<'s> {
+    section: Option<&'s str>,
+    key: &'s str,
+    in_section: bool,
+    value: Option<(String, usize)>,
+    /// Set once the final value has been found.
+    done: bool,
+}
+
+impl<'s> ValueSearch<'s> {
+    fn new((section, key): (Option<&'s str>, &'s str)) -> Self {
+        Self {
+            section,
+            key,
+            in_section: section.is_none(),
+            value: None,
+            done: false,
+        }
+    }
+
+    fn process_line(
+        &mut self,
+        parser: &IniParser,
+        line: &str,
+        line_number: usize,
+    ) -> Result<(), Error> {
+        if !self.done {
+            self.done = parser.process_line(
+                line,
+                line_number,
+                self.section,
+                self.key,
+                &mut self.in_section,
+                &mut self.value,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Hashes the key value pairs using FNV-1a so the result doesn't depend on the order of the pairs
+/// or change between Rust versions like [`std::hash::DefaultHasher`] may.
+fn hash_pairs(pairs: Vec<Pair>) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    let mut pairs: Vec<_> = pairs.into_iter().map(|p| (p.key, p.value)).collect();
+    pairs.sort();
+    let mut hash = FNV_OFFSET_BASIS;
+    for (key, value) in pairs {
+        // Separate the key and value with bytes that can't appear in utf-8 so `a=bc` and `ab=c`
+        // don't hash the same.
+        let bytes = key.bytes().chain([0xff]).chain(value.bytes()).chain([0xfe]);
+        for byte in bytes {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use crate::{DuplicateKeyStrategy, IniGroupedInt, ParseError, try_section_from_line};
+
+    use super::*;
+    #[cfg(feature = "async")]
+    use ::paste::paste;
+
+    /// Generate async and sync versions of tests that get values from a given ini
+    macro_rules! read_value_eq {
+        {
+            $test_name:ident,
+            $parser:expr,
+            $ini_file_string:expr,
+            $section:expr,
+            $key:expr,
+            $expected:expr $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = $parser;
+                let reader = std::io::Cursor::new($ini_file_string);
+                let value = parser.read_value(reader, $section, $key).unwrap();
+                assert_eq!(value, $expected);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    let parser = $parser;
+                    let reader = std::io::Cursor::new($ini_file_string);
+                    let value = parser.read_value_async(reader, $section, $key).await.unwrap();
+                    assert_eq!(value, $expected);
+                }
+            }
+        };
+    }
+
+    /// Generate async and sync versions of tests that get values from a given ini and assert that
+    /// the result matches a pattern. Useful for partially matching errors.
+    macro_rules! read_value_matches {
+        {
+            $test_name:ident,
+            $parser:expr,
+            $ini_file_string:expr,
+            $section:expr,
+            $key:expr,
+            $expected:pat $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = $parser;
+                let reader = std::io::Cursor::new($ini_file_string);
+                let value = parser.read_value(reader, $section, $key);
+                ::assert_matches::assert_matches!(value, $expected);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    let parser = $parser;
+                    let reader = std::io::Cursor::new($ini_file_string);
+                    let value = parser.read_value_async(reader, $section, $key).await;
+                    ::assert_matches::assert_matches!(value, $expected);
+                }
+            }
+        };
+    }
+
+    #[test]
+    fn try_section_not() {
+        assert_eq!(try_section_from_line("This is a line"), None);
+    }
+
+    #[test]
+    fn try_section_no_comment() {
+        assert_eq!(try_section_from_line("[SECTION]"), Some("SECTION"));
+    }
+
+    #[test]
+    fn try_section_comment() {
+        assert_eq!(
+            try_section_from_line("[SECTION] # This is a comment"),
+            Some("SECTION")
+        );
+    }
+
+    #[test]
+    fn try_section_whitespace() {
+        assert_eq!(try_section_from_line("[ SECTION ]"), Some("SECTION"));
+    }
+
+    #[test]
+    fn try_section_trailing_content() {
+        let parser = IniParser::default();
+        assert_eq!(parser.try_section("[SECTION]extra"), Some("SECTION"));
+        assert_eq!(parser.try_section("[SECTION] ; comment"), Some("SECTION"));
+    }
+
+    #[test]
+    fn try_section_strict() {
+        let parser = IniParser {
+            strict_sections: true,
+            ..Default::default()
+        };
+        assert_eq!(parser.try_section("[SECTION]extra"), None);
+        assert_eq!(parser.try_section("[SECTION] ; comment"), Some("SECTION"));
+        assert_eq!(parser.try_section("[SECTION] # comment"), Some("SECTION"));
+        assert_eq!(parser.try_section(" [SECTION]  "), Some("SECTION"));
+    }
+
+    #[test]
+    fn try_section_indented() {
+        let parser = IniParser::default();
+        assert_eq!(parser.try_section("    [SECTION]"), Some("SECTION"));
+        assert_eq!(parser.try_section("\t[SECTION]"), Some("SECTION"));
+
+        let parser = IniParser {
+            allow_indented_sections: false,
+            ..Default::default()
+        };
+        assert_eq!(parser.try_section("    [SECTION]"), None);
+        assert_eq!(parser.try_section("\t[SECTION]"), None);
+        assert_eq!(parser.try_section("[SECTION]"), Some("SECTION"));
+        assert_eq!(parser.try_section("\u{feff}[SECTION]"), Some("SECTION"));
+    }
+
+    const INDENTED_INI: &str = indoc::indoc! {"
+        [server]
+            port = 1
+            [client]
+                port = 2
+    "};
+
+    read_value_eq! {
+        read_value_indented,
+        IniParser::default(),
+        INDENTED_INI,
+        Some("client"),
+        "port",
+        Some(2),
+    }
+
+    read_value_eq! {
+        read_value_indented_key,
+        IniParser::default(),
+        INDENTED_INI,
+        Some("server"),
+        "port",
+        Some(1),
+    }
+
+    read_value_eq! {
+        read_value_indented_sections_not_allowed,
+        IniParser{ allow_indented_sections: false, ..Default::default() },
+        INDENTED_INI,
+        Some("server"),
+        "port",
+        Some(2),
+    }
+
+    read_value_eq! {
+        read_value_indented_sections_not_allowed_missing,
+        IniParser{ allow_indented_sections: false, ..Default::default() },
+        INDENTED_INI,
+        Some("client"),
+        "port",
+        None::<u16>,
+    }
+
+    read_value_eq! {
+        read_value_strict_sections,
+        IniParser{ strict_sections: true, ..Default::default() },
+        indoc::indoc! {"
+            [server]
+            port = 1
+            [other]extra
+            port = 2
+        "},
+        Some("server"),
+        "port",
+        Some(2),
+    }
+
+    #[test]
+    fn try_value() {
+        let name_line = "  Name=John Doe  ".to_string();
+        let parser = IniParser::default();
+
+        // make sure the variable's name check works and is case sensitive
+        assert!(parser.try_value(&name_line, "name").is_none());
+
+        let value_range = parser.try_value(&name_line, "Name").unwrap();
+        let mut new_name = String::new();
+        new_name.push_str(&name_line[..value_range.start]);
+        new_name.push_str("Ender Wiggins");
+        new_name.push_str(&name_line[value_range.end..]);
+        assert_eq!(new_name, "  Name=Ender Wiggins  ");
+    }
+
+    read_value_eq! {
+        read_value,
+        IniParser::default(),
+        r#"
+            first_name = "tom"
+        "#,
+        None,
+        "first_name",
+        Some("tom".to_string()),
+    }
+
+    /// Brackets after other content on a line aren't a section header. A line with a delimiter is
+    /// still a key, and the brackets are kept as part of its value rather than being dropped.
+    const MID_LINE_BRACKET_INI: &str = r#"
+        [user]
+        a = b [note]
+        value [other]
+        c = d
+    "#;
+
+    read_value_eq! {
+        read_value_mid_line_bracket,
+        IniParser::default(),
+        MID_LINE_BRACKET_INI,
+        Some("user"),
+        "a",
+        Some("b [note]".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_mid_line_bracket_same_section,
+        IniParser::default(),
+        MID_LINE_BRACKET_INI,
+        Some("user"),
+        "c",
+        Some("d".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_mid_line_bracket_not_section,
+        IniParser::default(),
+        MID_LINE_BRACKET_INI,
+        Some("other"),
+        "c",
+        None::<String>,
+    }
+
+    const VALUE_ON_NEXT_LINE_INI: &str = r#"
+        [paths]
+        home=
+            /home/tom # where tom lives
+        work=x
+            /srv
+        tmp=
+        log=/var/log
+    "#;
+
+    fn value_on_next_line_parser() -> IniParser<'static> {
+        IniParser {
+            value_on_next_line_when_empty: true,
+            ..Default::default()
+        }
+    }
+
+    read_value_eq! {
+        read_value_on_next_line,
+        value_on_next_line_parser(),
+        VALUE_ON_NEXT_LINE_INI,
+        Some("paths"),
+        "home",
+        Some("/home/tom".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_on_next_line_not_empty,
+        value_on_next_line_parser(),
+        VALUE_ON_NEXT_LINE_INI,
+        Some("paths"),
+        "work",
+        Some("x".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_on_next_line_not_indented,
+        value_on_next_line_parser(),
+        VALUE_ON_NEXT_LINE_INI,
+        Some("paths"),
+        "tmp",
+        Some("".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_on_next_line_put_back,
+        value_on_next_line_parser(),
+        VALUE_ON_NEXT_LINE_INI,
+        Some("paths"),
+        "log",
+        Some("/var/log".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_on_next_line_disabled,
+        IniParser::default(),
+        VALUE_ON_NEXT_LINE_INI,
+        Some("paths"),
+        "home",
+        Some("".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_section,
+        IniParser::default(),
+        r#"
+            [user]
+            first_name = "tom"
+        "#,
+        Some("user"),
+        "first_name",
+        Some("tom".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_no_section,
+        IniParser::default(),
+        r#"
+            date = "10/29/2024"
+
+            [user]
+            first_name = "tom"
+            date = "shouldn't get this"
+        "#,
+        None,
+        "date",
+        Some("10/29/2024".to_string()),
+    }
+
+    read_value_eq! {
+        read_unquoted_string,
+        IniParser::default(),
+        r#"
+            [user]
+            first_name = tom
+        "#,
+        Some("user"),
+        "first_name",
+        Some("tom".to_string()),
+    }
+
+    read_value_eq! {
+        read_bool_true,
+        IniParser::default(),
+        r#"
+            [user]
+            first_name = tom
+            is_admin = true
+        "#,
+        Some("user"),
+        "is_admin",
+        Some(true),
+    }
+
+    read_value_matches! {
+        read_bool_quotes,
+        IniParser::default(),
+        r#"
+            [user]
+            first_name = tom
+            is_admin = "true"
+        "#,
+        Some("user"),
+        "is_admin",
+        Err::<Option<bool>, _>(Error::Parse(_)),
+    }
+
+    read_value_matches! {
+        read_bool_uppercase,
+        IniParser::default(),
+        r#"
+            [user]
+            first_name = tom
+            is_admin = TRUE
+        "#,
+        Some("user"),
+        "is_admin",
+        Ok(Some(true)),
+    }
+    read_value_matches! {
+        read_bool_num_true,
+        IniParser::default(),
+        r#"
+            [user]
+            first_name = tom
+            is_admin = 1
+        "#,
+        Some("user"),
+        "is_admin",
+        Ok(Some(true)),
+    }
+    read_value_matches! {
+        read_bool_num_false,
+        IniParser::default(),
+        r#"
+            [user]
+            first_name = tom
+            is_admin = 0
+        "#,
+        Some("user"),
+        "is_admin",
+        Ok(Some(false)),
+    }
+
+    read_value_eq! {
+        read_bool_false,
+        IniParser::default(),
+        r#"
+            [user]
+            first_name = bill
+            is_admin = false
+        "#,
+        Some("user"),
+        "is_admin",
+        Some(false),
+    }
+
+    read_value_eq! {
+        read_value_multiline,
+        IniParser{ line_continuation: true, ..Default::default() },
+        r#"
+            description = "a longer \
+            value \
+            spanning multiple \
+            lines"
+        "#,
+        None,
+        "description",
+        Some("a longer value spanning multiple lines".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_continuation_char,
+        IniParser{ line_continuation: true, continuation_char: '&', ..Default::default() },
+        "description = first &\n  second \\\nother = value\n",
+        None,
+        "description",
+        Some("first second \\".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_continuation_in_trailing_comment,
+        IniParser{ line_continuation: true, ..Default::default() },
+        "a = 1 ; see \\\nb = 2\n",
+        None,
+        "b",
+        Some(2),
+    }
+
+    read_value_eq! {
+        read_value_servertest_map,
+        IniParser{ line_continuation: true, ..Default::default() },
+        "PVP=true\nMap=Muldraugh, KY ; see C:\\Users\\Zomboid\\\nMaxPlayers=32\n",
+        None,
+        "Map",
+        Some("Muldraugh, KY".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_continuation_in_trailing_comment_disabled,
+        IniParser{ line_continuation: true, trailing_comments: false, ..Default::default() },
+        "a = 1 ; see \\\nb = 2\n",
+        None,
+        "a",
+        Some("1 ; see b = 2".to_string()),
+    }
+    read_value_eq! {
+        read_value_multiline_disabled_trailing_slash_windows,
+        IniParser{line_continuation: false, ..Default::default()},
+        "# Comment with trailing backslash \\\r\nMap=Muldraugh, KY\r\n",
+        None,
+        "Map",
+        Some("Muldraugh, KY".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_multiline_windows_newlines,
+        IniParser{ line_continuation: true, ..Default::default() },
+        "description=first \\\r\nsecond\r\nnext=value\r\n",
+        None,
+        "description",
+        Some("first second".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_triple_quoted,
+        IniParser{ triple_quote_strings: true, ..Default::default() },
+        indoc::indoc!{r#"
+            [motd]
+            message = """first line
+            second line"""
+            other = value
+        "#},
+        Some("motd"),
+        "message",
+        Some("first line\nsecond line".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_triple_quote_in_comment,
+        IniParser{ triple_quote_strings: true, ..Default::default() },
+        indoc::indoc!{r#"
+            # docs=""" start
+            [s]
+            key = value
+        "#},
+        Some("s"),
+        "key",
+        Some("value".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_triple_quote_in_section_header,
+        IniParser{ triple_quote_strings: true, ..Default::default() },
+        indoc::indoc!{r#"
+            [a="""b]
+            [s]
+            key = value
+        "#},
+        Some("s"),
+        "key",
+        Some("value".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_triple_quoted_disabled,
+        IniParser::default(),
+        indoc::indoc!{r#"
+            message = """first line
+            second line"""
+        "#},
+        None,
+        "message",
+        Some("\"\"\"first line".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_tab_separated,
+        IniParser::default(),
+        "[server]\n\tport\t=\t8080\t; comment\n\thost\t=\tlocalhost\n",
+        Some("server"),
+        "port",
+        Some(8080),
+    }
+
+    read_value_eq! {
+        read_value_tab_inside_value,
+        IniParser::default(),
+        "[server]\nname\t=\tninja\tserver\t\n",
+        Some("server"),
+        "name",
+        Some("ninja\tserver".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_multiline_quoted,
+        IniParser{ multiline_quoted_values: true, ..Default::default() },
+        indoc::indoc!{r#"
+            [motd]
+            message = "first line
+            second line" ; greeting
+            other = value
+        "#},
+        Some("motd"),
+        "message",
+        Some("first line\nsecond line".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_multiline_quoted_key_after,
+        IniParser{ multiline_quoted_values: true, ..Default::default() },
+        indoc::indoc!{r#"
+            [motd]
+            message = "first line
+            second line"
+            other = value
+        "#},
+        Some("motd"),
+        "other",
+        Some("value".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_multiline_quote_in_comment,
+        IniParser{ multiline_quoted_values: true, ..Default::default() },
+        indoc::indoc!{r#"
+            ; say="hello
+            [s]
+            key = value
+        "#},
+        Some("s"),
+        "key",
+        Some("value".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_multiline_quote_in_section_header,
+        IniParser{ multiline_quoted_values: true, ..Default::default() },
+        indoc::indoc!{r#"
+            [a="b]
+            [s]
+            key = value
+        "#},
+        Some("s"),
+        "key",
+        Some("value".to_string()),
+    }
+
+    #[test]
+    fn read_value_multiline_quoted_unterminated() {
+        let parser = IniParser {
+            multiline_quoted_values: true,
+            ..Default::default()
+        };
+        let ini = "[motd]\nmessage = \"first line\nsecond line\n";
+        let value = parser.read_value::<String>(ini.as_bytes(), Some("motd"), "message");
+        let err = value.unwrap_err();
+        assert_matches::assert_matches!(err, Error::UnterminatedQuote { line: 2 });
+        assert_eq!(
+            err.to_string(),
+            "quoted value starting on line 2 is never closed"
+        );
+    }
+
+    const FLAG_INI: &str = indoc::indoc! {"
+        # EnableDebug
+        [logging]
+        EnableLogging ; turns on logging
+        Level = 3
+    "};
+
+    #[test]
+    fn read_value_raw() {
+        let parser = IniParser::default();
+        let ini = "a = \" x \" # comment\nb=  padded  \r\nc=\n";
+        let read = |key| parser.read_value_raw(ini.as_bytes(), None, key).unwrap();
+        assert_eq!(read("a"), Some(" \" x \" ".to_string()));
+        assert_eq!(read("b"), Some("  padded  ".to_string()));
+        assert_eq!(read("c"), Some("".to_string()));
+        assert_eq!(read("d"), None);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_raw_async() {
+        let parser = IniParser::default();
+        let value = parser
+            .read_value_raw_async("b=  padded  \n".as_bytes(), None, "b")
+            .await
+            .unwrap();
+        assert_eq!(value, Some("  padded  ".to_string()));
+    }
+
+    #[test]
+    fn read_value_str() {
+        let parser = IniParser::default();
+        let level: Option<u8> = parser
+            .read_value_str(FLAG_INI, Some("logging"), "Level")
+            .unwrap();
+        assert_eq!(level, Some(3));
+    }
+
+    read_value_eq! {
+        read_value_flag,
+        IniParser{ flag_keys_as_true: true, ..Default::default() },
+        FLAG_INI,
+        Some("logging"),
+        "EnableLogging",
+        Some(true),
+    }
+
+    read_value_eq! {
+        read_value_flag_disabled,
+        IniParser::default(),
+        FLAG_INI,
+        Some("logging"),
+        "EnableLogging",
+        None::<bool>,
+    }
+
+    read_value_eq! {
+        read_value_flag_not_comment,
+        IniParser{ flag_keys_as_true: true, ..Default::default() },
+        FLAG_INI,
+        None,
+        "EnableDebug",
+        None::<bool>,
+    }
+
+    read_value_eq! {
+        read_value_flag_not_section,
+        IniParser{ flag_keys_as_true: true, ..Default::default() },
+        FLAG_INI,
+        None,
+        "[logging]",
+        None::<bool>,
+    }
+
+    read_value_eq! {
+        read_value_collapse_whitespace,
+        IniParser{ collapse_whitespace: true, ..Default::default() },
+        "name=John    Doe\n",
+        None,
+        "name",
+        Some("John Doe".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_collapse_whitespace_disabled,
+        IniParser::default(),
+        "name=John    Doe\n",
+        None,
+        "name",
+        Some("John    Doe".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_utf8_bom,
+        IniParser::default(),
+        "\u{feff}name=tom\n",
+        None,
+        "name",
+        Some("tom".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_utf8_bom_section,
+        IniParser::default(),
+        "\u{feff}[user]\nname=tom\n",
+        Some("user"),
+        "name",
+        Some("tom".to_string()),
+    }
+
+    /// A file that uses an empty section header to go back to the global section
+    const EMPTY_HEADER_INI: &str = r#"
+        name = tom
+        [contact]
+        email = tom@example.com
+        []
+        age = 30
+    "#;
+
+    read_value_eq! {
+        read_value_empty_header_global,
+        IniParser{ empty_header_is_global: true, ..Default::default() },
+        EMPTY_HEADER_INI,
+        None,
+        "age",
+        Some(30),
+    }
+
+    read_value_eq! {
+        read_value_empty_header_global_before,
+        IniParser{ empty_header_is_global: true, ..Default::default() },
+        EMPTY_HEADER_INI,
+        None,
+        "name",
+        Some("tom".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_empty_header_global_section,
+        IniParser{ empty_header_is_global: true, ..Default::default() },
+        EMPTY_HEADER_INI,
+        Some("contact"),
+        "email",
+        Some("tom@example.com".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_empty_header_disabled,
+        IniParser::default(),
+        EMPTY_HEADER_INI,
+        None,
+        "age",
+        None::<u32>,
+    }
+
+    read_value_eq! {
+        read_value_empty_header_named,
+        IniParser::default(),
+        EMPTY_HEADER_INI,
+        Some(""),
+        "age",
+        Some(30),
+    }
+
+    read_value_eq! {
+        windows_newlines,
+        IniParser::default(),
+        "test=hello\r\ntest2=world",
+        None,
+        "test2",
+        Some("world".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_multiline_disabled_trailing_slash,
+        IniParser{line_continuation: false, ..Default::default()},
+        r#"
+            # Comment with trailing backslash\\
+            Map=Muldraugh, KY
+            Next=Other
+        "#,
+        None,
+        "Next",
+        Some("Other".to_string()),
+    }
+
+    /// A test ini file that has duplicate entries including a duplicate section with the same key
+    const DUPLICATE_INI: &str = r#"
+        [contact]
+        email = test@example.com
+        email = test2@example.com
+
+        [other]
+        another_key= something
+
+        [contact]
+        email = test3@example.com
+    "#;
+
+    read_value_eq! {
+        read_duplicate_value_first,
+        IniParser{
+            duplicate_keys: DuplicateKeyStrategy::UseFirst,
+            ..IniParser::default()
+        },
+        DUPLICATE_INI,
+        Some("contact"),
+        "email",
+        Some("test@example.com".to_string()),
+    }
+
+    read_value_eq! {
+        read_duplicate_value_last,
+        IniParser{
+            duplicate_keys: DuplicateKeyStrategy::UseLast,
+            ..IniParser::default()
+        },
+        DUPLICATE_INI,
+        Some("contact"),
+        "email",
+        Some("test3@example.com".to_string()),
+    }
+
+    read_value_matches! {
+        read_duplicate_value_error,
+        IniParser{
+            duplicate_keys: DuplicateKeyStrategy::Error,
+            ..IniParser::default()
+        },
+        DUPLICATE_INI,
+        Some("contact"),
+        "email",
+        Err::<Option<String>, _>(Error::DuplicateKey{line: 4, ..}),
+    }
+
+    #[test]
+    fn duplicate_key_error_display() {
+        let parser = IniParser {
+            duplicate_keys: DuplicateKeyStrategy::Error,
+            ..Default::default()
+        };
+        let err = parser
+            .read_value::<String>(DUPLICATE_INI.as_bytes(), Some("contact"), "email")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "duplicate key [contact].email found in ini file (line 4)"
+        );
+    }
+
+    /// 30 bytes, with the key in the last 8.
+    const MAX_BYTES_INI: &str = "[server]\nhost = local\nport=80\n";
+
+    #[test]
+    fn read_value_max_bytes() {
+        let parser = IniParser {
+            max_bytes: Some(30),
+            ..Default::default()
+        };
+        let value = parser.read_value::<u16>(MAX_BYTES_INI.as_bytes(), Some("server"), "port");
+        assert_matches::assert_matches!(value, Ok(Some(80)));
+        let parser = IniParser {
+            max_bytes: Some(29),
+            ..Default::default()
+        };
+        let value = parser.read_value::<u16>(MAX_BYTES_INI.as_bytes(), Some("server"), "port");
+        assert_matches::assert_matches!(
+            value,
+            Err(Error::TooLarge {
+                limit: 29,
+                found: 30
+            })
+        );
+    }
+
+    #[test]
+    fn read_value_max_bytes_no_newline() {
+        let parser = IniParser {
+            max_bytes: Some(64),
+            ..Default::default()
+        };
+        // A source that never ends or sends a newline.
+        let source = std::io::repeat(b'a');
+        let value = parser.read_value::<String>(source, None, "a");
+        assert_matches::assert_matches!(value, Err(Error::TooLarge { limit: 64, .. }));
+    }
+
+    #[test]
+    fn read_value_max_bytes_continuation() {
+        let parser = IniParser {
+            max_bytes: Some(16),
+            ..Default::default()
+        };
+        // Each line is short, but the continued line as a whole isn't.
+        let ini = "a = 1 \\\n2 \\\n3 \\\n4 \\\n5\n";
+        let value = parser.read_value::<String>(ini.as_bytes(), None, "a");
+        assert_matches::assert_matches!(value, Err(Error::TooLarge { limit: 16, .. }));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_max_bytes_async() {
+        let parser = IniParser {
+            max_bytes: Some(29),
+            ..Default::default()
+        };
+        let value = parser
+            .read_value_async::<u16>(MAX_BYTES_INI.as_bytes(), Some("server"), "port")
+            .await;
+        assert_matches::assert_matches!(
+            value,
+            Err(Error::TooLarge {
+                limit: 29,
+                found: 30
+            })
+        );
+    }
+
+    #[test]
+    fn read_value_invalid_utf8() {
+        let ini = b"[server]\nname = caf\xe9\nport = 80\n";
+        let parser = IniParser::default();
+        let value = parser.read_value::<u16>(&ini[..], Some("server"), "port");
+        assert_matches::assert_matches!(value, Err(Error::InvalidUtf8 { byte_offset: 19 }));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_invalid_utf8_async() {
+        let ini = b"[server]\nname = caf\xe9\nport = 80\n";
+        let parser = IniParser::default();
+        let value = parser
+            .read_value_async::<u16>(&ini[..], Some("server"), "port")
+            .await;
+        assert_matches::assert_matches!(value, Err(Error::InvalidUtf8 { byte_offset: 19 }));
+    }
+
+    #[test]
+    fn parse_error_line() {
+        let ini = "# ports\nname = \\\n  server\n[server]\nport = not-a-port\n";
+        let parser = IniParser::default();
+        let err = parser
+            .read_value::<u16>(ini.as_bytes(), Some("server"), "port")
+            .unwrap_err();
+        assert_matches::assert_matches!(err, Error::Parse(ParseError { line: 5, .. }));
+        assert_eq!(
+            err.to_string(),
+            r#"error parsing value "not-a-port" for key [server].port (line 5)"#
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn parse_error_line_async() {
+        let ini = "# ports\nname = \\\n  server\n[server]\nport = not-a-port\n";
+        let parser = IniParser::default();
+        let err = parser
+            .read_value_async::<u16>(ini.as_bytes(), Some("server"), "port")
+            .await
+            .unwrap_err();
+        assert_matches::assert_matches!(err, Error::Parse(ParseError { line: 5, .. }));
+    }
+
+    #[test]
+    fn read_all_values_parse_error_line() {
+        let parser = IniParser::default();
+        let values: Vec<Result<u16, Error>> = parser
+            .read_all_values_lenient(ALL_VALUES_INI.as_bytes(), Some("server"), "port")
+            .unwrap();
+        assert_matches::assert_matches!(
+            &values[1],
+            Err(Error::Parse(ParseError { line: 4, section: Some(section), key, value, .. }))
+                if section == "server" && key == "port" && value == "not-a-port"
+        );
+    }
+
+    #[test]
+    fn read_value_both() {
+        let parser = IniParser::default();
+        let reader = std::io::Cursor::new("[server]\nport = 8080 # default\n");
+        let value = parser
+            .read_value_both::<u16>(reader, Some("server"), "port")
+            .unwrap();
+        assert_eq!(value, Some((8080, "8080".to_string())));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_both_async() {
+        let parser = IniParser::default();
+        let reader = std::io::Cursor::new("[server]\nport = 8080 # default\n");
+        let value = parser
+            .read_value_both_async::<u16>(reader, Some("server"), "port")
+            .await
+            .unwrap();
+        assert_eq!(value, Some((8080, "8080".to_string())));
+    }
+
+    fn nested_parser() -> IniParser<'static> {
+        IniParser {
+            comment_delimiters: vec!['#'].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn read_nested_value() {
+        let ini = "[plugin]\noptions = a=1;b=2 # inner options\n";
+        let parser = nested_parser();
+        let read = |inner_key| {
+            parser
+                .read_nested_value(ini.as_bytes(), Some("plugin"), "options", inner_key)
+                .unwrap()
+        };
+        assert_eq!(read("b"), Some("2".to_string()));
+        assert_eq!(read("a"), Some("1".to_string()));
+        assert_eq!(read("c"), None);
+    }
+
+    #[test]
+    fn read_nested_value_quoted() {
+        let ini = r#"options = "a = 1; b = two""#;
+        let parser = nested_parser();
+        let b = parser
+            .read_nested_value(ini.as_bytes(), None, "options", "b")
+            .unwrap();
+        assert_eq!(b, Some("two".to_string()));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_nested_value_async() {
+        let parser = nested_parser();
+        let b = parser
+            .read_nested_value_async("options=a=1;b=2".as_bytes(), None, "options", "b")
+            .await
+            .unwrap();
+        assert_eq!(b, Some("2".to_string()));
+    }
+
+    const KEY_STATE_INI: &str = r#"
+        [video]
+        width = 1920
+        ; height = 1080
+        # This comment isn't a key = really
+        #fullscreen=true
+        vsync = on ; trailing comment
+        [audio]
+        ;volume = 10
+    "#;
+
+    const COMMENT_INI: &str = indoc::indoc! {"
+        [video]
+        width = 1920 # pixels
+        vsync = on ; needs a restart
+        fullscreen = true
+    "};
+
+    #[test]
+    fn read_comment() {
+        let parser = IniParser::default();
+        let read = |key| parser.read_comment(COMMENT_INI.as_bytes(), Some("video"), key);
+        assert_eq!(read("width").unwrap(), Some(('#', "pixels".to_string())));
+        assert_eq!(
+            read("vsync").unwrap(),
+            Some((';', "needs a restart".to_string()))
+        );
+        assert_eq!(read("fullscreen").unwrap(), None);
+        assert_eq!(read("missing").unwrap(), None);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_comment_async() {
+        let parser = IniParser::default();
+        let comment = parser
+            .read_comment_async(COMMENT_INI.as_bytes(), Some("video"), "vsync")
+            .await
+            .unwrap();
+        assert_eq!(comment, Some((';', "needs a restart".to_string())));
+    }
+
+    #[test]
+    fn list_keys_with_state() {
+        let parser = IniParser::default();
+        let keys = parser
+            .list_keys_with_state(KEY_STATE_INI.as_bytes(), Some("video"))
+            .unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                ("width".to_string(), true),
+                ("height".to_string(), false),
+                ("fullscreen".to_string(), false),
+                ("vsync".to_string(), true),
+            ]
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn list_keys_with_state_async() {
+        let parser = IniParser::default();
+        let keys = parser
+            .list_keys_with_state_async(KEY_STATE_INI.as_bytes(), Some("audio"))
+            .await
+            .unwrap();
+        assert_eq!(keys, vec![("volume".to_string(), false)]);
+    }
+
+    const PATH_INI: &str = r#"
+        port = 1
+        [net]
+        port = 8080
+        ip.v4 = 127.0.0.1
+    "#;
+
+    #[test]
+    fn read_path() {
+        let parser = IniParser::default();
+        let read = |path| {
+            parser
+                .read_path::<String>(PATH_INI.as_bytes(), path)
+                .unwrap()
+        };
+        assert_eq!(read("net.port"), Some("8080".to_string()));
+        assert_eq!(read("port"), Some("1".to_string()));
+        assert_eq!(read("net.ip.v4"), Some("127.0.0.1".to_string()));
+        assert_eq!(read("net.missing"), None);
+    }
+
+    #[test]
+    fn read_path_separator() {
+        let parser = IniParser {
+            path_separator: '/',
+            ..Default::default()
+        };
+        let port = parser
+            .read_path::<u16>(PATH_INI.as_bytes(), "net/port")
+            .unwrap();
+        assert_eq!(port, Some(8080));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_path_async() {
+        let parser = IniParser::default();
+        let port = parser
+            .read_path_async::<u16>(PATH_INI.as_bytes(), "net.port")
+            .await
+            .unwrap();
+        assert_eq!(port, Some(8080));
+    }
+
+    const NESTED_SECTIONS_INI: &str = r#"
+        key = global
+        [parent]
+        key = parent
+        [parent.child]
+        key = child
+        [parent/child]
+        key = slash
+    "#;
+
+    #[test]
+    fn read_value_nested() {
+        let parser = IniParser::default();
+        let read = |sections| {
+            parser
+                .read_value_nested::<String>(NESTED_SECTIONS_INI.as_bytes(), sections, "key")
+                .unwrap()
+        };
+        assert_eq!(read(&["parent", "child"]), Some("child".to_string()));
+        assert_eq!(read(&["parent"]), Some("parent".to_string()));
+        assert_eq!(read(&[]), Some("global".to_string()));
+        assert_eq!(read(&["parent", "missing"]), None);
+    }
+
+    #[test]
+    fn read_value_nested_separator() {
+        let parser = IniParser {
+            section_separator: '/',
+            ..Default::default()
+        };
+        let value = parser
+            .read_value_nested::<String>(
+                NESTED_SECTIONS_INI.as_bytes(),
+                &["parent", "child"],
+                "key",
+            )
+            .unwrap();
+        assert_eq!(value, Some("slash".to_string()));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_nested_async() {
+        let parser = IniParser::default();
+        let value = parser
+            .read_value_nested_async::<String>(
+                NESTED_SECTIONS_INI.as_bytes(),
+                &["parent", "child"],
+                "key",
+            )
+            .await
+            .unwrap();
+        assert_eq!(value, Some("child".to_string()));
+    }
+
+    #[test]
+    fn entries() {
+        let parser = IniParser {
+            line_continuation: true,
+            ..Default::default()
+        };
+        let ini = indoc::indoc! {r#"
+            name = "tom" # comment
+            [server]
+            ; port = 80
+            port = 8080
+            motd = hello \
+              world
+        "#};
+        let entry = |section: Option<&str>, key: &str, value: &str| Entry {
+            section: section.map(str::to_owned),
+            key: key.to_owned(),
+            value: value.to_owned(),
+        };
+        let entries: Vec<_> = parser
+            .entries(ini.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                entry(None, "name", "\"tom\""),
+                entry(Some("server"), "port", "8080"),
+                entry(Some("server"), "motd", "hello world"),
+            ]
+        );
+    }
+
+    const DUPLICATES_INI: &str = indoc::indoc! {"
+        name = a
+        Name = b
+        [server]
+        port = 1
+        host = x
+        [client]
+        port = 2
+        [server]
+        port = 3
+        host = y
+        port = 4
+    "};
+
+    #[test]
+    fn find_duplicate_keys() {
+        let parser = IniParser {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        let duplicates = parser
+            .find_duplicate_keys(DUPLICATES_INI.as_bytes())
+            .unwrap();
+        assert_eq!(
+            duplicates,
+            vec![
+                (None, "name".to_string(), 2),
+                (Some("server".to_string()), "port".to_string(), 3),
+                (Some("server".to_string()), "host".to_string(), 2),
+            ]
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn find_duplicate_keys_async() {
+        let parser = IniParser::default();
+        let duplicates = parser
+            .find_duplicate_keys_async(DUPLICATES_INI.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(duplicates.len(), 2);
+    }
+
+    #[test]
+    fn with_value() {
+        let parser = IniParser::default();
+        let ini = "[server]\nname = \"ninja server\" ; comment\nport = 8080\n";
+        let len = parser
+            .with_value(ini.as_bytes(), Some("server"), "name", str::len)
+            .unwrap();
+        assert_eq!(len, Some(14));
+        let first_word = parser
+            .with_value(ini.as_bytes(), Some("server"), "name", |value| {
+                value.trim_matches('"').split(' ').next().map(str::to_owned)
+            })
+            .unwrap();
+        assert_eq!(first_word, Some(Some("ninja".to_string())));
+        let missing = parser
+            .with_value(ini.as_bytes(), Some("server"), "host", |_| {
+                unreachable!("f shouldn't be called for a missing key")
+            })
+            .unwrap();
+        assert_eq!(missing, None::<()>);
+    }
+
+    #[test]
+    fn with_value_duplicates() {
+        let with_value = |duplicate_keys| {
+            IniParser {
+                duplicate_keys,
+                ..Default::default()
+            }
+            .with_value(
+                DUPLICATES_INI.as_bytes(),
+                Some("server"),
+                "port",
+                str::to_owned,
+            )
+        };
+        assert_eq!(
+            with_value(DuplicateKeyStrategy::UseFirst).unwrap(),
+            Some("1".to_string())
+        );
+        assert_eq!(
+            with_value(DuplicateKeyStrategy::UseLast).unwrap(),
+            Some("4".to_string())
+        );
+        assert_matches::assert_matches!(
+            with_value(DuplicateKeyStrategy::Error),
+            Err(Error::DuplicateKey { line: 9, .. })
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn with_value_async() {
+        let parser = IniParser::default();
+        let ini = "[server]\nport = 8080\n";
+        let port = parser
+            .with_value_async(ini.as_bytes(), Some("server"), "port", |value| {
+                value.parse::<u16>()
+            })
+            .await
+            .unwrap();
+        assert_eq!(port, Some(Ok(8080)));
+    }
+
+    #[test]
+    fn read_key_all_sections() {
+        let read = |duplicate_keys| {
+            IniParser {
+                duplicate_keys,
+                ..Default::default()
+            }
+            .read_key_all_sections::<u16>(DUPLICATES_INI.as_bytes(), "port")
+        };
+        let server = || Some("server".to_string());
+        let client = || Some("client".to_string());
+        assert_eq!(
+            read(DuplicateKeyStrategy::UseLast).unwrap(),
+            vec![(server(), 4), (client(), 2)]
+        );
+        assert_eq!(
+            read(DuplicateKeyStrategy::UseFirst).unwrap(),
+            vec![(server(), 1), (client(), 2)]
+        );
+        assert_matches::assert_matches!(
+            read(DuplicateKeyStrategy::Error),
+            Err(Error::DuplicateKey { line: 9, .. })
+        );
+    }
+
+    #[test]
+    fn read_key_all_sections_global() {
+        let parser = IniParser::default();
+        let ini = "enabled = true\n[feature.a]\nenabled = false\n[feature.b]\nother = 1\n";
+        let values = parser
+            .read_key_all_sections::<bool>(ini.as_bytes(), "enabled")
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![(None, true), (Some("feature.a".to_string()), false)]
+        );
+    }
+
+    #[test]
+    fn read_key_all_sections_parse_error() {
+        let parser = IniParser::default();
+        let ini = "[a]\nport = 1\n[b]\nport = x\n";
+        let err = parser
+            .read_key_all_sections::<u16>(ini.as_bytes(), "port")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error parsing value \"x\" for key [b].port (line 4)"
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_key_all_sections_async() {
+        let parser = IniParser::default();
+        let values = parser
+            .read_key_all_sections_async::<u16>(DUPLICATES_INI.as_bytes(), "port")
+            .await
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![
+                (Some("server".to_string()), 4),
+                (Some("client".to_string()), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn read_value_located() {
+        let parser = IniParser::default();
+        let ini = "[server]\nname = \"ninja\" ; comment\nport = 8080\n";
+        let name = parser
+            .read_value_located::<String>(ini.as_bytes(), Some("server"), "name")
+            .unwrap();
+        assert_eq!(
+            name,
+            Some((
+                "ninja".to_string(),
+                Location {
+                    line: 2,
+                    byte_range: 16..23
+                }
+            ))
+        );
+        let (port, location) = parser
+            .read_value_located::<u16>(ini.as_bytes(), Some("server"), "port")
+            .unwrap()
+            .unwrap();
+        assert_eq!(port, 8080);
+        assert_eq!(location.line, 3);
+        assert_eq!(&ini[location.byte_range], "8080");
+        let missing = parser
+            .read_value_located::<u16>(ini.as_bytes(), Some("server"), "host")
+            .unwrap();
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn read_value_located_duplicates() {
+        let read = |duplicate_keys| {
+            IniParser {
+                duplicate_keys,
+                ..Default::default()
+            }
+            .read_value_located::<u16>(
+                DUPLICATES_INI.as_bytes(),
+                Some("server"),
+                "port",
+            )
+        };
+        let (port, location) = read(DuplicateKeyStrategy::UseLast).unwrap().unwrap();
+        assert_eq!(port, 4);
+        assert_eq!(&DUPLICATES_INI[location.byte_range], "4");
+        let (port, location) = read(DuplicateKeyStrategy::UseFirst).unwrap().unwrap();
+        assert_eq!(port, 1);
+        assert_eq!(location.line, 4);
+        assert_matches::assert_matches!(
+            read(DuplicateKeyStrategy::Error),
+            Err(Error::DuplicateKey { line: 9, .. })
+        );
+    }
+
+    #[test]
+    fn read_value_located_continued_line() {
+        let parser = IniParser {
+            line_continuation: true,
+            ..Default::default()
+        };
+        let ini = "[a]\nfirst = 1\nlist = a, \\\n  b, c\nlast = 2\n";
+        let (list, location) = parser
+            .read_value_located::<String>(ini.as_bytes(), Some("a"), "list")
+            .unwrap()
+            .unwrap();
+        assert_eq!(list, "a, b, c");
+        assert_eq!(location.line, 3);
+        let (last, location) = parser
+            .read_value_located::<u16>(ini.as_bytes(), Some("a"), "last")
+            .unwrap()
+            .unwrap();
+        assert_eq!(last, 2);
+        assert_eq!(location.line, 5);
+    }
+
+    #[test]
+    fn read_value_located_quoted_lines() {
+        let parser = IniParser {
+            triple_quote_strings: true,
+            multiline_quoted_values: true,
+            ..Default::default()
+        };
+        let ini = "[a]\ntriple = \"\"\"one\r\ntwo\"\"\"\r\nquoted = \"three\n four\"\n";
+        for (key, line) in [("triple", 2), ("quoted", 4)] {
+            let (value, location) = parser
+                .read_value_located::<String>(ini.as_bytes(), Some("a"), key)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                Some(value),
+                parser.read_value(ini.as_bytes(), Some("a"), key).unwrap()
+            );
+            assert_eq!(location.line, line);
+        }
+    }
+
+    #[test]
+    fn read_value_located_on_next_line() {
+        let parser = value_on_next_line_parser();
+        let ini = "[s]\nkey=\n    hello\nempty=\nother=1\n";
+        let read = |key| {
+            parser
+                .read_value_located::<String>(ini.as_bytes(), Some("s"), key)
+                .unwrap()
+                .unwrap()
+        };
+        let (value, location) = read("key");
+        assert_eq!(
+            Some(value),
+            parser.read_value(ini.as_bytes(), Some("s"), "key").unwrap()
+        );
+        assert_eq!(location.line, 2);
+        assert_eq!(&ini[location.byte_range], "hello");
+        let (value, location) = read("empty");
+        assert_eq!(value, "");
+        assert_eq!(location.line, 4);
+        assert_eq!(location.byte_range, 25..25);
+    }
+
+    #[test]
+    fn read_value_located_parse_error() {
+        let parser = IniParser::default();
+        let ini = "[a]\nport = 1\n[b]\nport = x\n";
+        let err = parser
+            .read_value_located::<u16>(ini.as_bytes(), Some("b"), "port")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error parsing value \"x\" for key [b].port (line 4)"
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_located_async() {
+        let parser = IniParser::default();
+        let ini = "[server]\nport = 8080\n";
+        let (port, location) = parser
+            .read_value_located_async::<u16>(ini.as_bytes(), Some("server"), "port")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(port, 8080);
+        assert_eq!(
+            location,
+            Location {
+                line: 2,
+                byte_range: 16..20
+            }
+        );
+    }
+
+    #[test]
+    fn entries_stop_after_error() {
+        let parser = IniParser::default();
+        let ini = b"a=1\nb=\xff\nc=3\n";
+        let mut entries = parser.entries(&ini[..]);
+        assert_matches::assert_matches!(entries.next(), Some(Ok(Entry { .. })));
+        assert_matches::assert_matches!(entries.next(), Some(Err(Error::InvalidUtf8 { .. })));
+        assert_matches::assert_matches!(entries.next(), None);
+    }
+
+    #[test]
+    fn read_value_as() {
+        let parser = IniParser::default();
+        let ini = "port = 8080\ndebug = yes\nname = \"tom\"\nratio = 0.5\n";
+        let read = |key, kind| parser.read_value_as(ini.as_bytes(), None, key, kind);
+        assert_eq!(
+            read("port", ValueKind::Int).unwrap(),
+            Some(IniValue::Int(8080))
+        );
+        assert_eq!(
+            read("debug", ValueKind::Bool).unwrap(),
+            Some(IniValue::Bool(true))
+        );
+        assert_eq!(
+            read("name", ValueKind::String).unwrap(),
+            Some(IniValue::String("tom".to_string()))
+        );
+        assert_eq!(
+            read("ratio", ValueKind::Float).unwrap(),
+            Some(IniValue::Float(0.5))
+        );
+        assert_eq!(read("missing", ValueKind::Int).unwrap(), None);
+        assert_matches::assert_matches!(read("debug", ValueKind::Int), Err(Error::Parse(_)));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_as_async() {
+        let parser = IniParser::default();
+        let port = parser
+            .read_value_as_async("port = 8080".as_bytes(), None, "port", ValueKind::Int)
+            .await
+            .unwrap();
+        assert_eq!(port, Some(IniValue::Int(8080)));
+    }
+
+    const UNITS: &[(&str, f64)] = &[("KB", 1e3), ("MB", 1e6)];
+
+    #[test]
+    fn read_value_with_units() {
+        let parser = IniParser::default();
+        let ini = "size = 10MB\ncount = 42\nfreq = 2GHz\n";
+        let read = |key| parser.read_value_with_units(ini.as_bytes(), None, key, UNITS);
+        assert_eq!(read("size").unwrap(), Some(10e6));
+        assert_eq!(read("count").unwrap(), Some(42.0));
+        assert_eq!(read("missing").unwrap(), None);
+        assert_matches::assert_matches!(
+            read("freq"),
+            Err(Error::Parse(ParseError { line: 3, .. }))
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_with_units_async() {
+        let parser = IniParser::default();
+        let size = parser
+            .read_value_with_units_async("size = 1.5 KB".as_bytes(), None, "size", UNITS)
+            .await
+            .unwrap();
+        assert_eq!(size, Some(1500.0));
+    }
+
+    const LIST_INI: &str = r#"
+        servers = a, b ,c,
+        ports = 80 443
+        counts = 1,000 2,000
+        empty =
+    "#;
+
+    #[test]
+    fn read_list() {
+        let parser = IniParser::default();
+        let read =
+            |key, delimiter| parser.read_list::<String>(LIST_INI.as_bytes(), None, key, delimiter);
+        assert_eq!(
+            read("servers", ',').unwrap(),
+            Some(vec!["a".to_string(), "b".to_string(), "c".to_string()])
+        );
+        assert_eq!(read("empty", ',').unwrap(), Some(vec![]));
+        assert_eq!(read("missing", ',').unwrap(), None);
+    }
+
+    #[test]
+    fn read_list_parsed() {
+        let parser = IniParser::default();
+        let ports = parser
+            .read_list::<u16>(LIST_INI.as_bytes(), None, "ports", ' ')
+            .unwrap();
+        assert_eq!(ports, Some(vec![80, 443]));
+        let ports = parser.read_list::<u16>(LIST_INI.as_bytes(), None, "servers", ',');
+        assert_matches::assert_matches!(ports, Err(Error::Parse(ParseError { line: 2, .. })));
+    }
+
+    #[test]
+    fn read_list_grouped_int() {
+        let parser = IniParser::default();
+        let counts = parser
+            .read_list::<IniGroupedInt<u32>>(LIST_INI.as_bytes(), None, "counts", ' ')
+            .unwrap();
+        assert_eq!(
+            counts,
+            Some(vec![IniGroupedInt(1_000), IniGroupedInt(2_000)])
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_list_async() {
+        let parser = IniParser::default();
+        let ports = parser
+            .read_list_async::<u16>(LIST_INI.as_bytes(), None, "ports", ' ')
+            .await
+            .unwrap();
+        assert_eq!(ports, Some(vec![80, 443]));
+    }
+
+    /// Parser with custom boolean values
+    fn bool_parser() -> IniParser<'static> {
+        IniParser {
+            bool_true_values: &["enabled"],
+            bool_false_values: &["disabled"],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn read_bool_custom_values() {
+        let ini = "a=ENABLED\nb=Disabled\nc=YES\nd=off\ne=maybe\n";
+        let parser = bool_parser();
+        let read = |key| parser.read_bool(std::io::Cursor::new(ini), None, key);
+        assert_matches::assert_matches!(read("a"), Ok(Some(true)));
+        assert_matches::assert_matches!(read("b"), Ok(Some(false)));
+        assert_matches::assert_matches!(read("c"), Ok(Some(true)));
+        assert_matches::assert_matches!(read("d"), Ok(Some(false)));
+        assert_matches::assert_matches!(read("e"), Err(Error::Parse(_)));
+        assert_matches::assert_matches!(read("f"), Ok(None));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_bool_custom_values_async() {
+        let ini = "a=ENABLED\nb=Disabled\n";
+        let parser = bool_parser();
+        let value = parser
+            .read_bool_async(std::io::Cursor::new(ini), None, "a")
+            .await;
+        assert_matches::assert_matches!(value, Ok(Some(true)));
+        let value = parser
+            .read_bool_async(std::io::Cursor::new(ini), None, "b")
+            .await;
+        assert_matches::assert_matches!(value, Ok(Some(false)));
+    }
+
+    #[test]
+    fn read_value_file_ref() {
+        use std::io::Write;
+        let mut secret = tempfile::NamedTempFile::new().unwrap();
+        writeln!(secret, "hunter2").unwrap();
+        let ini = format!(
+            "password=@{}\nuser=@admin\n",
+            secret.path().to_string_lossy()
+        );
+        let parser = IniParser {
+            file_ref_prefix: Some('@'),
+            ..Default::default()
+        };
+        let value = parser
+            .read_value_file_ref(ini.as_bytes(), None, "password")
+            .unwrap();
+        assert_eq!(value, Some("hunter2".to_string()));
+
+        let parser = IniParser::default();
+        let value = parser
+            .read_value_file_ref(ini.as_bytes(), None, "user")
+            .unwrap();
+        assert_eq!(value, Some("@admin".to_string()));
+    }
+
+    #[test]
+    fn read_value_file_ref_max_bytes() {
+        let secret = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(secret.path(), "x".repeat(1000)).unwrap();
+        let ini = format!("password=@{}\n", secret.path().to_string_lossy());
+        let parser = IniParser {
+            file_ref_prefix: Some('@'),
+            max_bytes: Some(500),
+            ..Default::default()
+        };
+        let value = parser.read_value_file_ref(ini.as_bytes(), None, "password");
+        assert_matches::assert_matches!(
+            value,
+            Err(Error::TooLarge {
+                limit: 500,
+                found: 501
+            })
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_file_ref_async() {
+        let secret = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(secret.path(), "hunter2\n").unwrap();
+        let ini = format!("password=@{}\n", secret.path().to_string_lossy());
+        let parser = IniParser {
+            file_ref_prefix: Some('@'),
+            ..Default::default()
+        };
+        let value = parser
+            .read_value_file_ref_async(ini.as_bytes(), None, "password")
+            .await
+            .unwrap();
+        assert_eq!(value, Some("hunter2".to_string()));
+    }
+
+    #[test]
+    fn read_value_file_ref_literal() {
+        let parser = IniParser {
+            file_ref_prefix: Some('@'),
+            ..Default::default()
+        };
+        let value = parser
+            .read_value_file_ref("user=admin".as_bytes(), None, "user")
+            .unwrap();
+        assert_eq!(value, Some("admin".to_string()));
+    }
+
+    #[test]
+    fn section_hash_ignores_formatting() {
+        let parser = IniParser::default();
+        let original = "[db]\nhost=localhost\nport=5432\n[other]\nkey=value\n";
+        let reformatted = indoc::indoc! {"
+            [other]
+            key = something else
+
+            [ db ]
+            # The port
+            port   =   5432 ; comment
+            host = localhost
+        "};
+        let original_hash = parser
+            .section_hash(original.as_bytes(), Some("db"))
+            .unwrap();
+        let reformatted_hash = parser
+            .section_hash(reformatted.as_bytes(), Some("db"))
+            .unwrap();
+        assert!(original_hash.is_some());
+        assert_eq!(original_hash, reformatted_hash);
+    }
+
+    #[test]
+    fn section_hash_value_changed() {
+        let parser = IniParser::default();
+        let original = "[db]\nhost=localhost\nport=5432\n";
+        let changed = "[db]\nhost=localhost\nport=5433\n";
+        let original_hash = parser.section_hash(original.as_bytes(), Some("db"));
+        let changed_hash = parser.section_hash(changed.as_bytes(), Some("db"));
+        assert_ne!(original_hash.unwrap(), changed_hash.unwrap());
+    }
+
+    #[test]
+    fn section_hash_missing_section() {
+        let parser = IniParser::default();
+        let ini = "key=value\n[db]\nhost=localhost\n";
+        let hash = parser
+            .section_hash(ini.as_bytes(), Some("missing"))
+            .unwrap();
+        assert_eq!(hash, None);
+        let hash = parser.section_hash(ini.as_bytes(), None).unwrap();
+        assert!(hash.is_some());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn section_hash_async() {
+        let parser = IniParser::default();
+        let ini = "[db]\nhost=localhost\nport=5432\n";
+        let hash = parser.section_hash(ini.as_bytes(), Some("db")).unwrap();
+        let hash_async = parser
+            .section_hash_async(ini.as_bytes(), Some("db"))
+            .await
+            .unwrap();
+        assert_eq!(hash, hash_async);
+    }
+
+    /// An async reader that only returns one byte each time it's polled, like a slow network
+    /// stream might.
+    #[cfg(feature = "async")]
+    struct OneByteReader<'a>(&'a [u8]);
+
+    #[cfg(feature = "async")]
+    impl AsyncRead for OneByteReader<'_> {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            if let Some((first, rest)) = self.0.split_first() {
+                buf.put_slice(&[*first]);
+                self.0 = rest;
+                // Make the caller poll again instead of returning more data now
+                cx.waker().wake_by_ref();
+            }
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_async_one_byte_reads() {
+        let parser = IniParser {
+            line_continuation: true,
+            ..Default::default()
+        };
+        let ini = "name=tom\r\n[user]\ndescription=🚀 first \\\n second\nemail=bill@example.com";
+        let value: Option<String> = parser
+            .read_value_async(OneByteReader(ini.as_bytes()), Some("user"), "description")
+            .await
+            .unwrap();
+        assert_eq!(value, Some("🚀 first second".to_string()));
+        let value: Option<String> = parser
+            .read_value_async(OneByteReader(ini.as_bytes()), Some("user"), "email")
+            .await
+            .unwrap();
+        assert_eq!(value, Some("bill@example.com".to_string()));
+    }
+
+    /// Generate async and sync versions of tests for `read_value_or_default`
+    macro_rules! read_value_or_default_matches {
+        {
+            $test_name:ident,
+            $ini_file_string:expr,
+            $key:expr,
+            $expected:pat $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = IniParser::default();
+                let reader = std::io::Cursor::new($ini_file_string);
+                let value = parser.read_value_or_default(reader, None, $key);
+                ::assert_matches::assert_matches!(value, $expected);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    let parser = IniParser::default();
+                    let reader = std::io::Cursor::new($ini_file_string);
+                    let value = parser.read_value_or_default_async(reader, None, $key).await;
+                    ::assert_matches::assert_matches!(value, $expected);
+                }
+            }
+        };
+    }
+
+    read_value_or_default_matches! {
+        read_value_or_default_present,
+        "max_players=40",
+        "max_players",
+        Ok::<u32, _>(40),
+    }
+
+    read_value_or_default_matches! {
+        read_value_or_default_absent,
+        "max_players=40",
+        "min_players",
+        Ok::<u32, _>(0),
+    }
+
+    read_value_or_default_matches! {
+        read_value_or_default_malformed,
+        "max_players=forty",
+        "max_players",
+        Err::<u32, _>(Error::Parse(_)),
+    }
+
+    /// Generate async and sync versions of tests for `read_value_or`
+    macro_rules! read_value_or_matches {
+        {
+            $test_name:ident,
+            $ini_file_string:expr,
+            $key:expr,
+            $default:expr,
+            $expected:pat $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = IniParser::default();
+                let reader = std::io::Cursor::new($ini_file_string);
+                let value = parser.read_value_or(reader, None, $key, $default);
+                ::assert_matches::assert_matches!(value, $expected);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    let parser = IniParser::default();
+                    let reader = std::io::Cursor::new($ini_file_string);
+                    let value = parser.read_value_or_async(reader, None, $key, $default).await;
+                    ::assert_matches::assert_matches!(value, $expected);
+                }
+            }
+        };
+    }
+
+    read_value_or_matches! {
+        read_value_or_present,
+        "max_players=40",
+        "max_players",
+        10u32,
+        Ok(40),
+    }
+
+    read_value_or_matches! {
+        read_value_or_absent,
+        "max_players=40",
+        "min_players",
+        10u32,
+        Ok(10),
+    }
+
+    read_value_or_matches! {
+        read_value_or_malformed,
+        "max_players=forty",
+        "max_players",
+        10u32,
+        Err(Error::Parse(_)),
+    }
+
+    #[test]
+    fn read_value_or_else() {
+        let parser = IniParser::default();
+        let value = parser
+            .read_value_or_else("a=1".as_bytes(), None, "b", || "fallback".to_string())
+            .unwrap();
+        assert_eq!(value, "fallback");
+        let value: String = parser
+            .read_value_or_else("a=1".as_bytes(), None, "a", || unreachable!())
+            .unwrap();
+        assert_eq!(value, "1");
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_value_or_else_async() {
+        let parser = IniParser::default();
+        let value = parser
+            .read_value_or_else_async("a=1".as_bytes(), None, "b", || 2u8)
+            .await
+            .unwrap();
+        assert_eq!(value, 2);
+    }
+
+    const READ_SECTION_INI: &str = indoc::indoc! {r#"
+        name = global # not in a section
+        [contact]
+        name = "tom" ; comment
+        email = tom@example.com
+        [other]
+        key = value
+        [contact]
+        name = bill
+    "#};
+
+    #[test]
+    fn read_section() {
+        let parser = IniParser::default();
+        let pairs = parser
+            .read_section(READ_SECTION_INI.as_bytes(), Some("contact"))
+            .unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                ("name".to_string(), "bill".to_string()),
+                ("email".to_string(), "tom@example.com".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_section_use_first() {
+        let parser = IniParser {
+            duplicate_keys: DuplicateKeyStrategy::UseFirst,
+            ..Default::default()
+        };
+        let pairs = parser
+            .read_section(READ_SECTION_INI.as_bytes(), Some("contact"))
+            .unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                ("name".to_string(), "\"tom\"".to_string()),
+                ("email".to_string(), "tom@example.com".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_section_duplicate_error() {
+        let parser = IniParser {
+            duplicate_keys: DuplicateKeyStrategy::Error,
+            ..Default::default()
+        };
+        let pairs = parser.read_section(READ_SECTION_INI.as_bytes(), Some("contact"));
+        assert_matches::assert_matches!(pairs, Err(Error::DuplicateKey { .. }));
+    }
+
+    #[test]
+    fn read_section_global() {
+        let parser = IniParser::default();
+        let pairs = parser
+            .read_section(READ_SECTION_INI.as_bytes(), None)
+            .unwrap();
+        assert_eq!(pairs, vec![("name".to_string(), "global".to_string())]);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_section_async() {
+        let parser = IniParser::default();
+        let pairs = parser
+            .read_section_async(READ_SECTION_INI.as_bytes(), Some("other"))
+            .await
+            .unwrap();
+        assert_eq!(pairs, vec![("key".to_string(), "value".to_string())]);
+    }
+
+    #[test]
+    fn sections() {
+        let parser = IniParser::default();
+        let sections = parser.sections(READ_SECTION_INI.as_bytes()).unwrap();
+        assert_eq!(sections, vec!["contact".to_string(), "other".to_string()]);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn sections_async() {
+        let parser = IniParser::default();
+        let sections = parser
+            .sections_async(READ_SECTION_INI.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(sections, vec!["contact".to_string(), "other".to_string()]);
+    }
+
+    #[test]
+    fn sections_str() {
+        let parser = IniParser::default();
+        let sections = parser.sections_str(READ_SECTION_INI).unwrap();
+        assert_eq!(sections, vec!["contact".to_string(), "other".to_string()]);
+    }
+
+    #[test]
+    fn sections_empty_header_global() {
+        let parser = IniParser {
+            empty_header_is_global: true,
+            ..Default::default()
+        };
+        let sections = parser.sections(EMPTY_HEADER_INI.as_bytes()).unwrap();
+        assert_eq!(sections, vec!["contact".to_string()]);
+    }
+
+    const ALL_VALUES_INI: &str = r#"
+        [server]
+        port = 8080
+        port = not-a-port
+        [other]
+        port = 1
+        [server]
+        port = 9090
+    "#;
+
+    #[test]
+    fn read_all_values() {
+        let parser = IniParser::default();
+        let values: Vec<String> = parser
+            .read_all_values(DUPLICATE_INI.as_bytes(), Some("contact"), "email")
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![
+                "test@example.com".to_string(),
+                "test2@example.com".to_string(),
+                "test3@example.com".to_string()
+            ]
+        );
+        let parse_error =
+            parser.read_all_values::<u16>(ALL_VALUES_INI.as_bytes(), Some("server"), "port");
+        assert_matches::assert_matches!(parse_error, Err(Error::Parse(_)));
+    }
+
+    #[test]
+    fn read_all_values_lenient() {
+        let parser = IniParser {
+            duplicate_keys: DuplicateKeyStrategy::Error,
+            ..Default::default()
+        };
+        let values = parser
+            .read_all_values_lenient::<u16>(ALL_VALUES_INI.as_bytes(), Some("server"), "port")
+            .unwrap();
+        assert_eq!(values.len(), 3);
+        assert_matches::assert_matches!(values[0], Ok(8080));
+        assert_matches::assert_matches!(values[1], Err(Error::Parse(_)));
+        assert_matches::assert_matches!(values[2], Ok(9090));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_all_values_lenient_async() {
+        let parser = IniParser::default();
+        let values = parser
+            .read_all_values_lenient_async::<u16>(ALL_VALUES_INI.as_bytes(), Some("server"), "port")
+            .await
+            .unwrap();
+        assert_eq!(values.len(), 3);
+        assert_matches::assert_matches!(values[0], Ok(8080));
+        assert_matches::assert_matches!(values[1], Err(Error::Parse(_)));
+        assert_matches::assert_matches!(values[2], Ok(9090));
+    }
+
+    const QUOTED_INI: &str = r#"
+        double = "x"
+        single = 'x'
+        backtick = `x`
+        mismatched = "x'
+    "#;
+
+    fn quote_parser() -> IniParser<'static> {
+        IniParser {
+            quote_chars: &['"', '\'', '`'],
+            ..Default::default()
+        }
     }
 
     read_value_eq! {
-        read_value_section,
-        IniParser::default(),
-        r#"
-            [user]
-            first_name = "tom"
-        "#,
-        Some("user"),
-        "first_name",
-        Some("tom".to_string()),
+        read_quote_chars_double,
+        quote_parser(),
+        QUOTED_INI,
+        None,
+        "double",
+        Some("x".to_string()),
+    }
+
+    read_value_eq! {
+        read_quote_chars_single,
+        quote_parser(),
+        QUOTED_INI,
+        None,
+        "single",
+        Some("x".to_string()),
     }
 
     read_value_eq! {
-        read_value_no_section,
-        IniParser::default(),
-        r#"
-            date = "10/29/2024"
-
-            [user]
-            first_name = "tom"
-            date = "shouldn't get this"
-        "#,
+        read_quote_chars_backtick,
+        quote_parser(),
+        QUOTED_INI,
         None,
-        "date",
-        Some("10/29/2024".to_string()),
+        "backtick",
+        Some("x".to_string()),
     }
 
     read_value_eq! {
-        read_unquoted_string,
-        IniParser::default(),
-        r#"
-            [user]
-            first_name = tom
-        "#,
-        Some("user"),
-        "first_name",
-        Some("tom".to_string()),
+        read_quote_chars_mismatched,
+        quote_parser(),
+        QUOTED_INI,
+        None,
+        "mismatched",
+        Some("\"x'".to_string()),
     }
 
     read_value_eq! {
-        read_bool_true,
+        read_quote_chars_default_keeps_single,
         IniParser::default(),
-        r#"
-            [user]
-            first_name = tom
-            is_admin = true
-        "#,
-        Some("user"),
-        "is_admin",
-        Some(true),
+        QUOTED_INI,
+        None,
+        "single",
+        Some("'x'".to_string()),
     }
 
-    read_value_matches! {
-        read_bool_quotes,
-        IniParser::default(),
-        r#"
-            [user]
-            first_name = tom
-            is_admin = "true"
-        "#,
-        Some("user"),
-        "is_admin",
-        Err::<Option<bool>, _>(Error::Parse(_)),
+    const ENV_INI: &str = r#"
+        [db]
+        host = localhost
+        port = 5432
+
+        [db:production]
+        host = db.example.com
+    "#;
+
+    /// Generate async and sync versions of tests that get values for an environment from a given
+    /// ini and assert that it's equal to the expected value.
+    macro_rules! read_value_env_eq {
+        {
+            $test_name:ident,
+            $parser:expr,
+            $ini_file_string:expr,
+            $section:expr,
+            $env:expr,
+            $key:expr,
+            $expected:expr $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = $parser;
+                let reader = std::io::Cursor::new($ini_file_string);
+                let value = parser.read_value_env(reader, $section, $env, $key).unwrap();
+                assert_eq!(value, $expected);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    let parser = $parser;
+                    let reader = std::io::Cursor::new($ini_file_string);
+                    let value = parser
+                        .read_value_env_async(reader, $section, $env, $key)
+                        .await
+                        .unwrap();
+                    assert_eq!(value, $expected);
+                }
+            }
+        };
     }
 
-    read_value_matches! {
-        read_bool_uppercase,
+    read_value_env_eq! {
+        read_value_env_override,
         IniParser::default(),
-        r#"
-            [user]
-            first_name = tom
-            is_admin = TRUE
-        "#,
-        Some("user"),
-        "is_admin",
-        Ok(Some(true)),
+        ENV_INI,
+        "db",
+        "production",
+        "host",
+        Some("db.example.com".to_string()),
     }
-    read_value_matches! {
-        read_bool_num_true,
+
+    read_value_env_eq! {
+        read_value_env_fallback,
         IniParser::default(),
-        r#"
-            [user]
-            first_name = tom
-            is_admin = 1
-        "#,
-        Some("user"),
-        "is_admin",
-        Ok(Some(true)),
+        ENV_INI,
+        "db",
+        "production",
+        "port",
+        Some(5432),
     }
-    read_value_matches! {
-        read_bool_num_false,
+
+    read_value_env_eq! {
+        read_value_env_missing_env,
         IniParser::default(),
+        ENV_INI,
+        "db",
+        "staging",
+        "host",
+        Some("localhost".to_string()),
+    }
+
+    read_value_env_eq! {
+        read_value_env_separator,
+        IniParser {
+            env_separator: '.',
+            ..Default::default()
+        },
         r#"
-            [user]
-            first_name = tom
-            is_admin = 0
+            [db]
+            host = localhost
+            [db.production]
+            host = db.example.com
         "#,
-        Some("user"),
-        "is_admin",
-        Ok(Some(false)),
+        "db",
+        "production",
+        "host",
+        Some("db.example.com".to_string()),
+    }
+
+    const CASE_INI: &str = r#"
+        [/Script/Engine.GameSession]
+        MaxPlayers = 40
+        [ÄBC]
+        Key = unicode
+    "#;
+
+    fn case_insensitive_parser() -> IniParser<'static> {
+        IniParser {
+            case_sensitive: false,
+            ..Default::default()
+        }
     }
 
     read_value_eq! {
-        read_bool_false,
+        read_value_case_sensitive,
         IniParser::default(),
-        r#"
-            [user]
-            first_name = bill
-            is_admin = false
-        "#,
-        Some("user"),
-        "is_admin",
-        Some(false),
+        CASE_INI,
+        Some("/script/engine.gamesession"),
+        "maxplayers",
+        None::<u32>,
     }
 
     read_value_eq! {
-        read_value_multiline,
-        IniParser{ line_continuation: true, ..Default::default() },
-        r#"
-            description = "a longer \
-            value \
-            spanning multiple \
-            lines"
-        "#,
+        read_value_case_insensitive,
+        case_insensitive_parser(),
+        CASE_INI,
+        Some("/script/engine.gamesession"),
+        "maxplayers",
+        Some(40),
+    }
+
+    read_value_eq! {
+        read_value_case_insensitive_ascii_only,
+        case_insensitive_parser(),
+        CASE_INI,
+        Some("äbc"),
+        "key",
+        None::<String>,
+    }
+
+    const CRLF_INI: &str =
+        "[server]\r\nhost=localhost\r\nmotd=hello \\\r\n world\r\nport=8080 ; comment\r\n";
+
+    read_value_eq! {
+        read_value_crlf,
+        IniParser{line_continuation: true, ..Default::default()},
+        CRLF_INI,
+        Some("server"),
+        "host",
+        Some("localhost".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_crlf_continuation,
+        IniParser{line_continuation: true, ..Default::default()},
+        CRLF_INI,
+        Some("server"),
+        "motd",
+        Some("hello world".to_string()),
+    }
+
+    read_value_eq! {
+        read_value_crlf_comment,
+        IniParser{line_continuation: true, ..Default::default()},
+        CRLF_INI,
+        Some("server"),
+        "port",
+        Some(8080),
+    }
+
+    fn escape_parser() -> IniParser<'static> {
+        IniParser {
+            escape_sequences: true,
+            ..Default::default()
+        }
+    }
+
+    const ESCAPED_INI: &str = r#"
+        path=C:\\temp
+        message="line one\nline two\ttabbed \"quoted\""
+        unknown=\d\
+    "#;
+
+    read_value_eq! {
+        read_value_escaped_path,
+        escape_parser(),
+        ESCAPED_INI,
         None,
-        "description",
-        Some("a longer value spanning multiple lines".to_string()),
+        "path",
+        Some(r"C:\temp".to_string()),
     }
+
     read_value_eq! {
-        read_value_multiline_disabled_trailing_slash_windows,
-        IniParser{line_continuation: false, ..Default::default()},
-        "# Comment with trailing backslash \\\r\nMap=Muldraugh, KY\r\n",
+        read_value_escaped_sequences,
+        escape_parser(),
+        ESCAPED_INI,
         None,
-        "Map",
-        Some("Muldraugh, KY".to_string()),
+        "message",
+        Some("line one\nline two\ttabbed \"quoted\"".to_string()),
     }
 
     read_value_eq! {
-        windows_newlines,
-        IniParser::default(),
-        "test=hello\r\ntest2=world",
+        read_value_escaped_unknown,
+        escape_parser(),
+        ESCAPED_INI,
         None,
-        "test2",
-        Some("world".to_string()),
+        "unknown",
+        Some(r"\d\".to_string()),
     }
 
     read_value_eq! {
-        read_value_multiline_disabled_trailing_slash,
-        IniParser{line_continuation: false, ..Default::default()},
-        r#"
-            # Comment with trailing backslash\\
-            Map=Muldraugh, KY
-            Next=Other
-        "#,
+        read_value_escape_sequences_disabled,
+        IniParser::default(),
+        ESCAPED_INI,
         None,
-        "Next",
-        Some("Other".to_string()),
+        "path",
+        Some(r"C:\\temp".to_string()),
     }
 
-    /// A test ini file that has duplicate entries including a duplicate section with the same key
-    const DUPLICATE_INI: &str = r#"
-        [contact]
-        email = test@example.com
-        email = test2@example.com
+    read_value_eq! {
+        read_value_escaped_backslash_not_continuation,
+        IniParser {
+            escape_sequences: true,
+            line_continuation: true,
+            ..Default::default()
+        },
+        "path=C:\\\\\nname=tom\n",
+        None,
+        "path",
+        Some(r"C:\".to_string()),
+    }
 
-        [other]
-        another_key= something
+    /// Generate async and sync versions of tests that check if a key exists
+    macro_rules! contains_key_eq {
+        {
+            $test_name:ident,
+            $parser:expr,
+            $ini_file_string:expr,
+            $section:expr,
+            $key:expr,
+            $expected:expr $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = $parser;
+                let reader = std::io::Cursor::new($ini_file_string);
+                let value = parser.contains_key(reader, $section, $key).unwrap();
+                assert_eq!(value, $expected);
+            }
 
-        [contact]
-        email = test3@example.com
-    "#;
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    let parser = $parser;
+                    let reader = std::io::Cursor::new($ini_file_string);
+                    let value = parser.contains_key_async(reader, $section, $key).await.unwrap();
+                    assert_eq!(value, $expected);
+                }
+            }
+        };
+    }
 
-    read_value_eq! {
-        read_duplicate_value_first,
-        IniParser{
-            duplicate_keys: DuplicateKeyStrategy::UseFirst,
-            ..IniParser::default()
-        },
-        DUPLICATE_INI,
+    contains_key_eq! {
+        contains_key,
+        IniParser::default(),
+        READ_SECTION_INI,
         Some("contact"),
         "email",
-        Some("test@example.com".to_string()),
+        true,
     }
 
-    read_value_eq! {
-        read_duplicate_value_last,
-        IniParser{
-            duplicate_keys: DuplicateKeyStrategy::UseLast,
-            ..IniParser::default()
-        },
-        DUPLICATE_INI,
+    contains_key_eq! {
+        contains_key_other_section,
+        IniParser::default(),
+        READ_SECTION_INI,
+        Some("other"),
+        "email",
+        false,
+    }
+
+    contains_key_eq! {
+        contains_key_global,
+        IniParser::default(),
+        READ_SECTION_INI,
+        None,
+        "name",
+        true,
+    }
+
+    contains_key_eq! {
+        contains_key_empty_value,
+        IniParser::default(),
+        "[contact]\nemail=\n",
         Some("contact"),
         "email",
-        Some("test3@example.com".to_string()),
+        true,
     }
 
-    read_value_matches! {
-        read_duplicate_value_error,
-        IniParser{
+    contains_key_eq! {
+        contains_key_duplicate_error,
+        IniParser {
             duplicate_keys: DuplicateKeyStrategy::Error,
-            ..IniParser::default()
+            ..Default::default()
         },
         DUPLICATE_INI,
         Some("contact"),
         "email",
-        Err::<Option<String>, _>(Error::DuplicateKey{..}),
+        true,
+    }
+
+    const TYPED_INI: &str = r#"
+        timeout = 30
+        [server]
+        port = 8080
+        workers = 4
+        [client]
+        retries = 3
+        port = 9000
+    "#;
+
+    const TYPED_KEYS: &[(Option<&str>, &str)] = &[
+        (None, "timeout"),
+        (Some("server"), "port"),
+        (Some("client"), "port"),
+        (Some("client"), "retries"),
+        (Some("client"), "missing"),
+    ];
+
+    fn typed_expected() -> HashMap<(Option<String>, String), u32> {
+        [
+            ((None, "timeout"), 30),
+            ((Some("server"), "port"), 8080),
+            ((Some("client"), "port"), 9000),
+            ((Some("client"), "retries"), 3),
+        ]
+        .into_iter()
+        .map(|((section, key), value)| ((section.map(String::from), key.to_string()), value))
+        .collect()
+    }
+
+    #[test]
+    fn read_all_typed() {
+        let parser = IniParser::default();
+        let values: HashMap<_, u32> = parser
+            .read_all_typed(TYPED_INI.as_bytes(), TYPED_KEYS)
+            .unwrap();
+        assert_eq!(values, typed_expected());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_all_typed_async() {
+        let parser = IniParser::default();
+        let values: HashMap<_, u32> = parser
+            .read_all_typed_async(TYPED_INI.as_bytes(), TYPED_KEYS)
+            .await
+            .unwrap();
+        assert_eq!(values, typed_expected());
+    }
+
+    #[test]
+    fn read_all_typed_parse_error() {
+        let parser = IniParser::default();
+        let values = parser.read_all_typed::<u32>("name=tom".as_bytes(), &[(None, "name")]);
+        assert_matches::assert_matches!(values, Err(Error::Parse(_)));
     }
 }
@@ -0,0 +1,226 @@
+use crate::{IniParser, error::Error};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+impl IniParser<'_> {
+    /// Writes each section of the source to its own file in `out_dir`, named by calling `naming`
+    /// with the section name. The section header and all of the lines in the section are copied
+    /// unchanged, so formatting and comments are preserved. If a section appears more than once,
+    /// or several sections are given the same file name, they are all written to the same file.
+    ///
+    /// Anything before the first section header is written to `global_file_name`, unless it's
+    /// only blank lines.
+    /// Returns the paths of the files that were written, in the order they were first written to.
+    ///
+    /// Section names come from the source, so a file name that isn't a single file in `out_dir`,
+    /// e.g. one containing `..` or a path separator, returns [`Error::InvalidFileName`] instead of
+    /// being written.
+    pub fn split_sections(
+        &self,
+        source: impl Read,
+        out_dir: &Path,
+        global_file_name: &str,
+        naming: impl Fn(&str) -> String,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let mut source = std::io::BufReader::new(source);
+        let mut files = SplitFiles::default();
+        // Blank lines in the global section are held back so we don't create a global file that
+        // only contains whitespace.
+        let mut pending_blank_lines = String::new();
+        // Index of the file for the current section, `None` for the global section
+        let mut current = None;
+        // Index of the file for the global section once it's been opened
+        let mut global = None;
+        let mut line = String::new();
+        let mut bytes_processed = 0;
+        loop {
+            line.clear();
+            let bytes_read = self.read_raw_line(&mut source, &mut line, bytes_processed)?;
+            if bytes_read == 0 {
+                break;
+            }
+            bytes_processed += bytes_read;
+            if let Some(section) = self.try_section(&line) {
+                pending_blank_lines.clear();
+                current = if self.empty_header_is_global && section.is_empty() {
+                    None
+                } else {
+                    Some(files.open(out_dir, Some(section), &naming(section))?)
+                };
+                if current.is_none() {
+                    continue;
+                }
+            }
+            let index = match current {
+                Some(index) => index,
+                None if line.trim().is_empty() && global.is_none() => {
+                    pending_blank_lines.push_str(&line);
+                    continue;
+                }
+                None => match global {
+                    Some(index) => index,
+                    None => *global.insert(files.open(out_dir, None, global_file_name)?),
+                },
+            };
+            let writer = &mut files.writers[index];
+            writer.write_all(pending_blank_lines.as_bytes())?;
+            pending_blank_lines.clear();
+            writer.write_all(line.as_bytes())?;
+        }
+        for writer in &mut files.writers {
+            writer.flush()?;
+        }
+        Ok(files.paths)
+    }
+}
+
+/// The files written by [`IniParser::split_sections`].
+#[derive(Default)]
+struct SplitFiles {
+    paths: Vec<PathBuf>,
+    writers: Vec<BufWriter<File>>,
+}
+
+impl SplitFiles {
+    /// Returns the index of the file with the name in `out_dir`, creating it if needed. Files are
+    /// looked up by name rather than section so sections that share a file don't truncate it.
+    fn open(&mut self, out_dir: &Path, section: Option<&str>, name: &str) -> Result<usize, Error> {
+        let mut components = Path::new(name).components();
+        let is_single_file = matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        );
+        if !is_single_file {
+            return Err(Error::InvalidFileName {
+                section: section.map(|s| s.to_owned()),
+                name: name.to_owned(),
+            });
+        }
+        let path = out_dir.join(name);
+        if let Some(index) = self.paths.iter().position(|p| *p == path) {
+            return Ok(index);
+        }
+        self.writers.push(BufWriter::new(File::create(&path)?));
+        self.paths.push(path);
+        Ok(self.writers.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn split_sections() {
+        let source = indoc! {"
+            # Global comment
+            version=1
+
+            [server]
+            host = localhost ; the host
+
+            [client]
+            name=tom
+            [database]
+              url=postgres://localhost
+            [server]
+            port=8080
+        "};
+        let dir = tempfile::tempdir().unwrap();
+        let parser = IniParser::default();
+        let paths = parser
+            .split_sections(source.as_bytes(), dir.path(), "global.ini", |section| {
+                format!("{section}.ini")
+            })
+            .unwrap();
+        let names: Vec<_> = paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["global.ini", "server.ini", "client.ini", "database.ini"]
+        );
+
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("global.ini"), "# Global comment\nversion=1\n\n");
+        assert_eq!(
+            read("server.ini"),
+            "[server]\nhost = localhost ; the host\n\n[server]\nport=8080\n"
+        );
+        assert_eq!(read("client.ini"), "[client]\nname=tom\n");
+        assert_eq!(
+            read("database.ini"),
+            "[database]\n  url=postgres://localhost\n"
+        );
+
+        let port: Option<u16> = parser
+            .read_value(
+                File::open(dir.path().join("server.ini")).unwrap(),
+                Some("server"),
+                "port",
+            )
+            .unwrap();
+        assert_eq!(port, Some(8080));
+    }
+
+    #[test]
+    fn split_sections_shared_file() {
+        let source = "g=1\n[global]\nx=2\n[a]\ny=3\n[A]\nz=4\n";
+        let dir = tempfile::tempdir().unwrap();
+        let paths = IniParser::default()
+            .split_sections(source.as_bytes(), dir.path(), "global.ini", |section| {
+                format!("{}.ini", section.to_lowercase())
+            })
+            .unwrap();
+        assert_eq!(
+            paths,
+            [dir.path().join("global.ini"), dir.path().join("a.ini")]
+        );
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("global.ini"), "g=1\n[global]\nx=2\n");
+        assert_eq!(read("a.ini"), "[a]\ny=3\n[A]\nz=4\n");
+    }
+
+    #[test]
+    fn split_sections_outside_out_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().join("out");
+        std::fs::create_dir(&out_dir).unwrap();
+        let split = |source: &str, global_file_name: &str| {
+            IniParser::default().split_sections(
+                source.as_bytes(),
+                &out_dir,
+                global_file_name,
+                |section| section.to_owned(),
+            )
+        };
+        for section in ["..", "../escaped", "a/b", "/tmp/escaped", ""] {
+            let source = format!("[{section}]\nkey=value\n");
+            assert_matches::assert_matches!(
+                split(&source, "global.ini"),
+                Err(Error::InvalidFileName { section: Some(s), .. }) if s == section
+            );
+        }
+        assert_matches::assert_matches!(
+            split("key=value\n", "../global.ini"),
+            Err(Error::InvalidFileName { section: None, .. })
+        );
+        assert!(!dir.path().join("escaped").exists());
+    }
+
+    #[test]
+    fn split_sections_no_global() {
+        let source = "\n\n[server]\nport=8080\n";
+        let dir = tempfile::tempdir().unwrap();
+        let paths = IniParser::default()
+            .split_sections(source.as_bytes(), dir.path(), "global.ini", |section| {
+                format!("{section}.ini")
+            })
+            .unwrap();
+        assert_eq!(paths, [dir.path().join("server.ini")]);
+    }
+}
@@ -0,0 +1,358 @@
+use crate::{FromIniStr, IniParser};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// An inclusive range of values written as `start-end` or `start..end`, e.g. `ports=8000-8010`.
+/// The start must not be greater than the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IniRange<T> {
+    pub start: T,
+    pub end: T,
+}
+
+impl<T> From<IniRange<T>> for RangeInclusive<T> {
+    fn from(range: IniRange<T>) -> Self {
+        range.start..=range.end
+    }
+}
+
+impl<T> FromIniStr for IniRange<T>
+where
+    T: FromIniStr + PartialOrd,
+{
+    type Err = ParseRangeError;
+    fn from_ini_str(ini_str: &str) -> Result<Self, Self::Err> {
+        let ini_str = ini_str.trim();
+        // Skip the first character when looking for `-` so the start can be negative
+        let (start, end) = ini_str
+            .split_once("..")
+            .or_else(|| {
+                let first_len = ini_str.chars().next()?.len_utf8();
+                let index = ini_str[first_len..].find('-')? + first_len;
+                Some((&ini_str[..index], &ini_str[index + 1..]))
+            })
+            .ok_or(ParseRangeError::Malformed)?;
+        let parse = |bound: &str| {
+            T::from_ini_str(bound.trim()).map_err(|err| ParseRangeError::Bound(Box::new(err)))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start > end {
+            return Err(ParseRangeError::Reversed);
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// The type to parse a value as with [`IniParser::read_value_as`], for when the type is only
+/// known at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    /// Parsed like [`IniParser::read_bool`].
+    Bool,
+    Int,
+    Float,
+    String,
+}
+
+/// A value read with [`IniParser::read_value_as`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum IniValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+/// Errors from parsing an [`IniRange`].
+#[derive(Debug)]
+pub enum ParseRangeError {
+    /// The value isn't in the form `start-end` or `start..end`.
+    Malformed,
+    /// The start of the range is greater than the end.
+    Reversed,
+    /// The start or end of the range couldn't be parsed.
+    Bound(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl std::error::Error for ParseRangeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseRangeError::Bound(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseRangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseRangeError::Malformed => {
+                f.write_str("expected a range like start-end or start..end")
+            }
+            ParseRangeError::Reversed => f.write_str("start of range is greater than the end"),
+            ParseRangeError::Bound(_) => f.write_str("error while parsing the bounds of the range"),
+        }
+    }
+}
+
+/// A number that can be written with group separators, e.g. `count=1,000,000`. The separators
+/// are set by [`IniParser::group_separators`], `,` is used when parsed without a parser.
+///
+/// Separators are removed before parsing, wherever they are, so `1,0,0` is read as `100`. When the
+/// value is part of a list read with [`IniParser::read_list`], the list is split first so the list
+/// separator can't also be used as a group separator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IniGroupedInt<T>(pub T);
+
+impl<T: FromIniStr> IniGroupedInt<T> {
+    fn parse(ini_str: &str, separators: &[char]) -> Result<Self, T::Err> {
+        let ungrouped: String = ini_str
+            .chars()
+            .filter(|c| !separators.contains(c))
+            .collect();
+        T::from_ini_str(&ungrouped).map(Self)
+    }
+}
+
+impl<T: FromIniStr> FromIniStr for IniGroupedInt<T> {
+    type Err = T::Err;
+    fn from_ini_str(ini_str: &str) -> Result<Self, Self::Err> {
+        Self::parse(ini_str, &[','])
+    }
+
+    fn from_ini_str_with_parser(ini_str: &str, parser: &IniParser) -> Result<Self, Self::Err> {
+        Self::parse(ini_str, parser.group_separators)
+    }
+}
+
+/// Durations are written as a number followed by `ms`, `s`, `m` or `h`, e.g. `timeout=30s`. A
+/// number on its own is in seconds. The number can have a fractional part, like `1.5h`.
+impl FromIniStr for Duration {
+    type Err = ParseDurationError;
+    fn from_ini_str(ini_str: &str) -> Result<Self, Self::Err> {
+        let ini_str = ini_str.trim();
+        let unit_start = ini_str
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(ini_str.len());
+        let (number, unit) = ini_str.split_at(unit_start);
+        let number: f64 = number.trim().parse().map_err(ParseDurationError::Number)?;
+        let seconds = match unit {
+            "ms" => number / 1000.0,
+            "" | "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 60.0 * 60.0,
+            _ => return Err(ParseDurationError::Unit(unit.to_owned())),
+        };
+        Duration::try_from_secs_f64(seconds).map_err(|_| ParseDurationError::OutOfRange)
+    }
+}
+
+/// Errors from parsing a [`Duration`].
+#[derive(Debug)]
+pub enum ParseDurationError {
+    /// The number before the unit couldn't be parsed.
+    Number(std::num::ParseFloatError),
+    /// The unit isn't one of `ms`, `s`, `m` or `h`.
+    Unit(String),
+    /// The duration is negative or too large.
+    OutOfRange,
+}
+
+impl std::error::Error for ParseDurationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseDurationError::Number(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseDurationError::Number(_) => f.write_str("error while parsing the duration"),
+            ParseDurationError::Unit(unit) => {
+                write!(f, "unknown duration unit {unit:?}, expected ms, s, m or h")
+            }
+            ParseDurationError::OutOfRange => f.write_str("duration is negative or too large"),
+        }
+    }
+}
+
+/// Parses a number followed by one of the units, e.g. `10MB`, and multiplies the number by the
+/// unit's multiplier. A number on its own isn't multiplied.
+pub(crate) fn parse_with_units(value: &str, units: &[(&str, f64)]) -> Result<f64, ParseUnitError> {
+    let value = value.trim();
+    let unit_start = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_start);
+    let number: f64 = number.trim().parse().map_err(ParseUnitError::Number)?;
+    if unit.is_empty() {
+        return Ok(number);
+    }
+    units
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, multiplier)| number * multiplier)
+        .ok_or_else(|| ParseUnitError::Unit(unit.to_owned()))
+}
+
+/// Errors from [`IniParser::read_value_with_units`].
+#[derive(Debug)]
+pub enum ParseUnitError {
+    /// The number before the unit couldn't be parsed.
+    Number(std::num::ParseFloatError),
+    /// The unit isn't in the table of units.
+    Unit(String),
+}
+
+impl std::error::Error for ParseUnitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseUnitError::Number(err) => Some(err),
+            ParseUnitError::Unit(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseUnitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseUnitError::Number(_) => f.write_str("error while parsing the number"),
+            ParseUnitError::Unit(unit) => write!(f, "unknown unit {unit:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn range_dash() {
+        let range = IniRange::<u32>::from_ini_str("1-10").unwrap();
+        assert_eq!(range, IniRange { start: 1, end: 10 });
+        assert_eq!(RangeInclusive::from(range), 1..=10);
+    }
+
+    #[test]
+    fn range_dots() {
+        let range = IniRange::<u32>::from_ini_str(" 1..10 ").unwrap();
+        assert_eq!(range, IniRange { start: 1, end: 10 });
+    }
+
+    #[test]
+    fn range_negative() {
+        let range = IniRange::<i32>::from_ini_str("-10--5").unwrap();
+        assert_eq!(
+            range,
+            IniRange {
+                start: -10,
+                end: -5
+            }
+        );
+    }
+
+    #[test]
+    fn range_reversed() {
+        let range = IniRange::<u32>::from_ini_str("10-1");
+        assert_matches!(range, Err(ParseRangeError::Reversed));
+    }
+
+    #[test]
+    fn range_malformed() {
+        assert_matches!(
+            IniRange::<u32>::from_ini_str("10"),
+            Err(ParseRangeError::Malformed)
+        );
+        assert_matches!(
+            IniRange::<u32>::from_ini_str("a-b"),
+            Err(ParseRangeError::Bound(_))
+        );
+    }
+
+    #[test]
+    fn read_range() {
+        let parser = crate::IniParser::default();
+        let range: Option<IniRange<u16>> = parser
+            .read_value("ports=8000-8010".as_bytes(), None, "ports")
+            .unwrap();
+        assert_eq!(
+            range,
+            Some(IniRange {
+                start: 8000,
+                end: 8010
+            })
+        );
+    }
+
+    #[test]
+    fn duration_units() {
+        let parse = |s| Duration::from_ini_str(s).unwrap();
+        assert_eq!(parse("500ms"), Duration::from_millis(500));
+        assert_eq!(parse("30s"), Duration::from_secs(30));
+        assert_eq!(parse("5m"), Duration::from_secs(5 * 60));
+        assert_eq!(parse("2h"), Duration::from_secs(2 * 60 * 60));
+        assert_eq!(parse(" 1.5 h "), Duration::from_secs(90 * 60));
+        assert_eq!(parse("10"), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn duration_invalid() {
+        assert_matches!(
+            Duration::from_ini_str("10 days"),
+            Err(ParseDurationError::Unit(unit)) if unit == "days"
+        );
+        assert_matches!(
+            Duration::from_ini_str("soon"),
+            Err(ParseDurationError::Number(_))
+        );
+        assert_matches!(
+            Duration::from_ini_str("-5s"),
+            Err(ParseDurationError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn read_duration() {
+        let parser = crate::IniParser::default();
+        let timeout: Option<Duration> = parser
+            .read_value("timeout = 250ms # per request".as_bytes(), None, "timeout")
+            .unwrap();
+        assert_eq!(timeout, Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn grouped_int() {
+        let parser = crate::IniParser::default();
+        let count: Option<IniGroupedInt<u32>> = parser
+            .read_value("count=1,000,000".as_bytes(), None, "count")
+            .unwrap();
+        assert_eq!(count, Some(IniGroupedInt(1_000_000)));
+    }
+
+    #[test]
+    fn grouped_int_custom_separators() {
+        let parser = crate::IniParser {
+            group_separators: &['_', ' '],
+            ..Default::default()
+        };
+        let count: Option<IniGroupedInt<i64>> = parser
+            .read_value("count=-1_000 000".as_bytes(), None, "count")
+            .unwrap();
+        assert_eq!(count, Some(IniGroupedInt(-1_000_000)));
+        let count =
+            parser.read_value::<IniGroupedInt<u32>>("count=1,000".as_bytes(), None, "count");
+        assert_matches!(count, Err(crate::Error::Parse(_)));
+    }
+
+    #[test]
+    fn grouped_int_not_default() {
+        let parser = crate::IniParser::default();
+        let count = parser.read_value::<u32>("count=1,000,000".as_bytes(), None, "count");
+        assert_matches!(count, Err(crate::Error::Parse(_)));
+    }
+}
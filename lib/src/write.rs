@@ -1,24 +1,259 @@
 use crate::DuplicateKeyStrategy;
-use crate::try_section_from_line;
-use crate::{IniParser, ValueByteRangeResult, error::Error};
+use crate::read::indent_of;
+use crate::{
+    IniParser, LineEndings, NewKeyPosition, QuoteStyle, ValueByteRangeResult, WriteOutcome,
+    error::Error,
+};
+use crate::{escape, is_triple_quoted};
 use std::io::{BufRead, Seek, Write};
+use std::ops::Range;
+#[cfg(feature = "fs")]
+use std::path::Path;
 
 #[cfg(feature = "async")]
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
 
 const WRITE_BUFFER_SIZE: usize = 8192;
 
 impl IniParser<'_> {
     /// Changes the value in the source ini and writes the resulting changed ini file to the
     /// destination.
+    ///
+    /// The file is written to the destination as it's read, so if there's an error part way
+    /// through, e.g. the disk is full, the destination will only have part of the file. Use
+    /// [`IniParser::write_value_checked`] or [`IniParser::update_file`] if that matters.
     pub fn write_value(
         &self,
         source: &mut (impl std::io::Read + Seek),
+        destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        self.write_value_returning_old(source, destination, section, key, value)
+            .map(|_| ())
+    }
+
+    /// Same as [`IniParser::write_value`], but the whole file is written to memory first and only
+    /// written to the destination once that succeeds. An error reading the source leaves the
+    /// destination untouched. This needs enough memory for the whole file.
+    pub fn write_value_checked(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        let mut buffer = Vec::new();
+        self.write_value(source, &mut buffer, section, key, value)?;
+        destination.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Async version of [`IniParser::write_value_checked`].
+    #[cfg(feature = "async")]
+    pub async fn write_value_checked_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
         mut destination: impl Write,
         section: Option<&str>,
         key: &str,
         value: &str,
     ) -> Result<(), Error> {
+        let mut buffer = Vec::new();
+        self.write_value_async(source, &mut buffer, section, key, value)
+            .await?;
+        destination.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Same as [`IniParser::write_value`] but also appends a line describing the change to `log`.
+    /// The line contains a timestamp in seconds since the unix epoch, the section, the key, the old
+    /// value and the new value, formatted according to [`IniParser::write_log_format`].
+    ///
+    /// The global section and the old value of a newly added key are written as empty fields.
+    pub fn write_value_logged(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        destination: impl Write,
+        mut log: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        let old_value = self.write_value_returning_old(source, destination, section, key, value)?;
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+            .to_string();
+        let fields = [
+            timestamp.as_str(),
+            section.unwrap_or_default(),
+            key,
+            old_value.as_deref().unwrap_or_default(),
+            value,
+        ];
+        writeln!(log, "{}", self.write_log_format.format(&fields))?;
+        Ok(())
+    }
+
+    /// Changes the value in the file at `path`. The changes are written to a new temporary file
+    /// with a random name next to the original which then replaces it, so the file is never left
+    /// partially written and concurrent updates don't write to the same temporary file.
+    ///
+    /// If [`IniParser::durable`] is set, the temporary file is synced to the disk before it
+    /// replaces the original, and on Unix the directory is synced after so the rename is on the
+    /// disk too. Either the old or the new file is left after a crash, never an empty one.
+    ///
+    /// Read-only files are left untouched and return a [`std::io::ErrorKind::PermissionDenied`]
+    /// error.
+    #[cfg(feature = "fs")]
+    pub fn update_file(
+        &self,
+        path: impl AsRef<Path>,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        self.update_file_returning_old(path, section, key, value)
+            .map(|_| ())
+    }
+
+    /// Same as [`IniParser::update_file`] but returns the value that was replaced, or `None` if
+    /// the key was added. The old value comes from the same read of the file that the new file
+    /// is written from.
+    #[cfg(feature = "fs")]
+    pub fn update_file_returning_old(
+        &self,
+        path: impl AsRef<Path>,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<Option<String>, Error> {
+        let path = path.as_ref();
+        let permissions = std::fs::metadata(path)?.permissions();
+        if permissions.readonly() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{} is read-only", path.display()),
+            )
+            .into());
+        }
+        let directory = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let mut source = std::io::BufReader::new(std::fs::File::open(path)?);
+        // Created in the same directory so renaming it doesn't cross file systems. It's removed
+        // when dropped if anything fails before it replaces the original.
+        let temp = tempfile::NamedTempFile::new_in(directory)?;
+        let mut destination = std::io::BufWriter::new(temp.as_file());
+        let old_value =
+            self.write_value_returning_old(&mut source, &mut destination, section, key, value)?;
+        destination.flush()?;
+        drop(destination);
+        temp.as_file().set_permissions(permissions)?;
+        if self.durable {
+            temp.as_file().sync_all()?;
+        }
+        temp.persist(path).map_err(std::io::Error::from)?;
+        // Windows can't open directories to sync them
+        if self.durable && cfg!(unix) {
+            std::fs::File::open(directory)?.sync_all()?;
+        }
+        Ok(old_value)
+    }
+
+    /// Changes the value in each of the files using [`IniParser::update_file`]. A failure to update
+    /// one file doesn't stop the others from being updated, the result for each file is returned in
+    /// the same order as `paths`.
+    #[cfg(feature = "fs")]
+    pub fn write_value_to_paths(
+        &self,
+        paths: &[&Path],
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        Ok(paths
+            .iter()
+            .map(|path| self.update_file(path, section, key, value))
+            .collect())
+    }
+
+    /// Same as [`IniParser::write_value`] but returns the value that was replaced, or `None` if the
+    /// key was added. The old value is returned as it appears in the file, e.g. quotes around
+    /// strings are kept.
+    pub fn write_value_returning_old(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<Option<String>, Error> {
+        self.write_value_reporting(source, destination, section, key, value)
+            .map(|(old_value, _)| old_value)
+    }
+
+    /// Same as [`IniParser::write_value`] but says whether the value replaced an existing one,
+    /// was added to a section, or was added along with a new section.
+    pub fn write_value_outcome(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<WriteOutcome, Error> {
+        self.write_value_reporting(source, destination, section, key, value)
+            .map(|(_, outcome)| outcome)
+    }
+
+    /// Returns the value that writing the key would replace, or `None` if it would be added,
+    /// without writing anything. The old value is returned as it appears in the file, the same
+    /// as [`IniParser::write_value_returning_old`].
+    ///
+    /// This function is blocking and should be used carefully: it is possible for
+    /// an attacker to continuously send bytes without ever sending a newline
+    /// or EOF. Set [`IniParser::max_bytes`] to limit the maximum number of bytes read.
+    pub fn preview_write(
+        &self,
+        source: impl std::io::Read,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<String>, Error> {
+        let mut buffer = std::io::BufReader::new(source);
+        let byte_range_result = self.value_byte_range(&mut buffer, section, key)?;
+        Ok(byte_range_result.old_value)
+    }
+
+    /// Async version of [`IniParser::preview_write`].
+    #[cfg(feature = "async")]
+    pub async fn preview_write_async(
+        &self,
+        source: impl AsyncRead + Unpin,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<String>, Error> {
+        let mut buffer = tokio::io::BufReader::new(source);
+        let byte_range_result = self
+            .value_byte_range_async(&mut buffer, section, key)
+            .await?;
+        Ok(byte_range_result.old_value)
+    }
+
+    /// Writes the value, returning the value that was replaced and what was done to write it.
+    fn write_value_reporting(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<(Option<String>, WriteOutcome), Error> {
         source.rewind()?;
         // Because we might not know if there are other instances until we reach the end of
         // the file, we have to scan the file once to find the correct location of the value.
@@ -26,381 +261,3297 @@ impl IniParser<'_> {
         // Technically with DuplicateKeyStrategy::UseFirst, we could just use the first location
         // encountered and not have to rewind, it would need to be implemented as another method
         // though to remove the Seek trait bound.
-        let mut value = value.to_owned();
-        let ValueByteRangeResult {
-            file_size_bytes,
-            last_byte_in_section,
-            value_range,
-        } = {
+        let byte_range_result = {
             let mut buffer = std::io::BufReader::new(&mut *source);
             self.value_byte_range(&mut buffer, section, key)?
         };
-        // If the value wasn't found, we'll be adding it to the end of the section, or the end of
-        // the file. We'll also need to add the key and section.
-        let value_range = value_range.unwrap_or_else(|| {
-            if let Some(position) = last_byte_in_section {
-                value = format!("{key}={value}\n");
-                position..position
-            } else {
-                let section = section.map(|s| format!("[{s}]\n")).unwrap_or_default();
-                value = format!("{section}{key}={value}\n");
-                file_size_bytes..file_size_bytes
-            }
-        });
+        let (value_range, value) = self.replacement(&byte_range_result, section, key, value);
+        let outcome = byte_range_result.outcome();
+        let old_value = byte_range_result.old_value;
 
         source.rewind()?;
-        let mut buffer = [0; WRITE_BUFFER_SIZE];
-        let mut buffer_window_start = 0;
-        let mut buffer_window_end = 0;
-        let mut in_value = false;
-        let mut value_written = false;
-        loop {
-            let bytes_read = source.read(&mut buffer)?.min(WRITE_BUFFER_SIZE);
-
-            debug_assert!(bytes_read <= WRITE_BUFFER_SIZE, "{bytes_read}");
-            if bytes_read == 0 {
-                break;
-            }
-            buffer_window_end += bytes_read;
-            // is the start of the value inside of the buffer's current window?
-            let start_in_window =
-                (buffer_window_start..buffer_window_end).contains(&value_range.start);
-            // is the end of the value inside of the buffer's current window?
-            let end_in_window = (buffer_window_start..buffer_window_end).contains(&value_range.end);
-            if start_in_window {
-                in_value = true;
-            }
-            match (start_in_window, end_in_window, in_value) {
-                // We are not in a value and no value is starting or ending, write all the bytes we
-                // read exactly the same as the source.
-                (false, false, false) => destination.write_all(&buffer[..bytes_read])?,
-                // if the whole buffer window is inside the value we are replacing, we don't need to
-                // write the old value so do nothing
-                (false, false, true) => {}
-                // value is starting in this buffer window
-                (true, end_in_window, _) => {
-                    in_value = true;
-                    let write_until = value_range.start - buffer_window_start;
-                    debug_assert!(
-                        write_until < WRITE_BUFFER_SIZE,
-                        "buffer_window: [{}..{}], write_until: {}",
-                        buffer_window_start,
-                        buffer_window_end,
-                        write_until
-                    );
-                    destination.write_all(&buffer[0..write_until])?;
-                    destination.write_all(value.as_bytes())?;
-                    value_written = true;
-                    if end_in_window {
-                        destination.write_all(
-                            &buffer[value_range.end - buffer_window_start
-                                ..buffer_window_end - buffer_window_start],
-                        )?;
-                    }
-                }
-                // value is ending but did not start in this buffer window
-                (false, true, _) => {
-                    destination
-                        .write_all(&buffer[value_range.end - buffer_window_start..bytes_read])?;
-                }
-            }
-            if end_in_window {
-                in_value = false;
-            }
-            buffer_window_start = buffer_window_end
-        }
-        if !value_written {
-            destination.write_all(value.as_bytes())?;
-        }
-        Ok(())
+        splice(source, &mut destination, &[(value_range, value)])?;
+        Ok((old_value, outcome))
     }
 
     #[cfg(feature = "async")]
     pub async fn write_value_async(
         &self,
         source: &mut (impl AsyncRead + AsyncSeek + Unpin),
-        mut destination: impl Write,
+        destination: impl Write,
         section: Option<&str>,
         key: &str,
         value: &str,
     ) -> Result<(), Error> {
-        let mut value = value.to_owned();
-        let ValueByteRangeResult {
-            file_size_bytes,
-            last_byte_in_section,
-            value_range,
-        } = {
+        self.write_value_returning_old_async(source, destination, section, key, value)
+            .await
+            .map(|_| ())
+    }
+
+    /// Async version of [`IniParser::write_value_returning_old`].
+    #[cfg(feature = "async")]
+    pub async fn write_value_returning_old_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<Option<String>, Error> {
+        self.write_value_reporting_async(source, destination, section, key, value)
+            .await
+            .map(|(old_value, _)| old_value)
+    }
+
+    /// Async version of [`IniParser::write_value_outcome`].
+    #[cfg(feature = "async")]
+    pub async fn write_value_outcome_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<WriteOutcome, Error> {
+        self.write_value_reporting_async(source, destination, section, key, value)
+            .await
+            .map(|(_, outcome)| outcome)
+    }
+
+    /// Async version of [`IniParser::write_value_reporting`].
+    #[cfg(feature = "async")]
+    async fn write_value_reporting_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<(Option<String>, WriteOutcome), Error> {
+        let byte_range_result = {
             let mut buffer = tokio::io::BufReader::new(&mut *source);
             self.value_byte_range_async(&mut buffer, section, key)
                 .await?
         };
-        // If the value wasn't found, we'll be adding it to the end of the section, or the end of
-        // the file. We'll also need to add the key and section.
-        let value_range = value_range.unwrap_or_else(|| {
-            if let Some(position) = last_byte_in_section {
-                value = format!("{key}={value}\n");
-                position..position
-            } else {
-                let section = section.map(|s| format!("[{s}]\n")).unwrap_or_default();
-                value = format!("{section}{key}={value}\n");
-                file_size_bytes..file_size_bytes
-            }
-        });
+        let (value_range, value) = self.replacement(&byte_range_result, section, key, value);
+        let outcome = byte_range_result.outcome();
+        let old_value = byte_range_result.old_value;
 
         source.rewind().await?;
-        let mut buffer = [0; WRITE_BUFFER_SIZE];
-        let mut buffer_window_start = 0;
-        let mut buffer_window_end = 0;
-        let mut in_value = false;
-        let mut value_written = false;
-        loop {
-            let bytes_read = source.read(&mut buffer).await?;
-            if bytes_read == 0 {
-                break;
-            }
-            buffer_window_end += bytes_read;
-            // is the start of the value inside of the buffer's current window?
-            let start_in_window =
-                value_range.start >= buffer_window_start && value_range.start < buffer_window_end;
-            // is the end of the value inside of the buffer's current window?
-            let end_in_window =
-                value_range.end >= buffer_window_start && value_range.end < buffer_window_end;
-            if start_in_window {
-                in_value = true;
-            }
-            match (start_in_window, end_in_window, in_value) {
-                // We are not in a value and no value is starting or ending, write all the bytes we
-                // read exactly the same as the source.
-                (false, false, false) => destination.write_all(&buffer[..bytes_read])?,
-                // if the whole buffer window is inside the value we are replacing, we don't need to
-                // write the old value so do nothing
-                (false, false, true) => {}
-                // value is starting in this buffer window
-                (true, end_in_window, _) => {
-                    in_value = true;
-                    let write_until = value_range.start - buffer_window_start;
-                    debug_assert!(
-                        write_until < WRITE_BUFFER_SIZE,
-                        "buffer_window: [{}..{}], write_until: {}",
-                        buffer_window_start,
-                        buffer_window_end,
-                        write_until
-                    );
-                    destination.write_all(&buffer[0..write_until])?;
-                    destination.write_all(value.as_bytes())?;
-                    value_written = true;
-                    if end_in_window {
-                        destination.write_all(
-                            &buffer[value_range.end - buffer_window_start
-                                ..buffer_window_end - buffer_window_start],
-                        )?;
-                    }
-                }
-                // value is ending but did not start in this buffer window
-                (false, true, _) => {
-                    destination
-                        .write_all(&buffer[value_range.end - buffer_window_start..bytes_read])?;
-                }
-            }
-            if end_in_window {
-                in_value = false;
-            }
-            buffer_window_start = buffer_window_end
-        }
-        if !value_written {
-            destination.write_all(value.as_bytes())?;
-        }
-        Ok(())
+        splice_async(source, &mut destination, &[(value_range, value)]).await?;
+        Ok((old_value, outcome))
     }
 
-    /// Get the current byte range where the value is stored in the source ini file, if it exists.
-    ///
-    /// This function is blocking and should be used carefully: it is possible for
-    /// an attacker to continuously send bytes without ever sending a newline
-    /// or EOF. You can use [`take`] to limit the maximum number of bytes read.
-    fn value_byte_range(
+    /// Returns the length in bytes of the value as it appears in the source, without reading the
+    /// value into memory. For values using line continuation this includes the `\\` and line
+    /// endings of each line.
+    pub fn value_len(
         &self,
-        source: &mut impl BufRead,
+        source: &mut (impl std::io::Read + Seek),
         section: Option<&str>,
         key: &str,
-    ) -> Result<ValueByteRangeResult, Error> {
-        // Whitespace around section names is not significant
-        let section = section.map(|s| s.trim());
-
-        // Are we in the section we are looking for?
-        // Starts in the global namespace, so if section is none it starts as true, changing as we
-        // parse different sections.
-        let mut in_section = section.is_none();
-        let mut last_in_section = None;
-        let mut line = String::new();
-        let mut next_line = String::new();
-        let mut last_value_candidate = None;
-        let mut bytes_processed = 0;
-        if in_section {
-            last_in_section = Some(bytes_processed);
-        }
-        loop {
-            line.clear();
-            let mut bytes_read = source.read_line(&mut line)?;
-            if bytes_read == 0 {
-                break;
-            }
-            if line.trim().ends_with('\\') {
-                loop {
-                    let bytes_read_continuation = source.read_line(&mut next_line)?;
-                    if bytes_read_continuation == 0 {
-                        break;
-                    }
-                    bytes_read += bytes_read_continuation;
-                    if next_line.trim_end().ends_with('\\') {
-                        line.push_str(&next_line);
-                    } else {
-                        line.push_str(&next_line);
-                        break;
-                    }
-                    next_line.clear();
-                }
-            }
-            if let Some(this_section) = try_section_from_line(&line) {
-                if let Some(section) = section {
-                    in_section = section == this_section;
-                } else {
-                    in_section = false;
-                }
-            } else if in_section && let Some(line_range) = self.try_value(&line, key) {
-                last_value_candidate =
-                    Some(bytes_processed + line_range.start..bytes_processed + line_range.end);
-
-                // We can return early if UseFirst is set
-                if last_value_candidate.is_some()
-                    && self.duplicate_keys == DuplicateKeyStrategy::UseFirst
-                {
-                    bytes_processed += bytes_read;
-                    if in_section && !line.trim().is_empty() {
-                        last_in_section = Some(bytes_processed);
-                    }
-                    return Ok(ValueByteRangeResult {
-                        file_size_bytes: bytes_processed,
-                        last_byte_in_section: last_in_section,
-                        value_range: last_value_candidate,
-                    });
-                }
-            }
-            bytes_processed += bytes_read;
-
-            if in_section && !line.trim().is_empty() {
-                last_in_section = Some(bytes_processed);
-            }
-        }
-        Ok(ValueByteRangeResult {
-            file_size_bytes: bytes_processed,
-            last_byte_in_section: last_in_section,
-            value_range: last_value_candidate,
-        })
+    ) -> Result<Option<usize>, Error> {
+        source.rewind()?;
+        let mut buffer = std::io::BufReader::new(&mut *source);
+        let result = self.value_byte_range(&mut buffer, section, key)?;
+        Ok(result.value_range.map(|range| range.len()))
     }
 
-    /// Get the current byte range where the value is stored in the source ini file, if it exists.
+    /// Async version of [`IniParser::value_len`].
     #[cfg(feature = "async")]
-    async fn value_byte_range_async(
+    pub async fn value_len_async(
         &self,
-        source: &mut (impl AsyncBufRead + Unpin),
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
         section: Option<&str>,
         key: &str,
-    ) -> Result<ValueByteRangeResult, Error> {
-        // Whitespace around section names is not significant
-        let section = section.map(|s| s.trim());
-        // Are we in the section we are looking for?
-        // Starts in the global namespace, so if section is none it starts as true, changing as we
-        // parse different sections.
-        let mut in_section = section.is_none();
-        let mut last_in_section = None;
-        let mut line = String::new();
-        let mut next_line = String::new();
-        let mut last_value_candidate = None;
-        let mut bytes_processed = 0;
-        if in_section {
-            last_in_section = Some(bytes_processed);
-        }
-        loop {
-            line.clear();
-            let mut bytes_read = source.read_line(&mut line).await?;
-            if bytes_read == 0 {
-                break;
-            }
-            if line.trim().ends_with('\\') {
-                loop {
-                    let bytes_read_continuation = source.read_line(&mut next_line).await?;
-                    if bytes_read_continuation == 0 {
-                        break;
-                    }
-                    bytes_read += bytes_read_continuation;
-                    if next_line.trim_end().ends_with('\\') {
-                        line.push_str(&next_line);
-                    } else {
-                        line.push_str(&next_line);
-                        break;
-                    }
-                    next_line.clear();
-                }
-            }
+    ) -> Result<Option<usize>, Error> {
+        source.rewind().await?;
+        let mut buffer = tokio::io::BufReader::new(&mut *source);
+        let result = self
+            .value_byte_range_async(&mut buffer, section, key)
+            .await?;
+        Ok(result.value_range.map(|range| range.len()))
+    }
 
-            if let Some(this_section) = try_section_from_line(&line) {
-                if let Some(section) = section {
-                    in_section = section == this_section;
-                } else {
-                    in_section = false;
-                }
-            } else if in_section && let Some(line_range) = self.try_value(&line, key) {
-                last_value_candidate =
-                    Some(bytes_processed + line_range.start..bytes_processed + line_range.end);
-
-                // We can return early if UseFirst is set
-                if last_value_candidate.is_some()
-                    && self.duplicate_keys == DuplicateKeyStrategy::UseFirst
-                {
-                    bytes_processed += bytes_read;
-                    if in_section && !line.trim().is_empty() {
-                        last_in_section = Some(bytes_processed);
-                    }
-                    return Ok(ValueByteRangeResult {
-                        file_size_bytes: bytes_processed,
-                        last_byte_in_section: last_in_section,
-                        value_range: last_value_candidate,
-                    });
-                }
-            }
-            bytes_processed += bytes_read;
-            if in_section && !line.trim().is_empty() {
-                last_in_section = Some(bytes_processed);
-            }
-        }
-        Ok(ValueByteRangeResult {
-            file_size_bytes: bytes_processed,
-            last_byte_in_section: last_in_section,
-            value_range: last_value_candidate,
-        })
+    /// Returns a reader over the bytes of the value as they appear in the source, so large values
+    /// can be streamed instead of read into memory. Like [`IniParser::value_len`], quotes are
+    /// kept and values using line continuation include the `\\` and line endings of each line.
+    pub fn value_reader<R: std::io::Read + Seek>(
+        &self,
+        mut source: R,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<impl std::io::Read>, Error> {
+        source.rewind()?;
+        let result = {
+            let mut buffer = std::io::BufReader::new(&mut source);
+            self.value_byte_range(&mut buffer, section, key)?
+        };
+        let Some(range) = result.value_range else {
+            return Ok(None);
+        };
+        source.seek(std::io::SeekFrom::Start(range.start as u64))?;
+        Ok(Some(source.take(range.len() as u64)))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #![allow(clippy::unwrap_used)]
-    use super::*;
-    use crate::assert_eq_preserve_new_lines;
+    /// Async version of [`IniParser::value_reader`].
     #[cfg(feature = "async")]
-    use ::paste::paste;
-    use indoc::indoc;
+    pub async fn value_reader_async<R: AsyncRead + AsyncSeek + Unpin>(
+        &self,
+        mut source: R,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<impl AsyncRead>, Error> {
+        source.rewind().await?;
+        let result = {
+            let mut buffer = tokio::io::BufReader::new(&mut source);
+            self.value_byte_range_async(&mut buffer, section, key)
+                .await?
+        };
+        let Some(range) = result.value_range else {
+            return Ok(None);
+        };
+        source
+            .seek(std::io::SeekFrom::Start(range.start as u64))
+            .await?;
+        Ok(Some(source.take(range.len() as u64)))
+    }
 
-    macro_rules! write_value_eq {
-        {
-            test_name = $test_name:ident,
-            input = $input:expr,
-            section = $section:expr,
+    /// Sets multiple values in a section in a single pass over the source and writes the resulting
+    /// ini file to the destination. Keys that don't exist yet are added according to
+    /// [`IniParser::new_key_position`], and the section is added to the end of the file if it
+    /// doesn't exist.
+    ///
+    /// If the same key is given more than once, the last value is used.
+    pub fn set_section_values(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: Option<&str>,
+        values: &[(&str, &str)],
+    ) -> Result<(), Error> {
+        source.rewind()?;
+        let layout = {
+            let mut buffer = std::io::BufReader::new(&mut *source);
+            self.section_layout(&mut buffer, section)?
+        };
+        let edits = self.section_edits(&layout, section, values, &mut layout.end());
+        source.rewind()?;
+        splice(source, &mut destination, &edits)
+    }
+
+    /// Async version of [`IniParser::set_section_values`].
+    #[cfg(feature = "async")]
+    pub async fn set_section_values_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        section: Option<&str>,
+        values: &[(&str, &str)],
+    ) -> Result<(), Error> {
+        source.rewind().await?;
+        let layout = {
+            let mut buffer = tokio::io::BufReader::new(&mut *source);
+            self.section_layout_async(&mut buffer, section).await?
+        };
+        let edits = self.section_edits(&layout, section, values, &mut layout.end());
+        source.rewind().await?;
+        splice_async(source, &mut destination, &edits).await
+    }
+
+    /// Sets values in any number of sections in a single pass over the source and writes the
+    /// resulting ini file to the destination. Each edit is a `(section, key, value)`. Keys that
+    /// don't exist yet are added the same way as [`IniParser::set_section_values`] adds them, and
+    /// sections that don't exist are added to the end of the file in the order they first appear
+    /// in `edits`.
+    ///
+    /// If the same key in the same section is given more than once, the last edit wins.
+    pub fn write_values(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        edits: &[(Option<&str>, &str, &str)],
+    ) -> Result<(), Error> {
+        let mut sections = self.group_by_section(edits);
+        source.rewind()?;
+        {
+            let mut buffer = std::io::BufReader::new(&mut *source);
+            self.scan_layouts(&mut buffer, &mut sections.layouts)?;
+        }
+        let edits = self.grouped_section_edits(&sections);
+        source.rewind()?;
+        splice(source, &mut destination, &edits)
+    }
+
+    /// Async version of [`IniParser::write_values`].
+    #[cfg(feature = "async")]
+    pub async fn write_values_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        edits: &[(Option<&str>, &str, &str)],
+    ) -> Result<(), Error> {
+        let mut sections = self.group_by_section(edits);
+        source.rewind().await?;
+        {
+            let mut buffer = tokio::io::BufReader::new(&mut *source);
+            self.scan_layouts_async(&mut buffer, &mut sections.layouts)
+                .await?;
+        }
+        let edits = self.grouped_section_edits(&sections);
+        source.rewind().await?;
+        splice_async(source, &mut destination, &edits).await
+    }
+
+    /// Adds a new `key=value` line to the end of the section, even if the key already exists, and
+    /// writes the result to the destination. Useful for formats like Unreal Engine's config files
+    /// where each occurrence of a key adds to a list. The section is added to the end of the file if
+    /// it doesn't exist.
+    pub fn append_value(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        source.rewind()?;
+        let layout = {
+            let mut buffer = std::io::BufReader::new(&mut *source);
+            self.section_layout(&mut buffer, section)?
+        };
+        let edits = self.append_edits(&layout, section, key, value);
+        source.rewind()?;
+        splice(source, &mut destination, &edits)
+    }
+
+    /// Async version of [`IniParser::append_value`].
+    #[cfg(feature = "async")]
+    pub async fn append_value_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        source.rewind().await?;
+        let layout = {
+            let mut buffer = tokio::io::BufReader::new(&mut *source);
+            self.section_layout_async(&mut buffer, section).await?
+        };
+        let edits = self.append_edits(&layout, section, key, value);
+        source.rewind().await?;
+        splice_async(source, &mut destination, &edits).await
+    }
+
+    /// Sets the first instance of the key in the section to the value and removes every other
+    /// instance of it, then writes the result to the destination. If the key doesn't exist it's
+    /// added the same way as [`IniParser::write_value`] adds it.
+    /// Returns the number of duplicates that were removed.
+    pub fn write_value_dedup(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<usize, Error> {
+        source.rewind()?;
+        let layout = {
+            let mut buffer = std::io::BufReader::new(&mut *source);
+            self.section_layout(&mut buffer, section)?
+        };
+        let (edits, removed) = self.dedup_edits(&layout, section, key, value);
+        source.rewind()?;
+        splice(source, &mut destination, &edits)?;
+        Ok(removed)
+    }
+
+    /// Async version of [`IniParser::write_value_dedup`].
+    #[cfg(feature = "async")]
+    pub async fn write_value_dedup_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Result<usize, Error> {
+        source.rewind().await?;
+        let layout = {
+            let mut buffer = tokio::io::BufReader::new(&mut *source);
+            self.section_layout_async(&mut buffer, section).await?
+        };
+        let (edits, removed) = self.dedup_edits(&layout, section, key, value);
+        source.rewind().await?;
+        splice_async(source, &mut destination, &edits).await?;
+        Ok(removed)
+    }
+
+    /// Same as [`IniParser::write_value`], but only looks in the `n`th instance of a section that
+    /// has more than one header, starting at 0. The key is added to the end of that instance if
+    /// it's not in it, even if another instance has it.
+    /// Returns `false` and writes the source unchanged if the section has fewer instances.
+    pub fn write_value_in_section_nth(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: &str,
+        n: usize,
+        key: &str,
+        value: &str,
+    ) -> Result<bool, Error> {
+        source.rewind()?;
+        let layout = {
+            let mut buffer = std::io::BufReader::new(&mut *source);
+            self.section_layout(&mut buffer, Some(section))?
+        };
+        let edits = self.nth_section_edits(&layout, n, key, value);
+        source.rewind()?;
+        splice(
+            source,
+            &mut destination,
+            edits.as_deref().unwrap_or_default(),
+        )?;
+        Ok(edits.is_some())
+    }
+
+    /// Async version of [`IniParser::write_value_in_section_nth`].
+    #[cfg(feature = "async")]
+    pub async fn write_value_in_section_nth_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        section: &str,
+        n: usize,
+        key: &str,
+        value: &str,
+    ) -> Result<bool, Error> {
+        source.rewind().await?;
+        let layout = {
+            let mut buffer = tokio::io::BufReader::new(&mut *source);
+            self.section_layout_async(&mut buffer, Some(section))
+                .await?
+        };
+        let edits = self.nth_section_edits(&layout, n, key, value);
+        source.rewind().await?;
+        splice_async(
+            source,
+            &mut destination,
+            edits.as_deref().unwrap_or_default(),
+        )
+        .await?;
+        Ok(edits.is_some())
+    }
+
+    /// Same as [`IniParser::write_value`], but if the line directly above the key is a comment
+    /// with the text `comment`, e.g. `# disabled`, the comment line is removed. Useful for
+    /// enabling a setting that has been marked as disabled. The text is compared ignoring ASCII
+    /// case and surrounding whitespace.
+    /// Returns `true` if the comment was removed.
+    pub fn write_value_removing_comment(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+        comment: &str,
+    ) -> Result<bool, Error> {
+        source.rewind()?;
+        let layout = {
+            let mut buffer = std::io::BufReader::new(&mut *source);
+            self.section_layout(&mut buffer, section)?
+        };
+        let (edits, removed) = self.removing_comment_edits(&layout, section, key, value, comment);
+        source.rewind()?;
+        splice(source, &mut destination, &edits)?;
+        Ok(removed)
+    }
+
+    /// Async version of [`IniParser::write_value_removing_comment`].
+    #[cfg(feature = "async")]
+    pub async fn write_value_removing_comment_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+        comment: &str,
+    ) -> Result<bool, Error> {
+        source.rewind().await?;
+        let layout = {
+            let mut buffer = tokio::io::BufReader::new(&mut *source);
+            self.section_layout_async(&mut buffer, section).await?
+        };
+        let (edits, removed) = self.removing_comment_edits(&layout, section, key, value, comment);
+        source.rewind().await?;
+        splice_async(source, &mut destination, &edits).await?;
+        Ok(removed)
+    }
+
+    /// Removes the line containing the key from the source and writes the result to the
+    /// destination. If the key's value spans multiple lines using line continuation, all of them
+    /// are removed. The section header and any other lines are left alone, even if the section is
+    /// now empty.
+    ///
+    /// Which instance of a duplicate key is removed depends on [`IniParser::duplicate_keys`].
+    /// Returns `true` if a key was removed.
+    pub fn delete_value(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<bool, Error> {
+        source.rewind()?;
+        let layout = {
+            let mut buffer = std::io::BufReader::new(&mut *source);
+            self.section_layout(&mut buffer, section)?
+        };
+        let edits = self.delete_edits(&layout, section, key)?;
+        source.rewind()?;
+        splice(source, &mut destination, &edits)?;
+        Ok(!edits.is_empty())
+    }
+
+    /// Async version of [`IniParser::delete_value`].
+    #[cfg(feature = "async")]
+    pub async fn delete_value_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<bool, Error> {
+        source.rewind().await?;
+        let layout = {
+            let mut buffer = tokio::io::BufReader::new(&mut *source);
+            self.section_layout_async(&mut buffer, section).await?
+        };
+        let edits = self.delete_edits(&layout, section, key)?;
+        source.rewind().await?;
+        splice_async(source, &mut destination, &edits).await?;
+        Ok(!edits.is_empty())
+    }
+
+    /// Renames a key in the section, leaving its value, the whitespace around the delimiter and any
+    /// trailing comment untouched. Every instance of a duplicate key is renamed so the value that
+    /// gets used doesn't change.
+    /// Returns `true` if a key was renamed.
+    pub fn rename_key(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: Option<&str>,
+        old_key: &str,
+        new_key: &str,
+    ) -> Result<bool, Error> {
+        source.rewind()?;
+        let layout = {
+            let mut buffer = std::io::BufReader::new(&mut *source);
+            self.section_layout(&mut buffer, section)?
+        };
+        let edits = self.rename_edits(&layout, old_key, new_key);
+        source.rewind()?;
+        splice(source, &mut destination, &edits)?;
+        Ok(!edits.is_empty())
+    }
+
+    /// Async version of [`IniParser::rename_key`].
+    #[cfg(feature = "async")]
+    pub async fn rename_key_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        section: Option<&str>,
+        old_key: &str,
+        new_key: &str,
+    ) -> Result<bool, Error> {
+        source.rewind().await?;
+        let layout = {
+            let mut buffer = tokio::io::BufReader::new(&mut *source);
+            self.section_layout_async(&mut buffer, section).await?
+        };
+        let edits = self.rename_edits(&layout, old_key, new_key);
+        source.rewind().await?;
+        splice_async(source, &mut destination, &edits).await?;
+        Ok(!edits.is_empty())
+    }
+
+    /// Removes a section from the source and writes the result to the destination. If the section
+    /// appears more than once, every instance of it is removed.
+    ///
+    /// The section header and every line up to and including the section's last key are removed.
+    /// Blank lines and comments after the last key are kept since they are often separating or
+    /// describing the next section.
+    /// Returns `true` if a section was removed.
+    pub fn delete_section(
+        &self,
+        source: &mut (impl std::io::Read + Seek),
+        mut destination: impl Write,
+        section: &str,
+    ) -> Result<bool, Error> {
+        source.rewind()?;
+        let layout = {
+            let mut buffer = std::io::BufReader::new(&mut *source);
+            self.section_layout(&mut buffer, Some(section))?
+        };
+        let edits = delete_section_edits(&layout);
+        source.rewind()?;
+        splice(source, &mut destination, &edits)?;
+        Ok(!edits.is_empty())
+    }
+
+    /// Async version of [`IniParser::delete_section`].
+    #[cfg(feature = "async")]
+    pub async fn delete_section_async(
+        &self,
+        source: &mut (impl AsyncRead + AsyncSeek + Unpin),
+        mut destination: impl Write,
+        section: &str,
+    ) -> Result<bool, Error> {
+        source.rewind().await?;
+        let layout = {
+            let mut buffer = tokio::io::BufReader::new(&mut *source);
+            self.section_layout_async(&mut buffer, Some(section))
+                .await?
+        };
+        let edits = delete_section_edits(&layout);
+        source.rewind().await?;
+        splice_async(source, &mut destination, &edits).await?;
+        Ok(!edits.is_empty())
+    }
+
+    /// Works out the edit needed to delete the key from the section described by `layout`.
+    fn delete_edits(
+        &self,
+        layout: &SectionLayout,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Vec<(Range<usize>, String)>, Error> {
+        let mut matching = layout
+            .keys
+            .iter()
+            .filter(|k| self.names_match(&k.key, key.trim()));
+        let to_delete = match self.duplicate_keys {
+            DuplicateKeyStrategy::UseFirst => matching.next(),
+            DuplicateKeyStrategy::UseLast => matching.next_back(),
+            DuplicateKeyStrategy::Error => {
+                let first = matching.next();
+                if let Some(duplicate) = matching.next() {
+                    return Err(Error::DuplicateKey {
+                        key: key.to_owned(),
+                        section: section.map(|s| s.to_owned()),
+                        line: duplicate.line,
+                    });
+                }
+                first
+            }
+        };
+        Ok(to_delete
+            .map(|k| (k.line_range.clone(), String::new()))
+            .into_iter()
+            .collect())
+    }
+
+    /// Works out the edits needed to set the values in the section described by `layout`.
+    fn section_edits(
+        &self,
+        layout: &SectionLayout,
+        section: Option<&str>,
+        values: &[(&str, &str)],
+        end: &mut SourceEnd,
+    ) -> Vec<(Range<usize>, String)> {
+        // Later values for the same key replace earlier ones
+        let mut deduplicated: Vec<(&str, &str)> = Vec::with_capacity(values.len());
+        for (key, value) in values {
+            if let Some(existing) = deduplicated
+                .iter_mut()
+                .find(|(k, _)| self.names_match(k, key))
+            {
+                existing.1 = value;
+            } else {
+                deduplicated.push((key, value));
+            }
+        }
+
+        let mut edits = Vec::new();
+        let mut new_keys = Vec::new();
+        for (key, value) in deduplicated {
+            let mut matching = layout
+                .keys
+                .iter()
+                .filter(|k| self.names_match(&k.key, key.trim()));
+            let existing = if self.duplicate_keys == DuplicateKeyStrategy::UseFirst {
+                matching.next()
+            } else {
+                matching.next_back()
+            };
+            if let Some(existing) = existing {
+                let value = self.format_value(value, Some(&existing.value));
+                edits.push((existing.value_range.clone(), value));
+            } else {
+                new_keys.push((key.trim(), value));
+            }
+        }
+
+        let newline = layout.line_endings.newline(self.newline);
+        let new_line = |(key, value): (&str, &str)| {
+            let value = self.format_value(value, None);
+            format!("{key}={value}{newline}")
+        };
+        match (layout.header_end, layout.last_byte_in_section) {
+            (Some(header_end), Some(last_byte_in_section)) => match self.new_key_position {
+                NewKeyPosition::Bottom => {
+                    let lines: String = new_keys.into_iter().map(new_line).collect();
+                    edits.push(end.insert(last_byte_in_section, lines, newline));
+                }
+                NewKeyPosition::Top => {
+                    let lines: String = new_keys.into_iter().map(new_line).collect();
+                    edits.push(end.insert(header_end, lines, newline));
+                }
+                NewKeyPosition::Sorted => {
+                    new_keys.sort_by_key(|(key, _)| *key);
+                    for new_key in new_keys {
+                        // Insert before the first key that sorts after it
+                        let position = layout
+                            .keys
+                            .iter()
+                            .find(|k| k.key.as_str() > new_key.0)
+                            .map(|k| k.line_range.start)
+                            .unwrap_or(last_byte_in_section);
+                        edits.push(end.insert(position, new_line(new_key), newline));
+                    }
+                }
+            },
+            _ if new_keys.is_empty() => {}
+            _ => {
+                let file_size_bytes = layout.file_size_bytes;
+                let mut lines = self.new_section_header(
+                    section,
+                    file_size_bytes,
+                    newline,
+                    &layout.existing_parents,
+                );
+                lines.extend(new_keys.into_iter().map(new_line));
+                edits.push(end.insert(file_size_bytes, lines, newline));
+            }
+        }
+        // Stable sort so keys inserted at the same position stay in order
+        edits.sort_by_key(|(range, _)| range.start);
+        edits
+    }
+
+    /// Works out the edits for [`IniParser::write_value_dedup`] and how many duplicates they
+    /// remove.
+    fn dedup_edits(
+        &self,
+        layout: &SectionLayout,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> (Vec<(Range<usize>, String)>, usize) {
+        let mut matching = layout
+            .keys
+            .iter()
+            .filter(|k| self.names_match(&k.key, key.trim()));
+        let Some(first) = matching.next() else {
+            let edits = self.section_edits(layout, section, &[(key, value)], &mut layout.end());
+            return (edits, 0);
+        };
+        let value = self.format_value(value, Some(&first.value));
+        let mut edits = vec![(first.value_range.clone(), value)];
+        edits.extend(matching.map(|k| (k.line_range.clone(), String::new())));
+        let removed = edits.len() - 1;
+        (edits, removed)
+    }
+
+    /// Works out the edit needed to set the key in the `n`th instance of the section described by
+    /// `layout`, or `None` if there isn't one.
+    fn nth_section_edits(
+        &self,
+        layout: &SectionLayout,
+        n: usize,
+        key: &str,
+        value: &str,
+    ) -> Option<Vec<(Range<usize>, String)>> {
+        let end = *layout.instance_ends.get(n)?;
+        let mut matching = layout
+            .keys
+            .iter()
+            .filter(|k| k.instance == n && self.names_match(&k.key, key.trim()));
+        let existing = if self.duplicate_keys == DuplicateKeyStrategy::UseFirst {
+            matching.next()
+        } else {
+            matching.next_back()
+        };
+        let edit = match existing {
+            Some(k) => (
+                k.value_range.clone(),
+                self.format_value(value, Some(&k.value)),
+            ),
+            None => {
+                let newline = layout.line_endings.newline(self.newline);
+                let line = format!("{}={}{newline}", key.trim(), self.format_value(value, None));
+                layout.end().insert(end, line, newline)
+            }
+        };
+        Some(vec![edit])
+    }
+
+    /// Works out the edit needed to add a line for the key to the end of the section described by
+    /// `layout`.
+    fn append_edits(
+        &self,
+        layout: &SectionLayout,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> Vec<(Range<usize>, String)> {
+        let newline = layout.line_endings.newline(self.newline);
+        let line = format!("{}={}{newline}", key.trim(), self.format_value(value, None));
+        let mut end = layout.end();
+        let edit = if let Some(position) = layout.last_byte_in_section {
+            end.insert(position, line, newline)
+        } else {
+            let file_size_bytes = layout.file_size_bytes;
+            let header = self.new_section_header(
+                section,
+                file_size_bytes,
+                newline,
+                &layout.existing_parents,
+            );
+            end.insert(file_size_bytes, header + &line, newline)
+        };
+        vec![edit]
+    }
+
+    /// Groups the edits by section, keeping the sections in the order they first appear.
+    fn group_by_section<'e>(
+        &self,
+        edits: &[(Option<&'e str>, &'e str, &'e str)],
+    ) -> GroupedEdits<'e> {
+        let mut grouped = GroupedEdits {
+            layouts: Vec::new(),
+            values: Vec::new(),
+        };
+        for (section, key, value) in edits {
+            // Whitespace around section names is not significant
+            let section = section.map(|s| s.trim());
+            let index = grouped
+                .layouts
+                .iter()
+                .position(|(s, _)| match (s, section) {
+                    (Some(a), Some(b)) => self.names_match(a, b),
+                    (a, b) => *a == b,
+                })
+                .unwrap_or_else(|| {
+                    grouped.layouts.push((section, SectionLayout::new(section)));
+                    grouped.values.push(Vec::new());
+                    grouped.layouts.len() - 1
+                });
+            grouped.values[index].push((*key, *value));
+        }
+        grouped
+    }
+
+    /// Works out the edits needed to rename every instance of `old_key` in the section described by
+    /// `layout`.
+    fn rename_edits(
+        &self,
+        layout: &SectionLayout,
+        old_key: &str,
+        new_key: &str,
+    ) -> Vec<(Range<usize>, String)> {
+        layout
+            .keys
+            .iter()
+            .filter(|k| self.names_match(&k.key, old_key.trim()))
+            .map(|k| (k.key_range.clone(), new_key.trim().to_owned()))
+            .collect()
+    }
+
+    /// Works out the edits for [`IniParser::write_value_removing_comment`] and whether they
+    /// remove the comment.
+    fn removing_comment_edits(
+        &self,
+        layout: &SectionLayout,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+        comment: &str,
+    ) -> (Vec<(Range<usize>, String)>, bool) {
+        let mut edits = self.section_edits(layout, section, &[(key, value)], &mut layout.end());
+        let mut matching = layout
+            .keys
+            .iter()
+            .filter(|k| self.names_match(&k.key, key.trim()));
+        // The same instance of the key that `section_edits` changes
+        let existing = if self.duplicate_keys == DuplicateKeyStrategy::UseFirst {
+            matching.next()
+        } else {
+            matching.next_back()
+        };
+        let comment_line = existing
+            .and_then(|k| k.comment_above.as_ref())
+            .filter(|(_, text)| text.eq_ignore_ascii_case(comment.trim()))
+            .map(|(range, _)| range.clone());
+        let removed = comment_line.is_some();
+        if let Some(range) = comment_line {
+            edits.push((range, String::new()));
+            edits.sort_by_key(|(range, _)| range.start);
+        }
+        (edits, removed)
+    }
+
+    /// Works out the edits for every section in `sections`.
+    fn grouped_section_edits(&self, sections: &GroupedEdits) -> Vec<(Range<usize>, String)> {
+        // Every layout is of the same source, so they share the end and only the first line added
+        // there needs a newline before it
+        let Some(mut end) = sections.layouts.first().map(|(_, layout)| layout.end()) else {
+            return Vec::new();
+        };
+        let mut edits: Vec<_> = sections
+            .layouts
+            .iter()
+            .zip(&sections.values)
+            .flat_map(|((section, layout), values)| {
+                self.section_edits(layout, *section, values, &mut end)
+            })
+            .collect();
+        // Stable sort so sections added to the end of the file stay in order
+        edits.sort_by_key(|(range, _)| range.start);
+        edits
+    }
+
+    /// Works out which bytes of the source need to be replaced and what they should be replaced
+    /// with.
+    ///
+    /// If the value wasn't found, we'll be adding it to the end of the section, or the end of the
+    /// file. We'll also need to add the key and section.
+    fn replacement(
+        &self,
+        byte_range_result: &ValueByteRangeResult,
+        section: Option<&str>,
+        key: &str,
+        value: &str,
+    ) -> (Range<usize>, String) {
+        let ValueByteRangeResult {
+            file_size_bytes,
+            last_byte_in_section,
+            value_range,
+            old_value,
+            line_endings,
+            is_flag,
+            missing_final_newline,
+            existing_parents,
+            ..
+        } = byte_range_result;
+        let file_size_bytes = *file_size_bytes;
+        let value = self.format_value(value, old_value.as_deref());
+        let newline = line_endings.newline(self.newline);
+        let key_value = if self.flag_keys_as_true && value.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{key}={value}")
+        };
+        if let Some(value_range) = value_range {
+            // The range of a flag is at its end, so the delimiter has to be added too.
+            let value = if *is_flag && !value.is_empty() {
+                format!("={value}")
+            } else {
+                value
+            };
+            return (value_range.clone(), value);
+        }
+        let (position, line) = if let Some(position) = *last_byte_in_section {
+            (position, format!("{key_value}{newline}"))
+        } else {
+            let section =
+                self.new_section_header(section, file_size_bytes, newline, existing_parents);
+            (file_size_bytes, format!("{section}{key_value}{newline}"))
+        };
+        let mut end = SourceEnd {
+            file_size_bytes,
+            missing_final_newline: *missing_final_newline,
+        };
+        end.insert(position, line, newline)
+    }
+
+    /// The range of the key's value in the line and false, or if the line is the key as a flag,
+    /// an empty range at the end of the flag and true.
+    fn value_or_flag(&self, line: &str, key: &str) -> Option<(Range<usize>, bool)> {
+        if let Some(range) = self.try_value(line, key) {
+            return Some((range, false));
+        }
+        let flag = self
+            .try_flag(line)
+            .filter(|flag| self.names_match(flag, key.trim()))?;
+        // The flag is the first thing on the line other than whitespace or a BOM.
+        let end = line.find(flag).unwrap_or_default() + flag.len();
+        Some((end..end, true))
+    }
+
+    /// The header for a section being added to the end of a file, empty for the global section.
+    /// If [`IniParser::create_parent_sections`] is enabled, it's preceded by the headers of the
+    /// parents that aren't in `existing_parents`.
+    fn new_section_header(
+        &self,
+        section: Option<&str>,
+        file_size_bytes: usize,
+        newline: &str,
+        existing_parents: &[String],
+    ) -> String {
+        let Some(section) = section else {
+            return String::new();
+        };
+        let mut names: Vec<&str> = Vec::new();
+        if self.create_parent_sections {
+            names.extend(
+                section
+                    .match_indices(self.section_separator)
+                    .map(|(i, _)| &section[..i])
+                    .filter(|parent| !existing_parents.iter().any(|p| self.names_match(p, parent))),
+            );
+        }
+        names.push(section);
+        let mut headers = String::new();
+        for name in names {
+            // Don't start an empty file with a blank line
+            if self.blank_line_before_section && (file_size_bytes > 0 || !headers.is_empty()) {
+                headers.push_str(newline);
+            }
+            headers.push_str(&format!("[{name}]{newline}"));
+        }
+        headers
+    }
+
+    /// Adds the section to `parents` if it's one of the parents of `section`, so
+    /// [`IniParser::create_parent_sections`] knows not to add it again.
+    fn note_parent_section(
+        &self,
+        this_section: &str,
+        section: Option<&str>,
+        parents: &mut Vec<String>,
+    ) {
+        if !self.create_parent_sections {
+            return;
+        }
+        let Some(section) = section else {
+            return;
+        };
+        let is_parent = section.get(..this_section.len()).is_some_and(|prefix| {
+            self.names_match(prefix, this_section)
+                && section[this_section.len()..].starts_with(self.section_separator)
+        });
+        if is_parent {
+            parents.push(this_section.to_owned());
+        }
+    }
+
+    /// Formats a value so it can be written to the file, given the value it's replacing if there
+    /// is one.
+    fn format_value(&self, value: &str, old_value: Option<&str>) -> String {
+        let value = if self.escape_sequences {
+            escape(value)
+        } else {
+            value.to_owned()
+        };
+        let wrap_in_triple_quotes = self.triple_quote_strings
+            && (value.contains('\n') || old_value.is_some_and(is_triple_quoted));
+        if wrap_in_triple_quotes {
+            return format!(r#""""{value}""""#);
+        }
+        let is_quoted = |text: &str, quote: char| {
+            text.len() >= 2 * quote.len_utf8() && text.starts_with(quote) && text.ends_with(quote)
+        };
+        if self
+            .quote_chars
+            .iter()
+            .any(|&quote| is_quoted(&value, quote))
+        {
+            return value;
+        }
+        let quote = match self.quote_values {
+            QuoteStyle::Preserve => old_value.and_then(|old| {
+                self.quote_chars
+                    .iter()
+                    .copied()
+                    .find(|&quote| is_quoted(old, quote))
+            }),
+            QuoteStyle::Always => self.quote_chars.first().copied(),
+            QuoteStyle::Never => None,
+        };
+        match quote {
+            Some(quote) => format!("{quote}{value}{quote}"),
+            None => value,
+        }
+    }
+
+    /// Finds the position of the section and the keys in it.
+    fn section_layout(
+        &self,
+        source: &mut impl BufRead,
+        section: Option<&str>,
+    ) -> Result<SectionLayout, Error> {
+        // Whitespace around section names is not significant
+        let section = section.map(|s| s.trim());
+        let mut layouts = [(section, SectionLayout::new(section))];
+        self.scan_layouts(source, &mut layouts)?;
+        let [(_, layout)] = layouts;
+        Ok(layout)
+    }
+
+    /// Async version of [`IniParser::section_layout`].
+    #[cfg(feature = "async")]
+    async fn section_layout_async(
+        &self,
+        source: &mut (impl AsyncBufRead + Unpin),
+        section: Option<&str>,
+    ) -> Result<SectionLayout, Error> {
+        // Whitespace around section names is not significant
+        let section = section.map(|s| s.trim());
+        let mut layouts = [(section, SectionLayout::new(section))];
+        self.scan_layouts_async(source, &mut layouts).await?;
+        let [(_, layout)] = layouts;
+        Ok(layout)
+    }
+
+    /// Fills in the layouts of several sections in a single pass over the source. The section
+    /// names should already be trimmed.
+    fn scan_layouts(
+        &self,
+        source: &mut impl BufRead,
+        layouts: &mut [(Option<&str>, SectionLayout)],
+    ) -> Result<(), Error> {
+        let mut line = String::new();
+        let mut bytes_processed = 0;
+        loop {
+            line.clear();
+            let bytes_read = self.read_raw_line(source, &mut line, bytes_processed)?;
+            if bytes_read == 0 {
+                break;
+            }
+            for (section, layout) in layouts.iter_mut() {
+                layout.process_line(self, *section, &line, bytes_read);
+            }
+            bytes_processed += bytes_read;
+        }
+        Ok(())
+    }
+
+    /// Async version of [`IniParser::scan_layouts`].
+    #[cfg(feature = "async")]
+    async fn scan_layouts_async(
+        &self,
+        source: &mut (impl AsyncBufRead + Unpin),
+        layouts: &mut [(Option<&str>, SectionLayout)],
+    ) -> Result<(), Error> {
+        let mut line = String::new();
+        let mut bytes_processed = 0;
+        loop {
+            line.clear();
+            let bytes_read = self
+                .read_raw_line_async(source, &mut line, bytes_processed)
+                .await?;
+            if bytes_read == 0 {
+                break;
+            }
+            for (section, layout) in layouts.iter_mut() {
+                layout.process_line(self, *section, &line, bytes_read);
+            }
+            bytes_processed += bytes_read;
+        }
+        Ok(())
+    }
+
+    /// Reads the next line from the source into `line`, along with any following lines that are
+    /// part of it because of line continuation or triple quotes. Line endings are kept so byte
+    /// positions in the line match the source. Returns the number of bytes read.
+    ///
+    /// `bytes_before` is how many bytes have already been read from the source, so
+    /// [`IniParser::max_bytes`] can be enforced.
+    pub(crate) fn read_raw_line(
+        &self,
+        source: &mut impl BufRead,
+        line: &mut String,
+        bytes_before: usize,
+    ) -> Result<usize, Error> {
+        let mut bytes_read = self.read_line(source, line, bytes_before as u64)?;
+        if bytes_read == 0 {
+            return Ok(0);
+        }
+        let mut next_line = String::new();
+        let mut unfinished = self.unfinished(line, true, None);
+        while let Some(waiting) = unfinished {
+            next_line.clear();
+            let bytes_read_next =
+                self.read_line(source, &mut next_line, (bytes_before + bytes_read) as u64)?;
+            if bytes_read_next == 0 {
+                break;
+            }
+            bytes_read += bytes_read_next;
+            unfinished = self.join_line(line, &next_line, waiting, true);
+        }
+        Ok(bytes_read)
+    }
+
+    /// Async version of [`IniParser::read_raw_line`].
+    #[cfg(feature = "async")]
+    async fn read_raw_line_async(
+        &self,
+        source: &mut (impl AsyncBufRead + Unpin),
+        line: &mut String,
+        bytes_before: usize,
+    ) -> Result<usize, Error> {
+        let mut bytes_read = self
+            .read_line_async(source, line, bytes_before as u64)
+            .await?;
+        if bytes_read == 0 {
+            return Ok(0);
+        }
+        let mut next_line = String::new();
+        let mut unfinished = self.unfinished(line, true, None);
+        while let Some(waiting) = unfinished {
+            next_line.clear();
+            let bytes_read_next = self
+                .read_line_async(source, &mut next_line, (bytes_before + bytes_read) as u64)
+                .await?;
+            if bytes_read_next == 0 {
+                break;
+            }
+            bytes_read += bytes_read_next;
+            unfinished = self.join_line(line, &next_line, waiting, true);
+        }
+        Ok(bytes_read)
+    }
+
+    /// Get the current byte range where the value is stored in the source ini file, if it exists.
+    ///
+    /// This function is blocking and should be used carefully: it is possible for
+    /// an attacker to continuously send bytes without ever sending a newline
+    /// or EOF. Set [`IniParser::max_bytes`] to limit the maximum number of bytes read.
+    pub(crate) fn value_byte_range(
+        &self,
+        source: &mut impl BufRead,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<ValueByteRangeResult, Error> {
+        let mut scan = ValueScan::new(section, key);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let bytes_read = self.read_raw_line(source, &mut line, scan.result.file_size_bytes)?;
+            if bytes_read == 0 || scan.process_line(self, &line, bytes_read) {
+                break;
+            }
+        }
+        Ok(scan.result)
+    }
+
+    /// Get the current byte range where the value is stored in the source ini file, if it exists.
+    #[cfg(feature = "async")]
+    pub(crate) async fn value_byte_range_async(
+        &self,
+        source: &mut (impl AsyncBufRead + Unpin),
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<ValueByteRangeResult, Error> {
+        let mut scan = ValueScan::new(section, key);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let bytes_read = self
+                .read_raw_line_async(source, &mut line, scan.result.file_size_bytes)
+                .await?;
+            if bytes_read == 0 || scan.process_line(self, &line, bytes_read) {
+                break;
+            }
+        }
+        Ok(scan.result)
+    }
+}
+
+/// Finds where a key's value is while the source is read line by line, see
+/// [`IniParser::value_byte_range`].
+struct ValueScan<'a> {
+    section: Option<&'a str>,
+    key: &'a str,
+    /// Are we in the section we are looking for?
+    in_section: bool,
+    /// The indent of the last line if it's a key with an empty value, and whether it's the key
+    /// we are looking for, see [`IniParser::value_on_next_line_when_empty`].
+    empty_value: Option<(usize, bool)>,
+    /// How many lines have been scanned so far.
+    lines_read: usize,
+    /// What has been found so far. `file_size_bytes` is how many bytes have been scanned.
+    result: ValueByteRangeResult,
+}
+
+impl<'a> ValueScan<'a> {
+    fn new(section: Option<&'a str>, key: &'a str) -> Self {
+        // Whitespace around section names is not significant
+        let section = section.map(|s| s.trim());
+        // Starts in the global namespace, so if section is none it starts as true, changing as we
+        // parse different sections.
+        let in_section = section.is_none();
+        Self {
+            section,
+            key,
+            in_section,
+            empty_value: None,
+            lines_read: 0,
+            result: ValueByteRangeResult {
+                file_size_bytes: 0,
+                last_byte_in_section: in_section.then_some(0),
+                value_range: None,
+                old_value: None,
+                line_endings: LineEndings::default(),
+                is_flag: false,
+                missing_final_newline: false,
+                existing_parents: Vec::new(),
+                value_line: None,
+                value: None,
+                duplicate_line: None,
+            },
+        }
+    }
+
+    /// Updates the scan with the next line from [`IniParser::read_raw_line`]. Returns true if the
+    /// value has been found and the rest of the source doesn't need to be scanned.
+    fn process_line(&mut self, parser: &IniParser, line: &str, bytes_read: usize) -> bool {
+        let result = &mut self.result;
+        let bytes_processed = result.file_size_bytes;
+        let line_number = self.lines_read + 1;
+        // Continued lines and triple quoted strings come through as one line.
+        self.lines_read += line.trim_end_matches('\n').matches('\n').count() + 1;
+        result.line_endings.count(line);
+        result.missing_final_newline = !line.ends_with('\n');
+        let use_first = parser.duplicate_keys == DuplicateKeyStrategy::UseFirst;
+        let mut found_first = false;
+        // Is the line the value of the key with an empty value above it? See
+        // `IniParser::value_on_next_line_when_empty`.
+        let continued = match self.empty_value.take() {
+            Some((key_indent, is_key)) => match parser.indented_value(key_indent, line) {
+                Some(range) => {
+                    if is_key {
+                        result.value_range =
+                            Some(bytes_processed + range.start..bytes_processed + range.end);
+                        result.value = Some(parser.value_in_range(line, range.clone()).to_owned());
+                        result.old_value = Some(line[range].to_owned());
+                        found_first = use_first;
+                    }
+                    true
+                }
+                // The key's value really is empty
+                None if is_key && use_first => return true,
+                None => false,
+            },
+            None => false,
+        };
+        if continued {
+            // Already handled as part of the key above it
+        } else if let Some(this_section) = parser.try_section(line) {
+            self.in_section = parser.is_section(this_section, self.section);
+            parser.note_parent_section(this_section, self.section, &mut result.existing_parents);
+        } else if self.in_section
+            && let Some((line_range, flag)) = parser.value_or_flag(line, self.key)
+        {
+            let empty = !flag && line_range.is_empty();
+            result.value_range =
+                Some(bytes_processed + line_range.start..bytes_processed + line_range.end);
+            result.is_flag = flag;
+            result.old_value = Some(if flag {
+                "true".to_owned()
+            } else {
+                line[line_range].to_owned()
+            });
+            if result.value_line.is_some() && parser.duplicate_keys == DuplicateKeyStrategy::Error {
+                result.duplicate_line.get_or_insert(line_number);
+            }
+            result.value_line = Some(line_number);
+            result.value = parser
+                .value_or_flag_value(&parser.joined_line(line), self.key)
+                .map(str::to_owned);
+            if parser.value_on_next_line_when_empty && empty {
+                self.empty_value = Some((indent_of(line), true));
+            } else {
+                // We can stop early if UseFirst is set
+                found_first = use_first;
+            }
+        } else if parser.value_on_next_line_when_empty && parser.empty_value_start(line).is_some() {
+            self.empty_value = Some((indent_of(line), false));
+        }
+        result.file_size_bytes += bytes_read;
+
+        if self.in_section && !line.trim().is_empty() {
+            result.last_byte_in_section = Some(result.file_size_bytes);
+        }
+        found_first
+    }
+}
+
+/// Where a section and its keys are in the source.
+struct SectionLayout {
+    /// Are we currently in the section while scanning?
+    in_section: bool,
+    file_size_bytes: usize,
+    /// The position right after the first header for the section. Zero for the global section.
+    header_end: Option<usize>,
+    /// The position after the last non-empty line in the section.
+    last_byte_in_section: Option<usize>,
+    /// All the keys in the section in the order they appear in the source.
+    keys: Vec<KeyPosition>,
+    /// The lines of every header for the section, in case it appears more than once.
+    headers: Vec<Range<usize>>,
+    /// The position after the last non-empty line of each instance of the section, in the same
+    /// order as `headers`.
+    instance_ends: Vec<usize>,
+    /// The previous line and its text if it was a comment.
+    previous_comment: Option<(Range<usize>, String)>,
+    line_endings: LineEndings,
+    /// How many lines have been scanned so far.
+    lines_read: usize,
+    /// The parents of the section that are in the source, see
+    /// [`IniParser::create_parent_sections`].
+    existing_parents: Vec<String>,
+    /// The last line of the source doesn't have a line ending.
+    missing_final_newline: bool,
+    /// The indent of the last line if it's a key with an empty value, see
+    /// [`IniParser::value_on_next_line_when_empty`].
+    empty_value_indent: Option<usize>,
+}
+
+/// The end of the source, where lines are added for new sections and keys at the end of the
+/// last section.
+struct SourceEnd {
+    file_size_bytes: usize,
+    /// The last line of the source doesn't have a line ending, and no line added to the end has
+    /// added one yet.
+    missing_final_newline: bool,
+}
+
+impl SourceEnd {
+    /// The edit that inserts the lines at the position. Adding to the end of a source without a
+    /// final newline would continue its last line, so the first lines added there start with one.
+    fn insert(&mut self, position: usize, lines: String, newline: &str) -> (Range<usize>, String) {
+        if position == self.file_size_bytes && self.missing_final_newline {
+            self.missing_final_newline = false;
+            (position..position, format!("{newline}{lines}"))
+        } else {
+            (position..position, lines)
+        }
+    }
+}
+
+struct KeyPosition {
+    key: String,
+    value: String,
+    /// The whole line including the line ending.
+    line_range: Range<usize>,
+    /// The key name, without surrounding whitespace.
+    key_range: Range<usize>,
+    value_range: Range<usize>,
+    /// The line directly above the key and its text, if it's a comment.
+    comment_above: Option<(Range<usize>, String)>,
+    /// The line the key is on, starting at 1.
+    line: usize,
+    /// Which instance of the section the key is in, for sections with more than one header.
+    instance: usize,
+}
+
+impl SectionLayout {
+    fn new(section: Option<&str>) -> Self {
+        // Starts in the global namespace, so if section is none it starts as true
+        let in_section = section.is_none();
+        Self {
+            in_section,
+            file_size_bytes: 0,
+            header_end: in_section.then_some(0),
+            last_byte_in_section: in_section.then_some(0),
+            keys: Vec::new(),
+            headers: Vec::new(),
+            instance_ends: Vec::new(),
+            previous_comment: None,
+            line_endings: LineEndings::default(),
+            lines_read: 0,
+            existing_parents: Vec::new(),
+            missing_final_newline: false,
+            empty_value_indent: None,
+        }
+    }
+
+    /// The end of the source the layout was made from.
+    fn end(&self) -> SourceEnd {
+        SourceEnd {
+            file_size_bytes: self.file_size_bytes,
+            missing_final_newline: self.missing_final_newline,
+        }
+    }
+
+    fn process_line(
+        &mut self,
+        parser: &IniParser,
+        section: Option<&str>,
+        line: &str,
+        bytes_read: usize,
+    ) {
+        let line_start = self.file_size_bytes;
+        let line_end = line_start + bytes_read;
+        let previous_comment = self.previous_comment.take();
+        let line_number = self.lines_read + 1;
+        // Continued lines and triple quoted strings come through as one line.
+        self.lines_read += line.trim_end_matches('\n').matches('\n').count() + 1;
+        self.line_endings.count(line);
+        self.missing_final_newline = !line.ends_with('\n');
+        let indented_value = self
+            .empty_value_indent
+            .take()
+            .and_then(|key_indent| parser.indented_value(key_indent, line));
+        if let Some(range) = indented_value {
+            // The line is the value of the key with an empty value above it
+            if let Some(key) = self
+                .keys
+                .last_mut()
+                .filter(|k| self.in_section && k.line_range.end == line_start)
+            {
+                key.value = line[range.clone()].to_owned();
+                key.value_range = line_start + range.start..line_start + range.end;
+                key.line_range.end = line_end;
+            }
+        } else if let Some(this_section) = parser.try_section(line) {
+            self.in_section = parser.is_section(this_section, section);
+            parser.note_parent_section(this_section, section, &mut self.existing_parents);
+            if self.in_section {
+                self.header_end.get_or_insert(line_end);
+                self.headers.push(line_start..line_end);
+                self.instance_ends.push(line_end);
+            }
+        } else if self.in_section
+            && let Some((key, range)) = parser.try_key_value(line)
+        {
+            // The key is the first thing on the line other than whitespace or a BOM.
+            let key_start = line_start + line.find(key).unwrap_or_default();
+            self.keys.push(KeyPosition {
+                key: key.to_owned(),
+                value: line[range.clone()].to_owned(),
+                line_range: line_start..line_end,
+                key_range: key_start..key_start + key.len(),
+                value_range: line_start + range.start..line_start + range.end,
+                comment_above: previous_comment,
+                line: line_number,
+                instance: self.headers.len().saturating_sub(1),
+            });
+            if parser.value_on_next_line_when_empty && range.is_empty() {
+                self.empty_value_indent = Some(indent_of(line));
+            }
+        } else if parser.value_on_next_line_when_empty && parser.empty_value_start(line).is_some() {
+            self.empty_value_indent = Some(indent_of(line));
+        } else if let Some(comment) = line.trim().strip_prefix(parser.line_comments()) {
+            self.previous_comment = Some((line_start..line_end, comment.trim().to_owned()));
+        }
+        self.file_size_bytes = line_end;
+        if self.in_section && !line.trim().is_empty() {
+            self.last_byte_in_section = Some(line_end);
+            if let Some(end) = self.instance_ends.last_mut() {
+                *end = line_end;
+            }
+        }
+    }
+}
+
+/// Edits for [`IniParser::write_values`] grouped by section.
+struct GroupedEdits<'e> {
+    layouts: Vec<(Option<&'e str>, SectionLayout)>,
+    /// The keys and values for each section in `layouts`.
+    values: Vec<Vec<(&'e str, &'e str)>>,
+}
+
+/// Works out the edits needed to remove every instance of the section described by `layout`.
+fn delete_section_edits(layout: &SectionLayout) -> Vec<(Range<usize>, String)> {
+    layout
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            let next_header = layout.headers.get(i + 1).map(|h| h.start);
+            let end = layout
+                .keys
+                .iter()
+                .filter(|k| k.line_range.start >= header.end)
+                .filter(|k| next_header.is_none_or(|next| k.line_range.start < next))
+                .map(|k| k.line_range.end)
+                .next_back()
+                .unwrap_or(header.end);
+            (header.start..end, String::new())
+        })
+        .collect()
+}
+
+/// Copies the source to the destination, replacing each of the byte ranges with the accompanying
+/// string. Ranges where the start and end are the same insert the string at that position.
+///
+/// The ranges must not overlap. Edits at the same position are applied in the order given.
+fn splice(
+    source: &mut impl std::io::Read,
+    destination: &mut impl Write,
+    edits: &[(Range<usize>, String)],
+) -> Result<(), Error> {
+    let mut buffer = [0; WRITE_BUFFER_SIZE];
+    let mut splicer = Splicer::new(edits)?;
+    loop {
+        let bytes_read = source.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        splicer.write_window(&buffer[..bytes_read], destination)?;
+    }
+    splicer.finish(destination)
+}
+
+/// Async version of [`splice`].
+#[cfg(feature = "async")]
+async fn splice_async(
+    source: &mut (impl AsyncRead + Unpin),
+    destination: &mut impl Write,
+    edits: &[(Range<usize>, String)],
+) -> Result<(), Error> {
+    let mut buffer = [0; WRITE_BUFFER_SIZE];
+    let mut splicer = Splicer::new(edits)?;
+    loop {
+        let bytes_read = source.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            break;
+        }
+        splicer.write_window(&buffer[..bytes_read], destination)?;
+    }
+    splicer.finish(destination)
+}
+
+/// Keeps track of our position in the source while splicing so the source can be read in chunks.
+struct Splicer<'a> {
+    /// The edits sorted by where they start.
+    edits: std::iter::Peekable<std::vec::IntoIter<&'a (Range<usize>, String)>>,
+    /// Source position of the start of the current buffer window.
+    window_start: usize,
+    /// Everything in the source before this position has been written or skipped.
+    copied_until: usize,
+}
+
+impl<'a> Splicer<'a> {
+    /// Returns an error if any of the edits overlap.
+    fn new(edits: &'a [(Range<usize>, String)]) -> Result<Self, Error> {
+        let mut sorted: Vec<_> = edits.iter().collect();
+        // Stable sort so edits at the same position stay in order
+        sorted.sort_by_key(|(range, _)| range.start);
+        if let Some(pair) = sorted
+            .windows(2)
+            .find(|pair| pair[1].0.start < pair[0].0.end)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("edit at {:?} overlaps edit at {:?}", pair[1].0, pair[0].0),
+            )
+            .into());
+        }
+        Ok(Self {
+            edits: sorted.into_iter().peekable(),
+            window_start: 0,
+            copied_until: 0,
+        })
+    }
+
+    /// Writes the next chunk of the source to the destination with any edits in it applied.
+    fn write_window(&mut self, window: &[u8], destination: &mut impl Write) -> Result<(), Error> {
+        let window_end = self.window_start + window.len();
+        while self.copied_until < window_end {
+            let next_edit = self.edits.next_if(|(range, _)| range.start < window_end);
+            if let Some((range, replacement)) = next_edit {
+                debug_assert!(range.start >= self.copied_until, "edits overlap");
+                destination.write_all(
+                    &window[self.copied_until - self.window_start..range.start - self.window_start],
+                )?;
+                destination.write_all(replacement.as_bytes())?;
+                // The end might be past this window, in which case we'll skip the rest of the
+                // window and continue skipping in the next one.
+                self.copied_until = range.end;
+            } else {
+                destination.write_all(&window[self.copied_until - self.window_start..])?;
+                self.copied_until = window_end;
+            }
+        }
+        self.window_start = window_end;
+        Ok(())
+    }
+
+    /// Writes any edits that are at the end of the source. Returns an error if there are edits
+    /// past the end, which means the source is shorter than when the edits were worked out.
+    fn finish(self, destination: &mut impl Write) -> Result<(), Error> {
+        let source_end = self.window_start;
+        for (range, replacement) in self.edits {
+            if range.start != source_end || range.end != source_end {
+                return Err(edit_past_end(range, source_end));
+            }
+            destination.write_all(replacement.as_bytes())?;
+        }
+        // The last edit replaced bytes past the end of the source
+        if self.copied_until > source_end {
+            return Err(edit_past_end(&(source_end..self.copied_until), source_end));
+        }
+        Ok(())
+    }
+}
+
+fn edit_past_end(range: &Range<usize>, source_end: usize) -> Error {
+    std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        format!("edit at {range:?} is past the end of the source at {source_end}"),
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use crate::assert_eq_preserve_new_lines;
+    use crate::{NewKeyPosition, Newline, WriteLogFormat};
+    #[cfg(feature = "async")]
+    use ::paste::paste;
+    use indoc::indoc;
+
+    macro_rules! write_value_eq {
+        {
+            test_name = $test_name:ident,
+            input = $input:expr,
+            section = $section:expr,
+            key = $key:expr,
+            value = $value:expr,
+            expected = $expected:expr
+            $(, description = $description:expr)*
+            $(, parser = $parser:expr)* $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                #[allow(unused_variables)]
+                let parser = IniParser::default();
+                $(
+                    let parser = $parser;
+                )*
+                let mut reader = std::io::Cursor::new($input);
+                let mut dest = Vec::new();
+                parser.write_value(&mut reader, &mut dest, $section, $key, $value).unwrap();
+                let value = String::from_utf8(dest).unwrap();
+                let value = value.replace("\n", "\\n\n").replace(" ", "·");
+                let expected = $expected.replace("\n", "\\n\n").replace(" ", "·");
+                assert_eq_preserve_new_lines!(value, expected, $($description),*);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    #[allow(unused_variables)]
+                    let parser = IniParser::default();
+                    $(
+                        let parser = $parser;
+                    )*
+                    let mut reader = std::io::Cursor::new($input);
+                    let mut dest = Vec::new();
+                    parser.write_value_async(&mut reader, &mut dest, $section, $key, $value).await.unwrap();
+                    let value = String::from_utf8(dest).unwrap();
+                    assert_eq_preserve_new_lines!(value, $expected, $($description),*);
+                }
+            }
+        };
+    }
+
+    write_value_eq! {
+        test_name=write_value_no_section_replace,
+        input="name=tom",
+        section=None,
+        key="name",
+        value="bill",
+        expected="name=bill",
+        description="test",
+        parser=IniParser::default(),
+    }
+
+    write_value_eq! {
+        test_name=write_value_no_section_add_empty,
+        input="",
+        section=None,
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            name=bill
+        "},
+        description="expected name=bill to be added to an empty file",
+    }
+
+    write_value_eq! {
+        test_name=write_value_section_add_empty,
+        input="",
+        section=Some("contact"),
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            [contact]
+            name=bill
+        "},
+        description="expected [contact]name=bill to be added to an empty file",
+    }
+
+    write_value_eq! {
+        test_name=write_value_section_add,
+        input=indoc!{"
+            [contact]
+            name=bill
+        "},
+        section=Some("stats"),
+        key="performance",
+        value="100",
+        expected=indoc!{"
+            [contact]
+            name=bill
+            [stats]
+            performance=100
+        "},
+        description="expected [stats]performance=100 to be added as a new section, leaving the existing section intact.",
+    }
+
+    write_value_eq! {
+        test_name=write_value_section_add_blank_line,
+        input=indoc!{"
+            [contact]
+            name=bill
+        "},
+        section=Some("stats"),
+        key="performance",
+        value="100",
+        expected=indoc!{"
+            [contact]
+            name=bill
+
+            [stats]
+            performance=100
+        "},
+        description="expected a blank line to separate the new section from the previous one",
+        parser=IniParser{blank_line_before_section: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_section_add_empty_blank_line,
+        input="",
+        section=Some("contact"),
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            [contact]
+            name=bill
+        "},
+        description="expected no blank line at the start of an empty file",
+        parser=IniParser{blank_line_before_section: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_nested_section_without_parents,
+        input="",
+        section=Some("a.b.c"),
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            [a.b.c]
+            name=bill
+        "},
+        description="expected only the nested section to be added by default",
+    }
+
+    write_value_eq! {
+        test_name=write_value_nested_section_create_parents,
+        input="",
+        section=Some("a.b.c"),
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            [a]
+            [a.b]
+            [a.b.c]
+            name=bill
+        "},
+        description="expected the parent sections to be added before the nested section",
+        parser=IniParser{create_parent_sections: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_nested_section_create_missing_parents,
+        input=indoc!{"
+            [a]
+            name=tom
+        "},
+        section=Some("a.b.c"),
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            [a]
+            name=tom
+
+            [a.b]
+
+            [a.b.c]
+            name=bill
+        "},
+        description="expected only the missing parent sections to be added",
+        parser=IniParser{create_parent_sections: true, blank_line_before_section: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_nested_section_similar_name_not_parent,
+        input=indoc!{"
+            [ab]
+            name=tom
+        "},
+        section=Some("a.b"),
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            [ab]
+            name=tom
+            [a]
+            [a.b]
+            name=bill
+        "},
+        description="expected a section that only shares a prefix to not count as a parent",
+        parser=IniParser{create_parent_sections: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_no_section_add_blank_line,
+        input=indoc!{"
+            [contact]
+            name=tom
+        "},
+        section=None,
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            name=bill
+            [contact]
+            name=tom
+        "},
+        description="expected adding a global key to be unaffected by blank_line_before_section",
+        parser=IniParser{blank_line_before_section: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_section_add_multiple_sections,
+        input=indoc!{"
+            [schedule]
+
+            [contact]
+            name=bill
+        "},
+        section=Some("stats"),
+        key="performance",
+        value="100",
+        expected=indoc!{"
+            [schedule]
+
+            [contact]
+            name=bill
+            [stats]
+            performance=100
+        "},
+        description="expected [stats]performance=100 to be added as a new section, leaving the existing sections intact.",
+    }
+
+    write_value_eq! {
+        test_name=write_value_no_section_add_multiple_sections,
+        input=indoc!{"
+            [schedule]
+
+            [contact]
+            name=bill
+        "},
+        section=None,
+        key="performance",
+        value="100",
+        expected=indoc!{"
+            performance=100
+            [schedule]
+
+            [contact]
+            name=bill
+        "},
+        description="expected performance=100 to be added to the global space, leaving the existing sections intact.",
+    }
+
+    write_value_eq! {
+        test_name=write_value_no_section_add,
+        input=indoc!{"
+            [contact]
+            name=tom
+        "},
+        section=None,
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            name=bill
+            [contact]
+            name=tom
+        "},
+        description="expected this to add name=bill in the global space, leaving the contact section alone",
+    }
+
+    write_value_eq! {
+        test_name=write_new_value_existing_section,
+        input=indoc!{"
+            [contact]
+            name=bill
+        "},
+        section=Some("contact"),
+        key="email",
+        value="bill@example.com",
+        expected=indoc!{"
+            [contact]
+            name=bill
+            email=bill@example.com
+        "},
+        description="",
+    }
+
+    write_value_eq! {
+        test_name=write_value_section,
+        input=indoc!{"
+            [contact]
+            name=tom
+        "},
+        section=Some("contact"),
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            [contact]
+            name=bill
+        "},
+        description="expected name to change from tom to bill",
+    }
+
+    write_value_eq! {
+        test_name=write_value_trailing_comment,
+        input=indoc!{"
+            [contact]
+            name=tom # test
+        "},
+        section=Some("contact"),
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            [contact]
+            name=bill # test
+        "},
+        description="expected name to change while keeping the trailing comment",
+    }
+
+    write_value_eq! {
+        test_name=write_value_line_continuation_comment,
+        input=indoc!{"
+            [contact]
+            # this is a \\
+            multiline comment
+            test=hello
+        "},
+        section=Some("contact"),
+        key="test",
+        value="goodbye",
+        expected=indoc!{"
+            [contact]
+            # this is a \\
+            multiline comment
+            test=goodbye
+        "},
+        description="",
+        parser=IniParser{line_continuation:true, ..Default::default()}
+    }
+
+    write_value_eq! {
+        test_name=write_value_line_continuation,
+        input=indoc!{"
+            [contact]
+            description=first line \\
+            second line \\
+            third line
+            another_key=another value
+        "},
+        section=Some("contact"),
+        key="description",
+        value="hello world",
+        expected=indoc!{r#"
+            [contact]
+            description=hello world
+            another_key=another value
+        "#},
+        description="expected all of the lines for the value to be changed to `hello world`",
+        parser=IniParser{line_continuation:true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_continuation_in_trailing_comment,
+        input=indoc!{"
+            [contact]
+            description=first line ; see \\
+            another_key=another value
+        "},
+        section=Some("contact"),
+        key="another_key",
+        value="hello",
+        expected=indoc!{"
+            [contact]
+            description=first line ; see \\
+            another_key=hello
+        "},
+        description="expected the `\\` in the comment not to join the next line",
+        parser=IniParser{line_continuation:true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_line_continuation_disabled,
+        input=indoc!{"
+            [contact]
+            description=first line \\
+            another_key=another value
+        "},
+        section=Some("contact"),
+        key="another_key",
+        value="hello",
+        expected=indoc!{"
+            [contact]
+            description=first line \\
+            another_key=hello
+        "},
+        description="expected lines not to be joined when line continuation is disabled",
+        parser=IniParser::default(),
+    }
+
+    write_value_eq! {
+        test_name=write_value_continuation_char,
+        input=indoc!{"
+            [contact]
+            description=first line &
+            second line
+            another_key=another value
+        "},
+        section=Some("contact"),
+        key="description",
+        value="hello",
+        expected=indoc!{"
+            [contact]
+            description=hello
+            another_key=another value
+        "},
+        description="expected both lines of the value to be replaced",
+        parser=IniParser{line_continuation: true, continuation_char: '&', ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_line_continuation_windows_newlines,
+        input="[contact]\r\ndescription=first \\\r\nsecond\r\nanother_key=another value\r\n",
+        section=Some("contact"),
+        key="description",
+        value="hello world",
+        expected="[contact]\r\ndescription=hello world\r\nanother_key=another value\r\n",
+        description="expected both lines of the value to be replaced while keeping the CRLF of the last line",
+        parser=IniParser{line_continuation:true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_mixed_line_endings,
+        input="[contact]\nname=tom\r\nemail=tom@example.com\n",
+        section=Some("contact"),
+        key="name",
+        value="bill",
+        expected="[contact]\nname=bill\r\nemail=tom@example.com\n",
+        description="expected the line ending of the edited line to be preserved",
+    }
+
+    write_value_eq! {
+        test_name=write_value_new_key_crlf,
+        input="[contact]\r\nname=tom\r\n",
+        section=Some("contact"),
+        key="email",
+        value="tom@example.com",
+        expected="[contact]\r\nname=tom\r\nemail=tom@example.com\r\n",
+        description="expected the added line to use the configured line ending",
+        parser=IniParser{newline: Newline::CrLf, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_new_key_no_trailing_newline,
+        input="[s]\nkey=value",
+        section=Some("s"),
+        key="newkey",
+        value="x",
+        expected="[s]\nkey=value\nnewkey=x\n",
+        description="expected a newline to be added before the new key",
+    }
+
+    write_value_eq! {
+        test_name=write_value_new_key_no_trailing_newline_before_section,
+        input="[s]\nkey=value\n[t]\nother=1",
+        section=Some("t"),
+        key="newkey",
+        value="x",
+        expected="[s]\nkey=value\n[t]\nother=1\nnewkey=x\n",
+        description="expected a newline to be added before the new key",
+    }
+
+    write_value_eq! {
+        test_name=write_value_new_section_no_trailing_newline,
+        input="key=value",
+        section=Some("s"),
+        key="newkey",
+        value="x",
+        expected="key=value\n[s]\nnewkey=x\n",
+        description="expected a newline to be added before the new section",
+    }
+
+    write_value_eq! {
+        test_name=write_value_quote_preserve,
+        input="name=\"tom\"\nother=jerry\n",
+        section=None,
+        key="name",
+        value="bill",
+        expected="name=\"bill\"\nother=jerry\n",
+        description="expected the quotes around the old value to be kept",
+    }
+
+    write_value_eq! {
+        test_name=write_value_quote_preserve_unquoted,
+        input="name=tom\n",
+        section=None,
+        key="name",
+        value="bill",
+        expected="name=bill\n",
+        description="expected an unquoted value to stay unquoted",
+    }
+
+    write_value_eq! {
+        test_name=write_value_quote_preserve_already_quoted,
+        input="name=\"tom\"\n",
+        section=None,
+        key="name",
+        value="\"bill\"",
+        expected="name=\"bill\"\n",
+        description="expected a value that's already quoted not to be quoted again",
+    }
+
+    write_value_eq! {
+        test_name=write_value_quote_always,
+        input="name=\"tom\"\nother=jerry\n",
+        section=None,
+        key="other",
+        value="bill",
+        expected="name=\"tom\"\nother=\"bill\"\n",
+        description="expected the value to be quoted",
+        parser=IniParser{quote_values: QuoteStyle::Always, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_quote_never,
+        input="name=\"tom\"\n",
+        section=None,
+        key="name",
+        value="bill",
+        expected="name=bill\n",
+        description="expected the value not to be quoted",
+        parser=IniParser{quote_values: QuoteStyle::Never, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_flag,
+        input="[logging]\nEnableLogging ; comment\n",
+        section=Some("logging"),
+        key="EnableLogging",
+        value="false",
+        expected="[logging]\nEnableLogging=false ; comment\n",
+        description="expected a value to be added to the flag",
+        parser=IniParser{flag_keys_as_true: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_flag_empty,
+        input="[logging]\nEnableLogging\n",
+        section=Some("logging"),
+        key="EnableLogging",
+        value="",
+        expected="[logging]\nEnableLogging\n",
+        description="expected the flag to be left as is",
+        parser=IniParser{flag_keys_as_true: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_new_flag,
+        input="[logging]\nLevel=3\n",
+        section=Some("logging"),
+        key="EnableLogging",
+        value="",
+        expected="[logging]\nLevel=3\nEnableLogging\n",
+        description="expected an empty value to add the key as a flag",
+        parser=IniParser{flag_keys_as_true: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_empty_source_crlf,
+        input="",
+        section=None,
+        key="key",
+        value="value",
+        expected="key=value\r\n",
+        description="expected the first line of an empty file to use the configured line ending",
+        parser=IniParser{newline: Newline::CrLf, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_empty_source_new_section_crlf,
+        input="",
+        section=Some("contact"),
+        key="name",
+        value="tom",
+        expected="[contact]\r\nname=tom\r\n",
+        description="expected a new section in an empty file to use the configured line ending",
+        parser=IniParser{newline: Newline::CrLf, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_empty_value_existing_empty,
+        input=indoc!{"
+            name=
+        "},
+        section=None,
+        key="name",
+        value="",
+        expected=indoc!{"
+            name=
+        "},
+        description="expected writing an empty value to an empty value to reuse the existing key",
+    }
+
+    write_value_eq! {
+        test_name=write_value_existing_empty,
+        input=indoc!{"
+            name=
+        "},
+        section=None,
+        key="name",
+        value="bill",
+        expected=indoc!{"
+            name=bill
+        "},
+        description="expected writing a value to an empty value to reuse the existing key",
+    }
+
+    write_value_eq! {
+        test_name=write_value_emoji_characters,
+        input=indoc!{"
+            [display]
+            emoji=🚀🌎🌟 # space emoji
+        "},
+        section=Some("display"),
+        key="emoji",
+        value="🎮🎯",
+        expected=indoc!{"
+            [display]
+            emoji=🎮🎯 # space emoji
+        "},
+        description="multi-byte emoji characters as values should be allowed",
+    }
+
+    write_value_eq! {
+        test_name=write_value_special_characters_in_section,
+        input=indoc!{"
+            [special!@$%^&*()]
+            key=value
+        "},
+        section=Some("special!@$%^&*()"),
+        key="key",
+        value="new value",
+        expected=indoc!{"
+            [special!@$%^&*()]
+            key=new value
+        "},
+        description="section names should allow special characters",
+    }
+
+    write_value_eq! {
+        test_name=write_value_comment_delimiter_in_section,
+        input=indoc!{"
+            [special;#1]
+            key=value
+        "},
+        section=Some("special;#1"),
+        key="key",
+        value="new value",
+        expected=indoc!{"
+            [special;#1]
+            key=new value
+        "},
+        description="comment delimiter should work in section names",
+    }
+
+    #[test]
+    fn test_comment_delimiter_not_in_key() {
+        #[allow(unused_variables)]
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(indoc! {
+            "
+                [section]
+                special#1=value
+            "
+        });
+        let mut dest = Vec::new();
+        parser
+            .write_value(
+                &mut reader,
+                &mut dest,
+                Some("section"),
+                "special",
+                "new value",
+            )
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        let value = value.replace("\n", "\\n\n").replace(" ", "·");
+        let should_not_be = (indoc! {
+            "
+                [section]
+                special#1=new value
+            "
+        })
+        .replace("\n", "\\n\n")
+        .replace(" ", "·");
+        assert_ne!(
+            value, should_not_be,
+            "comment delimiter should not work in key names"
+        );
+    }
+
+    write_value_eq! {
+        test_name=write_value_special_characters_in_key,
+        input=indoc!{"
+            [section]
+            special!@$%^&*()=value
+        "},
+        section=Some("section"),
+        key="special!@$%^&*()",
+        value="new value",
+        expected=indoc!{"
+            [section]
+            special!@$%^&*()=new value
+        "},
+        description="key names should allow special characters that aren't comment delimiters",
+    }
+
+    write_value_eq! {
+        test_name=write_value_special_characters_in_value,
+        input=indoc!{"
+            [section]
+            key=value!@$%^&*()
+        "},
+        section=Some("section"),
+        key="key",
+        value="new!@$%^&*()",
+        expected=indoc!{"
+            [section]
+            key=new!@$%^&*()
+        "},
+        description="values should allow special characters that aren't comment delimiters",
+    }
+
+    write_value_eq! {
+        test_name=write_value_unicode_characters,
+        input=indoc!{"
+            [unicode]
+            key=áéíóúñ
+        "},
+        section=Some("unicode"),
+        key="key",
+        value="αβγδεζηθ",
+        expected=indoc!{"
+            [unicode]
+            key=αβγδεζηθ
+        "},
+        description="values should allow unicode characters",
+    }
+
+    write_value_eq! {
+        test_name=write_value_very_long_value,
+        input=indoc!{"
+            [section]
+            key=short value
+        "},
+        section=Some("section"),
+        key="key",
+        value="This is a very long value that contains many characters and should be properly handled by the parser. It includes multiple sentences and various punctuation marks. The value is intentionally made long to test the parser's ability to handle large values without issues.",
+        expected=indoc!{"
+            [section]
+            key=This is a very long value that contains many characters and should be properly handled by the parser. It includes multiple sentences and various punctuation marks. The value is intentionally made long to test the parser's ability to handle large values without issues.
+        "},
+        description="values should allow very long values",
+    }
+
+    write_value_eq! {
+        test_name=write_value_duplicate_keys_first,
+        input=indoc!{"
+            [section]
+            key=first value
+            other=other value
+            key=second value
+        "},
+        section=Some("section"),
+        key="key",
+        value="new value",
+        expected=indoc!{"
+            [section]
+            key=new value
+            other=other value
+            key=second value
+        "},
+        description="first key should be updated when using DuplicateKeyStrategy::UseFirst, other keys should be left alone",
+        parser=IniParser{duplicate_keys: DuplicateKeyStrategy::UseFirst,..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_duplicate_sections,
+        input=indoc!{"
+            [section]
+            key=first value
+            [other]
+            key=other value
+            [section]
+            key=second value
+        "},
+        section=Some("section"),
+        key="key",
+        value="new value",
+        expected=indoc!{"
+            [section]
+            key=new value
+            [other]
+            key=other value
+            [section]
+            key=second value
+        "},
+        description="first section should be updated when using DuplicateKeyStrategy::UseFirst, other sections should be left alone",
+        parser=IniParser{duplicate_keys: DuplicateKeyStrategy::UseFirst,..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_nested_sections,
+        input=indoc!{"
+            [parent]
+            key=parent value
+            [parent.child]
+            key=child value
+        "},
+        section=Some("parent.child"),
+        key="key",
+        value="new child value",
+        expected=indoc!{"
+            [parent]
+            key=parent value
+            [parent.child]
+            key=new child value
+        "},
+        description="nested sections should work the same as other sections and not affect the \"parent\" section",
+    }
+
+    write_value_eq! {
+        test_name=write_value_whitespace_in_section,
+        input=indoc!{"
+            [ section with spaces ]
+            key=value
+        "},
+        section=Some(" section with spaces "),
+        key="key",
+        value="new value",
+        expected=indoc!{"
+            [ section with spaces ]
+            key=new value
+        "},
+        description="whitespace around section names should not be significant",
+    }
+
+    write_value_eq! {
+        test_name=write_value_whitespace_in_key_value,
+        input=indoc!{"
+            [section]
+            key with spaces = value
+        "},
+        section=Some("section"),
+        key="key with spaces ",
+        value="new value",
+        expected=indoc!{"
+            [section]
+            key with spaces = new value
+        "},
+        description="whitespace around keys and values should be preserved",
+    }
+
+    write_value_eq! {
+        test_name=write_value_quoted_values,
+        input=indoc!{"
+            [section]
+            key=\"quoted value\"
+        "},
+        section=Some("section"),
+        key="key",
+        value="\"new quoted value\"",
+        expected=indoc!{"
+            [section]
+            key=\"new quoted value\"
+        "},
+        description="quoted values should be preserved when writing a value",
+    }
+
+    write_value_eq! {
+        test_name=write_value_multiple_comments,
+        input=indoc!{"
+            # Global comment
+            [section] # Section comment
+            key=value # Key comment
+        "},
+        section=Some("section"),
+        key="key",
+        value="new value",
+        expected=indoc!{"
+            # Global comment
+            [section] # Section comment
+            key=new value # Key comment
+        "},
+        description="multiple comments should be preserved when writing a value",
+    }
+    write_value_eq! {
+        test_name=add_key_to_section_trailing_empty_lines,
+        input=indoc!{"
+            [section]
+            key=value
+
+            [section2]
+            key=value2
+        "},
+        section=Some("section"),
+        key="key2",
+        value="new value",
+        expected=indoc!{"
+            [section]
+            key=value
+            key2=new value
+
+            [section2]
+            key=value2
+        "},
+        description="adding a key to a section should insert it before any trailing empty lines",
+    }
+
+    write_value_eq! {
+        test_name=add_key_to_global_trailing_empty_lines,
+        input=indoc!{"
+            # Global comment
+
+
+            [section]
+            key=value
+
+            [section2]
+            key=value2
+        "},
+        section=None,
+        key="key2",
+        value="new value",
+        expected=indoc!{"
+            # Global comment
+            key2=new value
+
+
+            [section]
+            key=value
+
+            [section2]
+            key=value2
+        "},
+        description="adding a key to the global section should insert it before any trailing empty lines",
+    }
+
+    write_value_eq! {
+        test_name=add_key_to_last_section_trailing_empty_lines,
+        input=indoc!{"
+            [section]
+            key=value
+
+            [section2]
+            key=value2
+
+
+
+        "},
+        section=Some("section2"),
+        key="key2",
+        value="new value",
+        expected=indoc!{"
+            [section]
+            key=value
+
+            [section2]
+            key=value2
+            key2=new value
+
+
+
+        "},
+        description="adding a key to the last section should insert it before any trailing empty lines",
+    }
+
+    #[test]
+    fn write_value_logged_replace() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[contact]\nname=tom\n");
+        let mut dest = Vec::new();
+        let mut log = Vec::new();
+        parser
+            .write_value_logged(
+                &mut reader,
+                &mut dest,
+                &mut log,
+                Some("contact"),
+                "name",
+                "bill",
+            )
+            .unwrap();
+        assert_eq!(String::from_utf8(dest).unwrap(), "[contact]\nname=bill\n");
+        let log = String::from_utf8(log).unwrap();
+        let (timestamp, rest) = log.split_once('\t').unwrap();
+        assert!(timestamp.parse::<u64>().is_ok());
+        assert_eq!(rest, "contact\tname\ttom\tbill\n");
+    }
+
+    #[test]
+    fn write_value_logged_new_key() {
+        let parser = IniParser {
+            write_log_format: WriteLogFormat::Csv,
+            ..Default::default()
+        };
+        let mut reader = std::io::Cursor::new("[contact]\nname=tom\n");
+        let mut dest = Vec::new();
+        let mut log = Vec::new();
+        parser
+            .write_value_logged(
+                &mut reader,
+                &mut dest,
+                &mut log,
+                None,
+                "motd",
+                "hello, world",
+            )
+            .unwrap();
+        let log = String::from_utf8(log).unwrap();
+        let (_, rest) = log.split_once(',').unwrap();
+        assert_eq!(rest, ",motd,,\"hello, world\"\n");
+    }
+
+    write_value_eq! {
+        test_name=write_value_triple_quoted,
+        input=indoc!{r#"
+            [motd]
+            message="""first line
+            second line"""
+            other=value
+        "#},
+        section=Some("motd"),
+        key="message",
+        value="hello",
+        expected=indoc!{r#"
+            [motd]
+            message="""hello"""
+            other=value
+        "#},
+        description="expected the whole triple quoted value to be replaced and re-wrapped in triple quotes",
+        parser=IniParser{triple_quote_strings: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_triple_quote_in_comment,
+        input=indoc!{r#"
+            # docs=""" start
+            [s]
+            key=1
+        "#},
+        section=Some("s"),
+        key="key",
+        value="2",
+        expected=indoc!{r#"
+            # docs=""" start
+            [s]
+            key=2
+        "#},
+        description="expected a triple quote in a comment to not start a multiline value",
+        parser=IniParser{triple_quote_strings: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_on_next_line,
+        input="[s]\nkey=\n    hello ; greeting\nother=1\n",
+        section=Some("s"),
+        key="key",
+        value="bye",
+        expected="[s]\nkey=\n    bye ; greeting\nother=1\n",
+        description="expected the indented value to be replaced",
+        parser=IniParser{value_on_next_line_when_empty: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_on_next_line_empty,
+        input="[s]\nkey=\nother=1\n",
+        section=Some("s"),
+        key="key",
+        value="bye",
+        expected="[s]\nkey=bye\nother=1\n",
+        description="expected an empty value without an indented line after it to be replaced",
+        parser=IniParser{value_on_next_line_when_empty: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_on_next_line_of_other_key,
+        input="[s]\nother=\n    key=1\n",
+        section=Some("s"),
+        key="key",
+        value="2",
+        expected="[s]\nother=\n    key=1\nkey=2\n",
+        description="expected an indented line that's another key's value to not be the key",
+        parser=IniParser{value_on_next_line_when_empty: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_triple_quoted_new_lines,
+        input=indoc!{"
+            [motd]
+            message=hello
+        "},
+        section=Some("motd"),
+        key="message",
+        value="first line\nsecond line",
+        expected=indoc!{r#"
+            [motd]
+            message="""first line
+            second line"""
+        "#},
+        description="expected a value containing new lines to be wrapped in triple quotes",
+        parser=IniParser{triple_quote_strings: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_empty_keeps_comment,
+        input="[server]\nkey=old # comment\nother = old ; comment\n",
+        section=Some("server"),
+        key="key",
+        value="",
+        expected="[server]\nkey= # comment\nother = old ; comment\n",
+        description="expected the value to be removed and the space before the comment kept",
+        parser=IniParser::default(),
+    }
+
+    write_value_eq! {
+        test_name=write_value_empty_keeps_comment_spaced,
+        input="[server]\nkey=old # comment\nother = old ; comment\n",
+        section=Some("server"),
+        key="other",
+        value="",
+        expected="[server]\nkey=old # comment\nother =  ; comment\n",
+        description="expected the value to be removed and the spaces around it kept",
+        parser=IniParser::default(),
+    }
+
+    write_value_eq! {
+        test_name=write_value_to_empty_value_with_comment,
+        input="[server]\nkey= # comment\n",
+        section=Some("server"),
+        key="key",
+        value="new",
+        expected="[server]\nkey=new # comment\n",
+        description="expected the value to be written right after the delimiter",
+        parser=IniParser::default(),
+    }
+
+    write_value_eq! {
+        test_name=write_value_tab_separated,
+        input="[server]\n\tport\t=\t8080\t; comment\n\thost\t=\tlocalhost\n",
+        section=Some("server"),
+        key="port",
+        value="9090",
+        expected="[server]\n\tport\t=\t9090\t; comment\n\thost\t=\tlocalhost\n",
+        description="expected the tabs around the delimiter to be kept",
+        parser=IniParser::default(),
+    }
+
+    write_value_eq! {
+        test_name=write_value_multiline_quoted,
+        input=indoc!{r#"
+            [motd]
+            message="first line
+            second line"
+            other=value
+        "#},
+        section=Some("motd"),
+        key="message",
+        value="hello",
+        expected=indoc!{r#"
+            [motd]
+            message="hello"
+            other=value
+        "#},
+        description="expected every line of the quoted value to be replaced",
+        parser=IniParser{multiline_quoted_values: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_multiline_quote_in_comment,
+        input=indoc!{r#"
+            ; say="hello
+            [s]
+            key=1
+        "#},
+        section=Some("s"),
+        key="key",
+        value="2",
+        expected=indoc!{r#"
+            ; say="hello
+            [s]
+            key=2
+        "#},
+        description="expected a quote in a comment to not start a multiline value",
+        parser=IniParser{multiline_quoted_values: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_multiline_quote_in_section_header,
+        input=indoc!{r#"
+            [a="b]
+            [s]
+            key=1
+        "#},
+        section=Some("s"),
+        key="key",
+        value="2",
+        expected=indoc!{r#"
+            [a="b]
+            [s]
+            key=2
+        "#},
+        description="expected a quote in a section header to not start a multiline value",
+        parser=IniParser{multiline_quoted_values: true, ..Default::default()},
+    }
+
+    const SECTION_VALUES_INI: &str = indoc! {"
+        [server]
+        host=localhost
+        port=8080
+
+        [other]
+        key=value
+    "};
+
+    /// Sets `port` and adds `debug` and `address` to the server section
+    fn set_server_values(parser: IniParser) -> String {
+        let mut reader = std::io::Cursor::new(SECTION_VALUES_INI);
+        let mut dest = Vec::new();
+        parser
+            .set_section_values(
+                &mut reader,
+                &mut dest,
+                Some("server"),
+                &[("port", "9000"), ("debug", "true"), ("address", "::1")],
+            )
+            .unwrap();
+        String::from_utf8(dest).unwrap()
+    }
+
+    #[test]
+    fn set_section_values_bottom() {
+        let value = set_server_values(IniParser::default());
+        let expected = indoc! {"
+            [server]
+            host=localhost
+            port=9000
+            debug=true
+            address=::1
+
+            [other]
+            key=value
+        "};
+        assert_eq_preserve_new_lines!(value, expected);
+    }
+
+    #[test]
+    fn set_section_values_top() {
+        let value = set_server_values(IniParser {
+            new_key_position: NewKeyPosition::Top,
+            ..Default::default()
+        });
+        let expected = indoc! {"
+            [server]
+            debug=true
+            address=::1
+            host=localhost
+            port=9000
+
+            [other]
+            key=value
+        "};
+        assert_eq_preserve_new_lines!(value, expected);
+    }
+
+    #[test]
+    fn set_section_values_sorted() {
+        let value = set_server_values(IniParser {
+            new_key_position: NewKeyPosition::Sorted,
+            ..Default::default()
+        });
+        let expected = indoc! {"
+            [server]
+            address=::1
+            debug=true
+            host=localhost
+            port=9000
+
+            [other]
+            key=value
+        "};
+        assert_eq_preserve_new_lines!(value, expected);
+    }
+
+    #[test]
+    fn set_section_values_trims_keys() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(SECTION_VALUES_INI);
+        let mut dest = Vec::new();
+        parser
+            .set_section_values(
+                &mut reader,
+                &mut dest,
+                Some("server"),
+                &[(" port ", "9000"), (" debug ", "true")],
+            )
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        let expected = indoc! {"
+            [server]
+            host=localhost
+            port=9000
+            debug=true
+
+            [other]
+            key=value
+        "};
+        assert_eq_preserve_new_lines!(value, expected);
+    }
+
+    #[test]
+    fn set_section_values_new_section() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(SECTION_VALUES_INI);
+        let mut dest = Vec::new();
+        parser
+            .set_section_values(
+                &mut reader,
+                &mut dest,
+                Some("new"),
+                &[("a", "1"), ("b", "2"), ("a", "3")],
+            )
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        let expected = format!("{SECTION_VALUES_INI}[new]\na=3\nb=2\n");
+        assert_eq_preserve_new_lines!(value, expected);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn set_section_values_async() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(SECTION_VALUES_INI);
+        let mut dest = Vec::new();
+        parser
+            .set_section_values_async(
+                &mut reader,
+                &mut dest,
+                Some("server"),
+                &[("port", "9000"), ("debug", "true"), ("address", "::1")],
+            )
+            .await
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq_preserve_new_lines!(value, set_server_values(IniParser::default()));
+    }
+
+    const WRITE_VALUES_EDITS: &[(Option<&str>, &str, &str)] = &[
+        (Some("server"), "port", "9000"),
+        (None, "version", "2"),
+        (Some("new"), "a", "1"),
+        (Some("other"), "key", "changed"),
+        (Some("server"), "port", "9001"),
+        (Some("server"), "debug", "true"),
+        (Some("another"), "b", "2"),
+    ];
+
+    const WRITE_VALUES_EXPECTED: &str = indoc! {"
+        version=2
+        [server]
+        host=localhost
+        port=9001
+        debug=true
+
+        [other]
+        key=changed
+        [new]
+        a=1
+        [another]
+        b=2
+    "};
+
+    #[test]
+    fn write_values() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(SECTION_VALUES_INI);
+        let mut dest = Vec::new();
+        parser
+            .write_values(&mut reader, &mut dest, WRITE_VALUES_EDITS)
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq_preserve_new_lines!(value, WRITE_VALUES_EXPECTED);
+    }
+
+    #[test]
+    fn set_section_values_no_trailing_newline() {
+        let parser = IniParser {
+            new_key_position: NewKeyPosition::Sorted,
+            ..Default::default()
+        };
+        let mut reader = std::io::Cursor::new("[s]\nkey=value");
+        let mut dest = Vec::new();
+        parser
+            .set_section_values(&mut reader, &mut dest, Some("s"), &[("x", "1"), ("y", "2")])
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq!(value, "[s]\nkey=value\nx=1\ny=2\n");
+    }
+
+    #[test]
+    fn write_values_no_trailing_newline() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[s]\nkey=value");
+        let mut dest = Vec::new();
+        parser
+            .write_values(
+                &mut reader,
+                &mut dest,
+                &[(Some("s"), "new", "x"), (Some("t"), "other", "y")],
+            )
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq!(value, "[s]\nkey=value\nnew=x\n[t]\nother=y\n");
+
+        let mut reader = std::io::Cursor::new("[s]\nkey=value");
+        let mut dest = Vec::new();
+        parser
+            .write_values(&mut reader, &mut dest, &[(Some("t"), "new", "x")])
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq!(value, "[s]\nkey=value\n[t]\nnew=x\n");
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn write_values_async() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(SECTION_VALUES_INI);
+        let mut dest = Vec::new();
+        parser
+            .write_values_async(&mut reader, &mut dest, WRITE_VALUES_EDITS)
+            .await
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq_preserve_new_lines!(value, WRITE_VALUES_EXPECTED);
+    }
+
+    #[test]
+    fn splice_across_buffer_windows() {
+        let source = "a".repeat(WRITE_BUFFER_SIZE * 3);
+        let edits = [
+            (0..0, "start".to_string()),
+            (
+                WRITE_BUFFER_SIZE - 2..WRITE_BUFFER_SIZE * 2 + 2,
+                "middle".to_string(),
+            ),
+            (
+                WRITE_BUFFER_SIZE * 3..WRITE_BUFFER_SIZE * 3,
+                "end".to_string(),
+            ),
+        ];
+        let mut dest = Vec::new();
+        splice(&mut source.as_bytes(), &mut dest, &edits).unwrap();
+        let expected = format!(
+            "start{}middle{}end",
+            "a".repeat(WRITE_BUFFER_SIZE - 2),
+            "a".repeat(WRITE_BUFFER_SIZE - 2)
+        );
+        assert_eq!(String::from_utf8(dest).unwrap(), expected);
+    }
+
+    #[test]
+    fn splice_unsorted() {
+        let edits = [
+            (4..5, "e".to_string()),
+            (0..0, "start ".to_string()),
+            (0..1, "A".to_string()),
+        ];
+        let mut dest = Vec::new();
+        splice(&mut "abcde".as_bytes(), &mut dest, &edits).unwrap();
+        assert_eq!(String::from_utf8(dest).unwrap(), "start Abcde");
+    }
+
+    #[test]
+    fn splice_overlapping() {
+        let edits = [(0..3, "x".to_string()), (2..4, "y".to_string())];
+        let mut dest = Vec::new();
+        let result = splice(&mut "abcde".as_bytes(), &mut dest, &edits);
+        assert_matches::assert_matches!(result, Err(Error::ReadIo(err)) if err.kind() == std::io::ErrorKind::InvalidInput);
+        assert!(dest.is_empty());
+    }
+
+    #[test]
+    fn splice_past_end() {
+        for edits in [[(6..6, "x".to_string())], [(3..7, "x".to_string())]] {
+            let mut dest = Vec::new();
+            let result = splice(&mut "abcde".as_bytes(), &mut dest, &edits);
+            assert_matches::assert_matches!(result, Err(Error::ReadIo(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    write_value_eq! {
+        test_name=write_value_utf8_bom,
+        input="\u{feff}name=tom\n[contact]\nname=tom\n",
+        section=None,
+        key="name",
+        value="bill",
+        expected="\u{feff}name=bill\n[contact]\nname=tom\n",
+        description="expected the byte order mark to be kept at the start of the file",
+    }
+
+    write_value_eq! {
+        test_name=write_value_utf8_bom_section,
+        input="\u{feff}[contact]\nname=tom\n",
+        section=Some("contact"),
+        key="name",
+        value="bill",
+        expected="\u{feff}[contact]\nname=bill\n",
+        description="expected the section header after the byte order mark to be found",
+    }
+
+    #[test]
+    fn value_len() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[contact]\nname = tom # comment\n");
+        let len = parser
+            .value_len(&mut reader, Some("contact"), "name")
+            .unwrap();
+        assert_eq!(len, Some(3));
+        let len = parser
+            .value_len(&mut reader, Some("contact"), "email")
+            .unwrap();
+        assert_eq!(len, None);
+    }
+
+    #[test]
+    fn value_len_line_continuation() {
+        let parser = IniParser {
+            line_continuation: true,
+            ..Default::default()
+        };
+        let mut reader = std::io::Cursor::new("description=first \\\nsecond\nother=value\n");
+        let len = parser.value_len(&mut reader, None, "description").unwrap();
+        assert_eq!(len, Some("first \\\nsecond".len()));
+    }
+
+    /// From a Project Zomboid servertest.ini, where the `\\` at the end of the comment used to
+    /// join the next line onto the `Map` line.
+    const SERVERTEST_INI: &str = indoc! {"
+        PVP=true
+        Map=Muldraugh, KY ; see C:\\Users\\Zomboid\\
+        MaxPlayers=32
+    "};
+
+    #[test]
+    fn value_len_continuation_in_comment() {
+        let parser = IniParser {
+            line_continuation: true,
+            ..Default::default()
+        };
+        let mut reader = std::io::Cursor::new(SERVERTEST_INI);
+        let len = parser.value_len(&mut reader, None, "Map").unwrap();
+        assert_eq!(len, Some("Muldraugh, KY".len()));
+    }
+
+    write_value_eq! {
+        test_name=write_value_servertest_map,
+        input=SERVERTEST_INI,
+        section=None,
+        key="Map",
+        value="Riverside, KY",
+        expected=indoc!{"
+            PVP=true
+            Map=Riverside, KY ; see C:\\Users\\Zomboid\\
+            MaxPlayers=32
+        "},
+        description="expected only the Map value to change",
+        parser=IniParser{line_continuation: true, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_servertest_after_map,
+        input=SERVERTEST_INI,
+        section=None,
+        key="MaxPlayers",
+        value="16",
+        expected=indoc!{"
+            PVP=true
+            Map=Muldraugh, KY ; see C:\\Users\\Zomboid\\
+            MaxPlayers=16
+        "},
+        description="expected the key after Map to be found and replaced in place",
+        parser=IniParser{line_continuation: true, ..Default::default()},
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn value_len_async() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[contact]\nname = tom # comment\n");
+        let len = parser
+            .value_len_async(&mut reader, Some("contact"), "name")
+            .await
+            .unwrap();
+        assert_eq!(len, Some(3));
+    }
+
+    /// Generate async and sync versions of tests that delete a value
+    macro_rules! delete_value_eq {
+        {
+            test_name = $test_name:ident,
+            input = $input:expr,
+            section = $section:expr,
             key = $key:expr,
-            value = $value:expr,
-            expected = $expected:expr
-            $(, description = $description:expr)*
-            $(, parser = $parser:expr)* $(,)?
+            expected = $expected:expr,
+            deleted = $deleted:expr
+            $(, parser = $parser:expr)? $(,)?
         } => {
             #[test]
             fn $test_name() {
@@ -408,625 +3559,1378 @@ mod tests {
                 let parser = IniParser::default();
                 $(
                     let parser = $parser;
-                )*
+                )?
                 let mut reader = std::io::Cursor::new($input);
                 let mut dest = Vec::new();
-                parser.write_value(&mut reader, &mut dest, $section, $key, $value).unwrap();
+                let deleted = parser.delete_value(&mut reader, &mut dest, $section, $key).unwrap();
                 let value = String::from_utf8(dest).unwrap();
-                let value = value.replace("\n", "\\n\n").replace(" ", "·");
-                let expected = $expected.replace("\n", "\\n\n").replace(" ", "·");
-                assert_eq_preserve_new_lines!(value, expected, $($description),*);
+                assert_eq_preserve_new_lines!(value, $expected);
+                assert_eq!(deleted, $deleted);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    #[allow(unused_variables)]
+                    let parser = IniParser::default();
+                    $(
+                        let parser = $parser;
+                    )?
+                    let mut reader = std::io::Cursor::new($input);
+                    let mut dest = Vec::new();
+                    let deleted = parser
+                        .delete_value_async(&mut reader, &mut dest, $section, $key)
+                        .await
+                        .unwrap();
+                    let value = String::from_utf8(dest).unwrap();
+                    assert_eq_preserve_new_lines!(value, $expected);
+                    assert_eq!(deleted, $deleted);
+                }
+            }
+        };
+    }
+
+    delete_value_eq! {
+        test_name=delete_value_on_next_line,
+        input="[s]\nkey=\n    hello\nother=1\n",
+        section=Some("s"),
+        key="key",
+        expected="[s]\nother=1\n",
+        deleted=true,
+        parser=IniParser{value_on_next_line_when_empty: true, ..Default::default()},
+    }
+
+    delete_value_eq! {
+        test_name=delete_value,
+        input=indoc!{"
+            # comment
+            [contact]
+            name=tom
+
+            email=tom@example.com
+            [other]
+            name=bill
+        "},
+        section=Some("contact"),
+        key="name",
+        expected=indoc!{"
+            # comment
+            [contact]
+
+            email=tom@example.com
+            [other]
+            name=bill
+        "},
+        deleted=true,
+    }
+
+    delete_value_eq! {
+        test_name=delete_value_only_key,
+        input=indoc!{"
+            [contact]
+            name=tom
+
+            [other]
+            name=bill
+        "},
+        section=Some("contact"),
+        key="name",
+        expected=indoc!{"
+            [contact]
+
+            [other]
+            name=bill
+        "},
+        deleted=true,
+    }
+
+    delete_value_eq! {
+        test_name=delete_value_missing,
+        input=indoc!{"
+            [contact]
+            name=tom
+        "},
+        section=Some("contact"),
+        key="email",
+        expected=indoc!{"
+            [contact]
+            name=tom
+        "},
+        deleted=false,
+    }
+
+    delete_value_eq! {
+        test_name=delete_value_line_continuation,
+        input=indoc!{"
+            description=first \\
+            second
+            name=tom
+        "},
+        section=None,
+        key="description",
+        expected=indoc!{"
+            name=tom
+        "},
+        deleted=true,
+        parser=IniParser{line_continuation: true, ..Default::default()},
+    }
+
+    delete_value_eq! {
+        test_name=delete_value_duplicate_first,
+        input=indoc!{"
+            name=tom
+            name=bill
+        "},
+        section=None,
+        key="name",
+        expected=indoc!{"
+            name=bill
+        "},
+        deleted=true,
+        parser=IniParser{duplicate_keys: DuplicateKeyStrategy::UseFirst, ..Default::default()},
+    }
+
+    delete_value_eq! {
+        test_name=delete_value_duplicate_last,
+        input=indoc!{"
+            name=tom
+            name=bill
+        "},
+        section=None,
+        key="name",
+        expected=indoc!{"
+            name=tom
+        "},
+        deleted=true,
+    }
+
+    #[test]
+    fn write_value_max_bytes() {
+        let parser = IniParser {
+            max_bytes: Some(8),
+            ..Default::default()
+        };
+        let mut reader = std::io::Cursor::new("a = 1 \\\n  2\nb = 3\n");
+        let result = parser.write_value(&mut reader, Vec::new(), None, "b", "4");
+        assert_matches::assert_matches!(result, Err(Error::TooLarge { limit: 8, .. }));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn write_value_max_bytes_async() {
+        let parser = IniParser {
+            max_bytes: Some(8),
+            ..Default::default()
+        };
+        let mut reader = std::io::Cursor::new("a = 1 \\\n  2\nb = 3\n");
+        let result = parser
+            .write_value_async(&mut reader, Vec::new(), None, "b", "4")
+            .await;
+        assert_matches::assert_matches!(result, Err(Error::TooLarge { limit: 8, .. }));
+    }
+
+    /// A source that starts failing part way through the second time it's read, like
+    /// [`IniParser::write_value`] does after scanning the source.
+    struct FailsOnSecondPass {
+        inner: std::io::Cursor<&'static str>,
+        rewinds: usize,
+    }
+
+    impl std::io::Read for FailsOnSecondPass {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.rewinds < 2 {
+                return std::io::Read::read(&mut self.inner, buf);
+            }
+            if self.inner.position() >= 8 {
+                return Err(std::io::Error::other("disconnected"));
+            }
+            let len = buf.len().min(8);
+            std::io::Read::read(&mut self.inner, &mut buf[..len])
+        }
+    }
+
+    impl Seek for FailsOnSecondPass {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            if pos == std::io::SeekFrom::Start(0) {
+                self.rewinds += 1;
+            }
+            Seek::seek(&mut self.inner, pos)
+        }
+    }
+
+    fn fails_on_second_pass() -> FailsOnSecondPass {
+        FailsOnSecondPass {
+            inner: std::io::Cursor::new("[section]\nname=tom\nemail=tom@example.com\n"),
+            rewinds: 0,
+        }
+    }
+
+    #[test]
+    fn write_value_partial() {
+        let parser = IniParser::default();
+        let mut dest = Vec::new();
+        let result = parser.write_value(
+            &mut fails_on_second_pass(),
+            &mut dest,
+            Some("section"),
+            "name",
+            "bill",
+        );
+        assert_matches::assert_matches!(result, Err(Error::ReadIo(_)));
+        assert_eq!(dest, b"[section");
+    }
+
+    #[test]
+    fn write_value_checked() {
+        let parser = IniParser::default();
+        let mut dest = Vec::new();
+        let result = parser.write_value_checked(
+            &mut fails_on_second_pass(),
+            &mut dest,
+            Some("section"),
+            "name",
+            "bill",
+        );
+        assert_matches::assert_matches!(result, Err(Error::ReadIo(_)));
+        assert!(dest.is_empty());
+
+        let mut reader = std::io::Cursor::new("[section]\nname=tom\n");
+        parser
+            .write_value_checked(&mut reader, &mut dest, Some("section"), "name", "bill")
+            .unwrap();
+        assert_eq!(String::from_utf8(dest).unwrap(), "[section]\nname=bill\n");
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn write_value_checked_async() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[section]\nname=tom\n");
+        let mut dest = Vec::new();
+        parser
+            .write_value_checked_async(&mut reader, &mut dest, Some("section"), "name", "bill")
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8(dest).unwrap(), "[section]\nname=bill\n");
+    }
+
+    #[test]
+    fn write_value_invalid_utf8() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(b"a=1\nb=\xff\n".to_vec());
+        let result = parser.write_value(&mut reader, Vec::new(), None, "c", "3");
+        assert_matches::assert_matches!(result, Err(Error::InvalidUtf8 { byte_offset: 6 }));
+    }
+
+    #[test]
+    fn delete_value_duplicate_error() {
+        let parser = IniParser {
+            duplicate_keys: DuplicateKeyStrategy::Error,
+            ..Default::default()
+        };
+        let mut reader = std::io::Cursor::new("name=tom\nname=bill\n");
+        let result = parser.delete_value(&mut reader, Vec::new(), None, "name");
+        assert_matches::assert_matches!(result, Err(Error::DuplicateKey { line: 2, .. }));
+    }
+
+    write_value_eq! {
+        test_name=write_value_empty_header_global,
+        input=indoc!{"
+            name=tom
+            [contact]
+            email=tom@example.com
+            []
+            age=30
+        "},
+        section=None,
+        key="age",
+        value="31",
+        expected=indoc!{"
+            name=tom
+            [contact]
+            email=tom@example.com
+            []
+            age=31
+        "},
+        description="expected the key after the empty header to be treated as global",
+        parser=IniParser{empty_header_is_global: true, ..Default::default()},
+    }
+
+    /// Generate async and sync versions of tests that delete a section
+    macro_rules! delete_section_eq {
+        {
+            test_name = $test_name:ident,
+            input = $input:expr,
+            section = $section:expr,
+            expected = $expected:expr,
+            deleted = $deleted:expr $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = IniParser::default();
+                let mut reader = std::io::Cursor::new($input);
+                let mut dest = Vec::new();
+                let deleted = parser.delete_section(&mut reader, &mut dest, $section).unwrap();
+                let value = String::from_utf8(dest).unwrap();
+                assert_eq_preserve_new_lines!(value, $expected);
+                assert_eq!(deleted, $deleted);
             }
 
             #[cfg(feature = "async")]
             paste! {
                 #[tokio::test]
                 async fn [<$test_name _async>]() {
-                    #[allow(unused_variables)]
                     let parser = IniParser::default();
-                    $(
-                        let parser = $parser;
-                    )*
                     let mut reader = std::io::Cursor::new($input);
                     let mut dest = Vec::new();
-                    parser.write_value_async(&mut reader, &mut dest, $section, $key, $value).await.unwrap();
+                    let deleted = parser
+                        .delete_section_async(&mut reader, &mut dest, $section)
+                        .await
+                        .unwrap();
                     let value = String::from_utf8(dest).unwrap();
-                    assert_eq_preserve_new_lines!(value, $expected, $($description),*);
+                    assert_eq_preserve_new_lines!(value, $expected);
+                    assert_eq!(deleted, $deleted);
                 }
             }
         };
     }
 
-    write_value_eq! {
-        test_name=write_value_no_section_replace,
-        input="name=tom",
-        section=None,
-        key="name",
-        value="bill",
-        expected="name=bill",
-        description="test",
-        parser=IniParser::default(),
-    }
-
-    write_value_eq! {
-        test_name=write_value_no_section_add_empty,
-        input="",
-        section=None,
-        key="name",
-        value="bill",
-        expected=indoc!{"
-            name=bill
-        "},
-        description="expected name=bill to be added to an empty file",
-    }
-
-    write_value_eq! {
-        test_name=write_value_section_add_empty,
-        input="",
-        section=Some("contact"),
-        key="name",
-        value="bill",
-        expected=indoc!{"
-            [contact]
-            name=bill
-        "},
-        description="expected [contact]name=bill to be added to an empty file",
-    }
-
-    write_value_eq! {
-        test_name=write_value_section_add,
+    delete_section_eq! {
+        test_name=delete_section,
         input=indoc!{"
+            name=global
+            # Contact details
             [contact]
-            name=bill
-        "},
-        section=Some("stats"),
-        key="performance",
-        value="100",
-        expected=indoc!{"
-            [contact]
-            name=bill
-            [stats]
-            performance=100
-        "},
-        description="expected [stats]performance=100 to be added as a new section, leaving the existing section intact.",
-    }
-
-    write_value_eq! {
-        test_name=write_value_section_add_multiple_sections,
-        input=indoc!{"
-            [schedule]
+            name=tom
+            # comment inside the section
+            email=tom@example.com
 
-            [contact]
-            name=bill
+            # Other section
+            [other]
+            key=value
         "},
-        section=Some("stats"),
-        key="performance",
-        value="100",
+        section="contact",
         expected=indoc!{"
-            [schedule]
+            name=global
+            # Contact details
 
-            [contact]
-            name=bill
-            [stats]
-            performance=100
+            # Other section
+            [other]
+            key=value
         "},
-        description="expected [stats]performance=100 to be added as a new section, leaving the existing sections intact.",
+        deleted=true,
     }
 
-    write_value_eq! {
-        test_name=write_value_no_section_add_multiple_sections,
+    delete_section_eq! {
+        test_name=delete_section_last,
         input=indoc!{"
-            [schedule]
+            [other]
+            key=value
 
             [contact]
-            name=bill
+            name=tom
         "},
-        section=None,
-        key="performance",
-        value="100",
+        section="contact",
         expected=indoc!{"
-            performance=100
-            [schedule]
+            [other]
+            key=value
 
-            [contact]
-            name=bill
         "},
-        description="expected performance=100 to be added to the global space, leaving the existing sections intact.",
+        deleted=true,
     }
 
-    write_value_eq! {
-        test_name=write_value_no_section_add,
+    delete_section_eq! {
+        test_name=delete_section_duplicate,
         input=indoc!{"
             [contact]
             name=tom
+            [other]
+            key=value
+            [contact]
+            email=tom@example.com
         "},
-        section=None,
-        key="name",
-        value="bill",
+        section="contact",
         expected=indoc!{"
-            name=bill
-            [contact]
-            name=tom
+            [other]
+            key=value
         "},
-        description="expected this to add name=bill in the global space, leaving the contact section alone",
+        deleted=true,
     }
 
-    write_value_eq! {
-        test_name=write_new_value_existing_section,
+    delete_section_eq! {
+        test_name=delete_section_empty,
         input=indoc!{"
             [contact]
-            name=bill
+            [other]
+            key=value
         "},
-        section=Some("contact"),
-        key="email",
-        value="bill@example.com",
+        section="contact",
         expected=indoc!{"
-            [contact]
-            name=bill
-            email=bill@example.com
+            [other]
+            key=value
         "},
-        description="",
+        deleted=true,
     }
 
-    write_value_eq! {
-        test_name=write_value_section,
+    delete_section_eq! {
+        test_name=delete_section_missing,
         input=indoc!{"
-            [contact]
-            name=tom
+            [other]
+            key=value
         "},
-        section=Some("contact"),
-        key="name",
-        value="bill",
+        section="contact",
         expected=indoc!{"
-            [contact]
-            name=bill
+            [other]
+            key=value
         "},
-        description="expected name to change from tom to bill",
+        deleted=false,
     }
 
-    write_value_eq! {
-        test_name=write_value_trailing_comment,
-        input=indoc!{"
-            [contact]
-            name=tom # test
-        "},
-        section=Some("contact"),
-        key="name",
-        value="bill",
-        expected=indoc!{"
-            [contact]
-            name=bill # test
-        "},
-        description="expected name to change while keeping the trailing comment",
+    #[cfg(feature = "fs")]
+    #[test]
+    fn update_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.ini");
+        for durable in [true, false] {
+            std::fs::write(&path, "[server]\nport=8080\n").unwrap();
+            let parser = IniParser {
+                durable,
+                ..Default::default()
+            };
+            parser
+                .update_file(&path, Some("server"), "port", "9090")
+                .unwrap();
+            assert_eq!(
+                std::fs::read_to_string(&path).unwrap(),
+                "[server]\nport=9090\n"
+            );
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        }
     }
 
-    write_value_eq! {
-        test_name=write_value_line_continuation_comment,
-        input=indoc!{"
-            [contact]
-            # this is a \\
-            multiline comment
-            test=hello
-        "},
-        section=Some("contact"),
-        key="test",
-        value="goodbye",
-        expected=indoc!{"
-            [contact]
-            # this is a \\
-            multiline comment
-            test=goodbye
-        "},
-        description="",
-        parser=IniParser{line_continuation:true, ..Default::default()}
+    #[cfg(feature = "fs")]
+    #[test]
+    fn update_file_returning_old() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.ini");
+        std::fs::write(&path, "[server]\nport=8080\n").unwrap();
+        let parser = IniParser::default();
+        let old = parser
+            .update_file_returning_old(&path, Some("server"), "port", "9090")
+            .unwrap();
+        assert_eq!(old.as_deref(), Some("8080"));
+        let old = parser
+            .update_file_returning_old(&path, Some("server"), "host", "localhost")
+            .unwrap();
+        assert_eq!(old, None);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[server]\nport=9090\nhost=localhost\n"
+        );
     }
 
-    write_value_eq! {
-        test_name=write_value_line_continuation,
-        input=indoc!{"
-            [contact]
-            description=first line \\
-            second line \\
-            third line
-            another_key=another value
-        "},
-        section=Some("contact"),
-        key="description",
-        value="hello world",
-        expected=indoc!{r#"
-            [contact]
-            description=hello world
-            another_key=another value
-        "#},
-        description="expected all of the lines for the value to be changed to `hello world`",
-        parser=IniParser{line_continuation:true, ..Default::default()},
+    #[cfg(all(feature = "fs", unix))]
+    #[test]
+    fn update_file_ignores_existing_temp_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.ini");
+        let victim = dir.path().join("victim.txt");
+        std::fs::write(&path, "[server]\nport=8080\n").unwrap();
+        std::fs::write(&victim, "untouched").unwrap();
+        std::os::unix::fs::symlink(&victim, dir.path().join(".config.ini.tmp")).unwrap();
+        IniParser::default()
+            .update_file(&path, Some("server"), "port", "9090")
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&victim).unwrap(), "untouched");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[server]\nport=9090\n"
+        );
     }
 
-    write_value_eq! {
-        test_name=write_empty_value_existing_empty,
-        input=indoc!{"
-            name=
-        "},
-        section=None,
-        key="name",
-        value="",
-        expected=indoc!{"
-            name=
-        "},
-        description="expected writing an empty value to an empty value to reuse the existing key",
+    #[cfg(feature = "fs")]
+    #[test]
+    fn update_file_concurrently() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.ini");
+        std::fs::write(&path, "[server]\nport=8080\n").unwrap();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let path = &path;
+                    scope.spawn(move || {
+                        IniParser::default().update_file(
+                            path,
+                            Some("server"),
+                            "port",
+                            &format!("900{i}"),
+                        )
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap().unwrap();
+            }
+        });
+        // One of the updates wins, the file is never a mix of them
+        let port: Option<u16> = IniParser::default()
+            .read_value(std::fs::File::open(&path).unwrap(), Some("server"), "port")
+            .unwrap();
+        assert!(port.is_some_and(|port| (9000..9008).contains(&port)));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
     }
 
-    write_value_eq! {
-        test_name=write_value_existing_empty,
+    #[cfg(feature = "fs")]
+    #[test]
+    fn write_value_to_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = ["one.ini", "two.ini", "three.ini"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        for path in &paths {
+            std::fs::write(path, "[server]\nport=8080\n").unwrap();
+        }
+        let mut permissions = std::fs::metadata(&paths[1]).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&paths[1], permissions).unwrap();
+
+        let parser = IniParser::default();
+        let path_refs: Vec<&Path> = paths.iter().map(|p| p.as_path()).collect();
+        let results = parser
+            .write_value_to_paths(&path_refs, Some("server"), "port", "9090")
+            .unwrap();
+
+        assert_matches::assert_matches!(results[0], Ok(()));
+        assert_matches::assert_matches!(results[1], Err(Error::ReadIo(_)));
+        assert_matches::assert_matches!(results[2], Ok(()));
+        assert_eq!(
+            std::fs::read_to_string(&paths[0]).unwrap(),
+            "[server]\nport=9090\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&paths[1]).unwrap(),
+            "[server]\nport=8080\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&paths[2]).unwrap(),
+            "[server]\nport=9090\n"
+        );
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    /// Generate async and sync versions of tests that rename a key
+    macro_rules! rename_key_eq {
+        {
+            test_name = $test_name:ident,
+            input = $input:expr,
+            section = $section:expr,
+            old_key = $old_key:expr,
+            new_key = $new_key:expr,
+            expected = $expected:expr,
+            renamed = $renamed:expr $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = IniParser::default();
+                let mut reader = std::io::Cursor::new($input);
+                let mut dest = Vec::new();
+                let renamed = parser
+                    .rename_key(&mut reader, &mut dest, $section, $old_key, $new_key)
+                    .unwrap();
+                let value = String::from_utf8(dest).unwrap();
+                assert_eq_preserve_new_lines!(value, $expected);
+                assert_eq!(renamed, $renamed);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    let parser = IniParser::default();
+                    let mut reader = std::io::Cursor::new($input);
+                    let mut dest = Vec::new();
+                    let renamed = parser
+                        .rename_key_async(&mut reader, &mut dest, $section, $old_key, $new_key)
+                        .await
+                        .unwrap();
+                    let value = String::from_utf8(dest).unwrap();
+                    assert_eq_preserve_new_lines!(value, $expected);
+                    assert_eq!(renamed, $renamed);
+                }
+            }
+        };
+    }
+
+    rename_key_eq! {
+        test_name=rename_key,
         input=indoc!{"
-            name=
+            [contact]
+              name  =  name ; name comment
+            email=name@example.com
         "},
-        section=None,
-        key="name",
-        value="bill",
+        section=Some("contact"),
+        old_key="name",
+        new_key="full_name",
         expected=indoc!{"
-            name=bill
+            [contact]
+              full_name  =  name ; name comment
+            email=name@example.com
         "},
-        description="expected writing a value to an empty value to reuse the existing key",
+        renamed=true,
     }
 
-    write_value_eq! {
-        test_name=write_value_emoji_characters,
+    rename_key_eq! {
+        test_name=rename_key_other_section,
         input=indoc!{"
-            [display]
-            emoji=🚀🌎🌟 # space emoji
+            name=global
+            [contact]
+            name=tom
+            [other]
+            name=other
+            [contact]
+            name=tom2
         "},
-        section=Some("display"),
-        key="emoji",
-        value="🎮🎯",
+        section=Some("contact"),
+        old_key="name",
+        new_key="full_name",
         expected=indoc!{"
-            [display]
-            emoji=🎮🎯 # space emoji
+            name=global
+            [contact]
+            full_name=tom
+            [other]
+            name=other
+            [contact]
+            full_name=tom2
         "},
-        description="multi-byte emoji characters as values should be allowed",
+        renamed=true,
     }
 
-    write_value_eq! {
-        test_name=write_value_special_characters_in_section,
+    rename_key_eq! {
+        test_name=rename_key_missing,
         input=indoc!{"
-            [special!@$%^&*()]
-            key=value
+            [contact]
+            email=name@example.com
         "},
-        section=Some("special!@$%^&*()"),
-        key="key",
-        value="new value",
+        section=Some("contact"),
+        old_key="name",
+        new_key="full_name",
         expected=indoc!{"
-            [special!@$%^&*()]
-            key=new value
+            [contact]
+            email=name@example.com
         "},
-        description="section names should allow special characters",
+        renamed=false,
     }
 
     write_value_eq! {
-        test_name=write_value_comment_delimiter_in_section,
+        test_name=write_value_case_insensitive,
         input=indoc!{"
-            [special;#1]
-            key=value
+            [Server]
+            MaxPlayers=40
         "},
-        section=Some("special;#1"),
-        key="key",
-        value="new value",
+        section=Some("server"),
+        key="maxplayers",
+        value="50",
         expected=indoc!{"
-            [special;#1]
-            key=new value
+            [Server]
+            MaxPlayers=50
         "},
-        description="comment delimiter should work in section names",
+        description="keys and sections should match regardless of ASCII case",
+        parser=IniParser{case_sensitive: false, ..Default::default()},
     }
 
-    #[test]
-    fn test_comment_delimiter_not_in_key() {
-        #[allow(unused_variables)]
-        let parser = IniParser::default();
-        let mut reader = std::io::Cursor::new(indoc! {
-            "
-                [section]
-                special#1=value
-            "
-        });
-        let mut dest = Vec::new();
-        parser
-            .write_value(
-                &mut reader,
-                &mut dest,
-                Some("section"),
-                "special",
-                "new value",
-            )
-            .unwrap();
-        let value = String::from_utf8(dest).unwrap();
-        let value = value.replace("\n", "\\n\n").replace(" ", "·");
-        let should_not_be = (indoc! {
-            "
-                [section]
-                special#1=new value
-            "
-        })
-        .replace("\n", "\\n\n")
-        .replace(" ", "·");
-        assert_ne!(
-            value, should_not_be,
-            "comment delimiter should not work in key names"
-        );
+    const CRLF_INI: &str =
+        "[server]\r\nhost=localhost\r\nmotd=hello \\\r\n world\r\nport=8080 ; comment\r\n";
+
+    write_value_eq! {
+        test_name=write_value_crlf,
+        input=CRLF_INI,
+        section=Some("server"),
+        key="port",
+        value="9090",
+        expected="[server]\r\nhost=localhost\r\nmotd=hello \\\r\n world\r\nport=9090 ; comment\r\n",
+        description="CRLF line endings should be kept as they are when replacing a value",
+        parser=IniParser{line_continuation: true, ..Default::default()},
     }
 
     write_value_eq! {
-        test_name=write_value_special_characters_in_key,
-        input=indoc!{"
-            [section]
-            special!@$%^&*()=value
-        "},
-        section=Some("section"),
-        key="special!@$%^&*()",
-        value="new value",
-        expected=indoc!{"
-            [section]
-            special!@$%^&*()=new value
-        "},
-        description="key names should allow special characters that aren't comment delimiters",
+        test_name=write_value_crlf_continuation,
+        input=CRLF_INI,
+        section=Some("server"),
+        key="motd",
+        value="hi",
+        expected="[server]\r\nhost=localhost\r\nmotd=hi\r\nport=8080 ; comment\r\n",
+        description="a continued value should be replaced up to but not including the CRLF",
+        parser=IniParser{line_continuation: true, ..Default::default()},
     }
 
     write_value_eq! {
-        test_name=write_value_special_characters_in_value,
+        test_name=write_value_crlf_new_key,
+        input=CRLF_INI,
+        section=Some("server"),
+        key="debug",
+        value="true",
+        expected="[server]\r\nhost=localhost\r\nmotd=hello \\\r\n world\r\nport=8080 ; comment\r\ndebug=true\r\n",
+        description="a new key should be added after the CRLF of the last line",
+        parser=IniParser{line_continuation: true, newline: Newline::CrLf, ..Default::default()},
+    }
+
+    write_value_eq! {
+        test_name=write_value_comment_above_preserved,
         input=indoc!{"
-            [section]
-            key=value!@$%^&*()
+            [server]
+            # disabled
+            max=40
         "},
-        section=Some("section"),
-        key="key",
-        value="new!@$%^&*()",
+        section=Some("server"),
+        key="max",
+        value="50",
         expected=indoc!{"
-            [section]
-            key=new!@$%^&*()
+            [server]
+            # disabled
+            max=50
         "},
-        description="values should allow special characters that aren't comment delimiters",
+        description="a comment on the line above the key should be left alone",
     }
 
-    write_value_eq! {
-        test_name=write_value_unicode_characters,
+    /// Generate async and sync versions of tests that write a value removing the comment above it
+    macro_rules! write_value_removing_comment_eq {
+        {
+            test_name = $test_name:ident,
+            input = $input:expr,
+            comment = $comment:expr,
+            expected = $expected:expr,
+            removed = $removed:expr $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = IniParser::default();
+                let mut reader = std::io::Cursor::new($input);
+                let mut dest = Vec::new();
+                let removed = parser
+                    .write_value_removing_comment(
+                        &mut reader,
+                        &mut dest,
+                        Some("server"),
+                        "max",
+                        "50",
+                        $comment,
+                    )
+                    .unwrap();
+                let value = String::from_utf8(dest).unwrap();
+                assert_eq_preserve_new_lines!(value, $expected);
+                assert_eq!(removed, $removed);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    let parser = IniParser::default();
+                    let mut reader = std::io::Cursor::new($input);
+                    let mut dest = Vec::new();
+                    let removed = parser
+                        .write_value_removing_comment_async(
+                            &mut reader,
+                            &mut dest,
+                            Some("server"),
+                            "max",
+                            "50",
+                            $comment,
+                        )
+                        .await
+                        .unwrap();
+                    let value = String::from_utf8(dest).unwrap();
+                    assert_eq_preserve_new_lines!(value, $expected);
+                    assert_eq!(removed, $removed);
+                }
+            }
+        };
+    }
+
+    write_value_removing_comment_eq! {
+        test_name=write_value_removing_comment_no_trailing_newline,
+        input="[server]\nkey=value",
+        comment="disabled",
+        expected="[server]\nkey=value\nmax=50\n",
+        removed=false,
+    }
+
+    write_value_removing_comment_eq! {
+        test_name=write_value_removing_comment,
         input=indoc!{"
-            [unicode]
-            key=áéíóúñ
+            [server]
+            name=test
+            # Disabled
+            max=40
         "},
-        section=Some("unicode"),
-        key="key",
-        value="αβγδεζηθ",
+        comment="disabled",
         expected=indoc!{"
-            [unicode]
-            key=αβγδεζηθ
+            [server]
+            name=test
+            max=50
         "},
-        description="values should allow unicode characters",
+        removed=true,
     }
 
-    write_value_eq! {
-        test_name=write_value_very_long_value,
+    write_value_removing_comment_eq! {
+        test_name=write_value_removing_comment_other_comment,
         input=indoc!{"
-            [section]
-            key=short value
+            [server]
+            # Maximum number of players
+            max=40
         "},
-        section=Some("section"),
-        key="key",
-        value="This is a very long value that contains many characters and should be properly handled by the parser. It includes multiple sentences and various punctuation marks. The value is intentionally made long to test the parser's ability to handle large values without issues.",
+        comment="disabled",
         expected=indoc!{"
-            [section]
-            key=This is a very long value that contains many characters and should be properly handled by the parser. It includes multiple sentences and various punctuation marks. The value is intentionally made long to test the parser's ability to handle large values without issues.
+            [server]
+            # Maximum number of players
+            max=50
         "},
-        description="values should allow very long values",
+        removed=false,
     }
 
-    write_value_eq! {
-        test_name=write_value_duplicate_keys_first,
+    write_value_removing_comment_eq! {
+        test_name=write_value_removing_comment_not_directly_above,
         input=indoc!{"
-            [section]
-            key=first value
-            other=other value
-            key=second value
+            [server]
+            # disabled
+
+            max=40
         "},
-        section=Some("section"),
-        key="key",
-        value="new value",
+        comment="disabled",
         expected=indoc!{"
-            [section]
-            key=new value
-            other=other value
-            key=second value
+            [server]
+            # disabled
+
+            max=50
         "},
-        description="first key should be updated when using DuplicateKeyStrategy::UseFirst, other keys should be left alone",
-        parser=IniParser{duplicate_keys: DuplicateKeyStrategy::UseFirst,..Default::default()},
+        removed=false,
+    }
+
+    write_value_eq! {
+        test_name=write_value_detect_crlf,
+        input="[server]\r\nhost=localhost\r\n",
+        section=Some("server"),
+        key="port",
+        value="8080",
+        expected="[server]\r\nhost=localhost\r\nport=8080\r\n",
+        description="new keys should use the line ending the rest of the file uses",
+    }
+
+    write_value_eq! {
+        test_name=write_value_detect_crlf_new_section,
+        input="[server]\r\nhost=localhost\r\n",
+        section=Some("client"),
+        key="name",
+        value="tom",
+        expected="[server]\r\nhost=localhost\r\n[client]\r\nname=tom\r\n",
+        description="new sections should use the line ending the rest of the file uses",
+    }
+
+    write_value_eq! {
+        test_name=write_value_detect_mostly_lf,
+        input="a=1\r\nb=2\nc=3\n",
+        section=None,
+        key="d",
+        value="4",
+        expected="a=1\r\nb=2\nc=3\nd=4\n",
+        description="the most common line ending should be used",
+    }
+
+    write_value_eq! {
+        test_name=write_value_detect_empty,
+        input="",
+        section=Some("server"),
+        key="port",
+        value="8080",
+        expected="[server]\nport=8080\n",
+        description="empty files should fall back to LF",
+    }
+
+    write_value_eq! {
+        test_name=write_value_newline_lf_overrides_detect,
+        input="[server]\r\nhost=localhost\r\n",
+        section=Some("server"),
+        key="port",
+        value="8080",
+        expected="[server]\r\nhost=localhost\r\nport=8080\n",
+        description="an explicit newline setting should be used even if the file uses another",
+        parser=IniParser{newline: Newline::Lf, ..Default::default()},
+    }
+
+    #[test]
+    fn set_section_values_detect_crlf() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[server]\r\nhost=localhost\r\n");
+        let mut dest = Vec::new();
+        parser
+            .set_section_values(&mut reader, &mut dest, Some("server"), &[("port", "8080")])
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq_preserve_new_lines!(value, "[server]\r\nhost=localhost\r\nport=8080\r\n");
+    }
+
+    write_value_eq! {
+        test_name=write_value_escape_sequences,
+        input="path=old\n",
+        section=None,
+        key="path",
+        value="C:\\temp\nline \"two\"",
+        expected="path=C:\\\\temp\\nline \\\"two\\\"\n",
+        description="special characters should be escaped when escape sequences are enabled",
+        parser=IniParser{escape_sequences: true, ..Default::default()},
+    }
+
+    #[test]
+    fn escape_sequences_roundtrip() {
+        let parser = IniParser {
+            escape_sequences: true,
+            ..Default::default()
+        };
+        let value = "C:\\temp\\new\tfolder\r\n\"quoted\"";
+        let mut reader = std::io::Cursor::new("[paths]\npath=old\n");
+        let mut dest = Vec::new();
+        parser
+            .write_value(&mut reader, &mut dest, Some("paths"), "path", value)
+            .unwrap();
+        let read: Option<String> = parser
+            .read_value(dest.as_slice(), Some("paths"), "path")
+            .unwrap();
+        assert_eq!(read.as_deref(), Some(value));
+    }
+
+    #[test]
+    fn write_value_returning_old_replaced() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[server]\nport = 8080 ; comment\n");
+        let mut dest = Vec::new();
+        let old = parser
+            .write_value_returning_old(&mut reader, &mut dest, Some("server"), "port", "9090")
+            .unwrap();
+        assert_eq!(old.as_deref(), Some("8080"));
+        assert_eq!(
+            String::from_utf8(dest).unwrap(),
+            "[server]\nport = 9090 ; comment\n"
+        );
+    }
+
+    #[test]
+    fn write_value_returning_old_added() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[server]\nport=8080\n");
+        let mut dest = Vec::new();
+        let old = parser
+            .write_value_returning_old(&mut reader, &mut dest, Some("server"), "host", "localhost")
+            .unwrap();
+        assert_eq!(old, None);
+        assert_eq!(
+            String::from_utf8(dest).unwrap(),
+            "[server]\nport=8080\nhost=localhost\n"
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn write_value_returning_old_async() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[server]\nport=8080\n");
+        let mut dest = Vec::new();
+        let old = parser
+            .write_value_returning_old_async(&mut reader, &mut dest, Some("server"), "port", "9090")
+            .await
+            .unwrap();
+        assert_eq!(old.as_deref(), Some("8080"));
+        let old = parser
+            .write_value_returning_old_async(
+                &mut std::io::Cursor::new(dest),
+                Vec::new(),
+                Some("server"),
+                "host",
+                "localhost",
+            )
+            .await
+            .unwrap();
+        assert_eq!(old, None);
+    }
+
+    #[test]
+    fn preview_write() {
+        let parser = IniParser::default();
+        let source = "[server]\nport=8080\n";
+        let old = parser
+            .preview_write(source.as_bytes(), Some("server"), "port")
+            .unwrap();
+        assert_eq!(old.as_deref(), Some("8080"));
+        let old = parser
+            .preview_write(source.as_bytes(), Some("server"), "host")
+            .unwrap();
+        assert_eq!(old, None);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn preview_write_async() {
+        let parser = IniParser::default();
+        let source = "[server]\nport=8080\n";
+        let old = parser
+            .preview_write_async(source.as_bytes(), Some("server"), "port")
+            .await
+            .unwrap();
+        assert_eq!(old.as_deref(), Some("8080"));
+    }
+
+    #[test]
+    fn write_value_outcome() {
+        let parser = IniParser::default();
+        let source = "global=1\n[server]\nport=8080\n";
+        let cases = [
+            (None, "global", WriteOutcome::Replaced),
+            (None, "other", WriteOutcome::AppendedToSection),
+            (Some("server"), "port", WriteOutcome::Replaced),
+            (Some("server"), "host", WriteOutcome::AppendedToSection),
+            (Some("client"), "host", WriteOutcome::CreatedSection),
+        ];
+        for (section, key, expected) in cases {
+            let outcome = parser
+                .write_value_outcome(
+                    &mut std::io::Cursor::new(source),
+                    std::io::sink(),
+                    section,
+                    key,
+                    "value",
+                )
+                .unwrap();
+            assert_eq!(outcome, expected, "writing {section:?} {key}");
+        }
+    }
+
+    #[test]
+    fn write_value_outcome_empty_source() {
+        let parser = IniParser::default();
+        let mut dest = Vec::new();
+        let outcome = parser
+            .write_value_outcome(
+                &mut std::io::Cursor::new(""),
+                &mut dest,
+                Some("server"),
+                "port",
+                "8080",
+            )
+            .unwrap();
+        assert_eq!(outcome, WriteOutcome::CreatedSection);
+        assert_eq!(String::from_utf8(dest).unwrap(), "[server]\nport=8080\n");
     }
 
-    write_value_eq! {
-        test_name=write_value_duplicate_sections,
-        input=indoc!{"
-            [section]
-            key=first value
-            [other]
-            key=other value
-            [section]
-            key=second value
-        "},
-        section=Some("section"),
-        key="key",
-        value="new value",
-        expected=indoc!{"
-            [section]
-            key=new value
-            [other]
-            key=other value
-            [section]
-            key=second value
-        "},
-        description="first section should be updated when using DuplicateKeyStrategy::UseFirst, other sections should be left alone",
-        parser=IniParser{duplicate_keys: DuplicateKeyStrategy::UseFirst,..Default::default()},
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn write_value_outcome_async() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[server]\nport=8080\n");
+        let outcome = parser
+            .write_value_outcome_async(&mut reader, std::io::sink(), Some("server"), "host", "a")
+            .await
+            .unwrap();
+        assert_eq!(outcome, WriteOutcome::AppendedToSection);
     }
 
-    write_value_eq! {
-        test_name=write_value_nested_sections,
-        input=indoc!{"
-            [parent]
-            key=parent value
-            [parent.child]
-            key=child value
-        "},
-        section=Some("parent.child"),
-        key="key",
-        value="new child value",
-        expected=indoc!{"
-            [parent]
-            key=parent value
-            [parent.child]
-            key=new child value
-        "},
-        description="nested sections should work the same as other sections and not affect the \"parent\" section",
+    macro_rules! append_value_eq {
+        {
+            test_name = $test_name:ident,
+            input = $input:expr,
+            section = $section:expr,
+            key = $key:expr,
+            value = $value:expr,
+            expected = $expected:expr $(,)?
+        } => {
+            #[test]
+            fn $test_name() {
+                let parser = IniParser::default();
+                let mut reader = std::io::Cursor::new($input);
+                let mut dest = Vec::new();
+                parser
+                    .append_value(&mut reader, &mut dest, $section, $key, $value)
+                    .unwrap();
+                let value = String::from_utf8(dest).unwrap();
+                assert_eq_preserve_new_lines!(value, $expected);
+            }
+
+            #[cfg(feature = "async")]
+            paste! {
+                #[tokio::test]
+                async fn [<$test_name _async>]() {
+                    let parser = IniParser::default();
+                    let mut reader = std::io::Cursor::new($input);
+                    let mut dest = Vec::new();
+                    parser
+                        .append_value_async(&mut reader, &mut dest, $section, $key, $value)
+                        .await
+                        .unwrap();
+                    let value = String::from_utf8(dest).unwrap();
+                    assert_eq_preserve_new_lines!(value, $expected);
+                }
+            }
+        };
     }
 
-    write_value_eq! {
-        test_name=write_value_whitespace_in_section,
-        input=indoc!{"
-            [ section with spaces ]
-            key=value
-        "},
-        section=Some(" section with spaces "),
-        key="key",
-        value="new value",
-        expected=indoc!{"
-            [ section with spaces ]
-            key=new value
-        "},
-        description="whitespace around section names should not be significant",
+    append_value_eq! {
+        test_name=append_value_no_trailing_newline,
+        input="[s]\nkey=value",
+        section=Some("s"),
+        key="new",
+        value="x",
+        expected="[s]\nkey=value\nnew=x\n",
     }
 
-    write_value_eq! {
-        test_name=write_value_whitespace_in_key_value,
+    append_value_eq! {
+        test_name=append_value_new_section_no_trailing_newline,
+        input="[s]\nkey=value",
+        section=Some("t"),
+        key="new",
+        value="x",
+        expected="[s]\nkey=value\n[t]\nnew=x\n",
+    }
+
+    append_value_eq! {
+        test_name=append_value_existing_key,
         input=indoc!{"
-            [section]
-            key with spaces = value
+            [/Script/Engine.Engine]
+            +ActiveGameNameRedirects=(OldGameName=\"A\")
+            Other=1
+
+            [Next]
         "},
-        section=Some("section"),
-        key="key with spaces ",
-        value="new value",
+        section=Some("/Script/Engine.Engine"),
+        key="+ActiveGameNameRedirects",
+        value="(OldGameName=\"B\")",
         expected=indoc!{"
-            [section]
-            key with spaces = new value
+            [/Script/Engine.Engine]
+            +ActiveGameNameRedirects=(OldGameName=\"A\")
+            Other=1
+            +ActiveGameNameRedirects=(OldGameName=\"B\")
+
+            [Next]
         "},
-        description="whitespace around keys and values should be preserved",
     }
 
-    write_value_eq! {
-        test_name=write_value_quoted_values,
+    append_value_eq! {
+        test_name=append_value_global,
         input=indoc!{"
+            path=a
             [section]
-            key=\"quoted value\"
+            path=c
         "},
-        section=Some("section"),
-        key="key",
-        value="\"new quoted value\"",
+        section=None,
+        key="path",
+        value="b",
         expected=indoc!{"
+            path=a
+            path=b
             [section]
-            key=\"new quoted value\"
+            path=c
         "},
-        description="quoted values should be preserved when writing a value",
     }
 
-    write_value_eq! {
-        test_name=write_value_multiple_comments,
+    append_value_eq! {
+        test_name=append_value_new_section,
         input=indoc!{"
-            # Global comment
-            [section] # Section comment
-            key=value # Key comment
+            name=tom
         "},
-        section=Some("section"),
-        key="key",
-        value="new value",
+        section=Some("paths"),
+        key="path",
+        value="a",
         expected=indoc!{"
-            # Global comment
-            [section] # Section comment
-            key=new value # Key comment
+            name=tom
+            [paths]
+            path=a
         "},
-        description="multiple comments should be preserved when writing a value",
     }
-    write_value_eq! {
-        test_name=add_key_to_section_trailing_empty_lines,
-        input=indoc!{"
-            [section]
-            key=value
-
-            [section2]
-            key=value2
-        "},
-        section=Some("section"),
-        key="key2",
-        value="new value",
-        expected=indoc!{"
-            [section]
-            key=value
-            key2=new value
 
-            [section2]
-            key=value2
-        "},
-        description="adding a key to a section should insert it before any trailing empty lines",
+    #[test]
+    fn write_value_dedup() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(indoc! {"
+            [server]
+            port=80 # first
+            host=localhost
+            port=8080
+            [other]
+            port=1
+            [server]
+            port=9090
+        "});
+        let mut dest = Vec::new();
+        let removed = parser
+            .write_value_dedup(&mut reader, &mut dest, Some("server"), "port", "443")
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq_preserve_new_lines!(
+            value,
+            indoc! {"
+                [server]
+                port=443 # first
+                host=localhost
+                [other]
+                port=1
+                [server]
+            "}
+        );
+        assert_eq!(removed, 2);
     }
 
-    write_value_eq! {
-        test_name=add_key_to_global_trailing_empty_lines,
-        input=indoc!{"
-            # Global comment
-
-
-            [section]
-            key=value
-
-            [section2]
-            key=value2
-        "},
-        section=None,
-        key="key2",
-        value="new value",
-        expected=indoc!{"
-            # Global comment
-            key2=new value
-
-
-            [section]
-            key=value
+    #[test]
+    fn write_value_dedup_new_key() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[server]\nhost=localhost\n");
+        let mut dest = Vec::new();
+        let removed = parser
+            .write_value_dedup(&mut reader, &mut dest, Some("server"), "port", "443")
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq!(value, "[server]\nhost=localhost\nport=443\n");
+        assert_eq!(removed, 0);
+    }
 
-            [section2]
-            key=value2
-        "},
-        description="adding a key to the global section should insert it before any trailing empty lines",
+    #[test]
+    fn write_value_dedup_no_trailing_newline() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[s]\nkey=value");
+        let mut dest = Vec::new();
+        parser
+            .write_value_dedup(&mut reader, &mut dest, Some("s"), "new", "x")
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq!(value, "[s]\nkey=value\nnew=x\n");
     }
 
-    write_value_eq! {
-        test_name=add_key_to_last_section_trailing_empty_lines,
-        input=indoc!{"
-            [section]
-            key=value
+    #[test]
+    fn value_reader() {
+        let blob = "0123456789".repeat(10_000);
+        let ini = format!("[data]\nblob = {blob} # comment\nother = 1\n");
+        let parser = IniParser::default();
+        let mut reader = parser
+            .value_reader(std::io::Cursor::new(&ini), Some("data"), "blob")
+            .unwrap()
+            .unwrap();
+        let mut value = Vec::new();
+        let mut chunk = [0; 7];
+        loop {
+            let read = std::io::Read::read(&mut reader, &mut chunk).unwrap();
+            if read == 0 {
+                break;
+            }
+            value.extend_from_slice(&chunk[..read]);
+        }
+        assert_eq!(String::from_utf8(value).unwrap(), blob);
+        let missing = parser
+            .value_reader(std::io::Cursor::new(&ini), Some("data"), "missing")
+            .unwrap();
+        assert!(missing.is_none());
+    }
 
-            [section2]
-            key=value2
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn value_reader_async() {
+        let parser = IniParser::default();
+        let mut reader = parser
+            .value_reader_async(std::io::Cursor::new("a = 1\nb = two\n"), None, "b")
+            .await
+            .unwrap()
+            .unwrap();
+        let mut value = String::new();
+        reader.read_to_string(&mut value).await.unwrap();
+        assert_eq!(value, "two");
+    }
 
+    const NTH_SECTION_INI: &str = indoc! {"
+        [contact]
+        name=tom
+        [other]
+        name=jerry
+        [contact]
+        name=bill
+        email=bill@example.com
+    "};
 
+    #[test]
+    fn write_value_in_section_nth() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(NTH_SECTION_INI);
+        let mut dest = Vec::new();
+        let written = parser
+            .write_value_in_section_nth(&mut reader, &mut dest, "contact", 1, "name", "sam")
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq_preserve_new_lines!(value, NTH_SECTION_INI.replace("bill\n", "sam\n"));
+        assert!(written);
+    }
 
-        "},
-        section=Some("section2"),
-        key="key2",
-        value="new value",
-        expected=indoc!{"
-            [section]
-            key=value
+    #[test]
+    fn write_value_in_section_nth_new_key() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(NTH_SECTION_INI);
+        let mut dest = Vec::new();
+        parser
+            .write_value_in_section_nth(&mut reader, &mut dest, "contact", 0, "email", "a@b.c")
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq_preserve_new_lines!(
+            value,
+            NTH_SECTION_INI.replace("name=tom\n", "name=tom\nemail=a@b.c\n")
+        );
+    }
 
-            [section2]
-            key=value2
-            key2=new value
+    #[test]
+    fn write_value_in_section_nth_no_trailing_newline() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("[s]\na=1\n[s]\nkey=value");
+        let mut dest = Vec::new();
+        parser
+            .write_value_in_section_nth(&mut reader, &mut dest, "s", 1, "new", "x")
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq!(value, "[s]\na=1\n[s]\nkey=value\nnew=x\n");
+    }
 
+    #[test]
+    fn write_value_in_section_nth_missing() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(NTH_SECTION_INI);
+        let mut dest = Vec::new();
+        let written = parser
+            .write_value_in_section_nth(&mut reader, &mut dest, "contact", 2, "name", "sam")
+            .unwrap();
+        assert_eq!(String::from_utf8(dest).unwrap(), NTH_SECTION_INI);
+        assert!(!written);
+    }
 
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn write_value_in_section_nth_async() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new(NTH_SECTION_INI);
+        let mut dest = Vec::new();
+        parser
+            .write_value_in_section_nth_async(&mut reader, &mut dest, "contact", 1, "name", "sam")
+            .await
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq_preserve_new_lines!(value, NTH_SECTION_INI.replace("bill\n", "sam\n"));
+    }
 
-        "},
-        description="adding a key to the last section should insert it before any trailing empty lines",
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn write_value_dedup_async() {
+        let parser = IniParser::default();
+        let mut reader = std::io::Cursor::new("port=1\nport=2\nport=3\n");
+        let mut dest = Vec::new();
+        let removed = parser
+            .write_value_dedup_async(&mut reader, &mut dest, None, "port", "4")
+            .await
+            .unwrap();
+        let value = String::from_utf8(dest).unwrap();
+        assert_eq!(value, "port=4\n");
+        assert_eq!(removed, 2);
     }
 }